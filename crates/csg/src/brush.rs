@@ -1,6 +1,6 @@
 use super::PLANE_EPSILON;
 use super::{Csg, Location, Plane, Polygon, Vertex};
-use bevy::prelude::*;
+use bevy::{math::Affine3A, prelude::*};
 use serde::{Deserialize, Serialize};
 
 const BASE_POLYGON_SIZE: f32 = 1024.0 * 8.0;
@@ -20,6 +20,48 @@ impl Brush {
         }
     }
 
+    /// axis aligned box between min and max
+    pub fn from_bounds(min: Vec3, max: Vec3) -> Self {
+        let planes = vec![
+            Plane::new(Vec3::X, max.x),
+            Plane::new(-Vec3::X, -min.x),
+            Plane::new(Vec3::Y, max.y),
+            Plane::new(-Vec3::Y, -min.y),
+            Plane::new(Vec3::Z, max.z),
+            Plane::new(-Vec3::Z, -min.z),
+        ];
+        Brush::from_planes(planes)
+    }
+
+    /// prism from a convex outline, extruded along `axis` (unit length) between `min` and `max`. The component of the
+    /// outline points along `axis` is ignored.
+    pub fn extruded(outline: &[Vec3], axis: Vec3, min: f32, max: f32) -> Self {
+        let center = outline.iter().sum::<Vec3>() / outline.len() as f32;
+        let mut planes = vec![Plane::new(axis, max), Plane::new(-axis, -min)];
+        for (i, p0) in outline.iter().enumerate() {
+            let p1 = outline[(i + 1) % outline.len()];
+            let mut normal = (p1 - *p0).cross(axis).normalize();
+            // side planes point away from the center, independent of the winding
+            if normal.dot(center - *p0) > 0.0 {
+                normal = -normal;
+            }
+            planes.push(Plane::new(normal, normal.dot(*p0)));
+        }
+        Brush::from_planes(planes)
+    }
+
+    /// brush with all planes transformed, e.g. for rotating or scaling it around a pivot
+    pub fn transformed(&self, transform: &Affine3A) -> Self {
+        Brush {
+            planes: self
+                .planes
+                .iter()
+                .map(|plane| plane.transformed(transform))
+                .collect(),
+            appearances: self.appearances.clone(),
+        }
+    }
+
     /// get planes that are affected by a drag starting at this ray
     pub fn get_planes_behind_ray(&self, ray: Ray3d) -> Vec<(usize, f32)> {
         let mut res = Vec::new();
@@ -180,8 +222,22 @@ impl Default for Brush {
 #[derive(Debug)]
 pub enum BrushError {
     Degenerated(Brush),
+    NonConvex,
 }
 
+impl std::fmt::Display for BrushError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrushError::Degenerated(brush) => {
+                write!(f, "degenerated brush ({} planes)", brush.planes.len())
+            }
+            BrushError::NonConvex => write!(f, "brush is not convex"),
+        }
+    }
+}
+
+impl std::error::Error for BrushError {}
+
 impl TryFrom<Brush> for Csg {
     type Error = BrushError;
 
@@ -225,3 +281,46 @@ fn test_csg_from_brush() {
 
     println!("{:?}", csg);
 }
+
+#[test]
+fn test_brush_from_bounds() {
+    let brush = Brush::from_bounds(Vec3::new(1.0, -2.0, 0.0), Vec3::new(3.0, 2.0, 0.5));
+    let csg: Csg = brush.try_into().unwrap();
+    let (center, _) = csg.bounding_sphere();
+    assert!((center - Vec3::new(2.0, 0.0, 0.25)).length() < 1e-4);
+}
+
+#[test]
+fn test_brush_extruded() {
+    let outline = [
+        Vec3::new(0.0, 5.0, 0.0),
+        Vec3::new(2.0, 5.0, 0.0),
+        Vec3::new(0.0, 5.0, 2.0),
+    ];
+    let brush = Brush::extruded(&outline, Vec3::Y, -1.0, 1.0);
+    assert_eq!(brush.planes.len(), 5);
+    let csg: Csg = brush.try_into().unwrap();
+    let (center, _) = csg.bounding_sphere();
+    assert!(center.y.abs() < 1e-4);
+}
+
+#[test]
+fn test_brush_transformed() {
+    let brush = Brush::from_bounds(Vec3::new(1.0, 0.0, 0.0), Vec3::new(3.0, 1.0, 1.0));
+
+    // quarter turn around the y axis through the origin
+    let rotation = Affine3A::from_rotation_y(std::f32::consts::FRAC_PI_2);
+    let csg: Csg = brush.transformed(&rotation).try_into().unwrap();
+    let (center, _) = csg.bounding_sphere();
+    assert!((center - Vec3::new(0.5, 0.5, -2.0)).length() < 1e-4);
+
+    // non-uniform scale around x = 1, mirrored along z
+    let scale = Affine3A::from_translation(Vec3::X)
+        * Affine3A::from_scale(Vec3::new(2.0, 1.0, -1.0))
+        * Affine3A::from_translation(-Vec3::X);
+    let transformed = brush.transformed(&scale);
+    let csg: Csg = transformed.clone().try_into().unwrap();
+    let (center, _) = csg.bounding_sphere();
+    assert!((center - Vec3::new(3.0, 0.5, -0.5)).length() < 1e-4);
+    assert_eq!(transformed.appearances, brush.appearances);
+}
@@ -0,0 +1,186 @@
+use bevy::prelude::*;
+
+const EPSILON: f32 = 1e-5;
+
+/// decompose a simple (i.e. not self-intersecting) 2d polygon into convex parts. The parts are returned as counter
+/// clockwise index lists into `points`. Returns None if the outline could not be triangulated (e.g. because it is
+/// self-intersecting).
+///
+/// Ear clipping followed by Hertel-Mehlhorn merging, so there are at most four times as many parts as needed.
+pub fn convex_decomposition(points: &[Vec2]) -> Option<Vec<Vec<usize>>> {
+    if points.len() < 3 || self_intersecting(points) {
+        return None;
+    }
+    let mut outline = (0..points.len()).collect::<Vec<_>>();
+    if signed_area(points, &outline) < 0.0 {
+        outline.reverse();
+    }
+    let mut parts = triangulate(points, outline)?;
+
+    // merge neighboring parts as long as the result stays convex
+    'merge: loop {
+        for i in 0..parts.len() {
+            for j in (i + 1)..parts.len() {
+                if let Some(merged) = merge(points, &parts[i], &parts[j]) {
+                    parts[i] = merged;
+                    parts.swap_remove(j);
+                    continue 'merge;
+                }
+            }
+        }
+        break;
+    }
+    for part in &mut parts {
+        remove_collinear(points, part);
+    }
+    Some(parts)
+}
+
+fn signed_area(points: &[Vec2], polygon: &[usize]) -> f32 {
+    let mut area = 0.0;
+    for (i, a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        area += points[*a].perp_dot(points[b]);
+    }
+    area * 0.5
+}
+
+fn self_intersecting(points: &[Vec2]) -> bool {
+    let n = points.len();
+    let edge = |i: usize| (points[i], points[(i + 1) % n]);
+    // proper crossings of non-adjacent edges
+    let crosses = |(a, b): (Vec2, Vec2), (c, d): (Vec2, Vec2)| {
+        turn(a, b, c) * turn(a, b, d) < -EPSILON && turn(c, d, a) * turn(c, d, b) < -EPSILON
+    };
+    (0..n).any(|i| ((i + 2)..n).any(|j| (i + n - j) % n != 1 && crosses(edge(i), edge(j))))
+}
+
+// > 0 for a left turn a -> b -> c
+fn turn(a: Vec2, b: Vec2, c: Vec2) -> f32 {
+    (b - a).perp_dot(c - b)
+}
+
+fn in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    turn(a, b, p) >= -EPSILON && turn(b, c, p) >= -EPSILON && turn(c, a, p) >= -EPSILON
+}
+
+// ear clipping of a counter clockwise polygon
+fn triangulate(points: &[Vec2], mut outline: Vec<usize>) -> Option<Vec<Vec<usize>>> {
+    let mut triangles = Vec::new();
+    while outline.len() > 3 {
+        let n = outline.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (outline[(i + n - 1) % n], outline[i], outline[(i + 1) % n]);
+            let (pa, pb, pc) = (points[a], points[b], points[c]);
+            if turn(pa, pb, pc) <= EPSILON {
+                return false;
+            }
+            // no other vertex may be inside the ear
+            outline.iter().filter(|v| ![a, b, c].contains(v)).all(|v| {
+                points[*v] == pa || points[*v] == pc || !in_triangle(points[*v], pa, pb, pc)
+            })
+        });
+        match ear {
+            Some(i) => {
+                triangles.push(vec![
+                    outline[(i + n - 1) % n],
+                    outline[i],
+                    outline[(i + 1) % n],
+                ]);
+                outline.remove(i);
+            }
+            None => {
+                // only collinear vertices left (i.e. nothing with an area), otherwise the outline is broken
+                let n = outline.len();
+                let collinear = (0..n).find(|&i| {
+                    let (a, b, c) = (outline[(i + n - 1) % n], outline[i], outline[(i + 1) % n]);
+                    turn(points[a], points[b], points[c]).abs() <= EPSILON
+                })?;
+                outline.remove(collinear);
+            }
+        }
+    }
+    if outline.len() == 3 && signed_area(points, &outline) > EPSILON {
+        triangles.push(outline);
+    }
+    (!triangles.is_empty()).then_some(triangles)
+}
+
+// merge two counter clockwise polygons along a shared edge, if the result is convex
+fn merge(points: &[Vec2], p: &[usize], q: &[usize]) -> Option<Vec<usize>> {
+    // edge a -> b of p is b -> a in q
+    let (i, j) = (0..p.len()).find_map(|i| {
+        let (a, b) = (p[i], p[(i + 1) % p.len()]);
+        (0..q.len())
+            .find(|&j| q[j] == b && q[(j + 1) % q.len()] == a)
+            .map(|j| (i, j))
+    })?;
+    // p starting after a (i.e. at b) around to a, then q from after a up to before b
+    let mut merged = Vec::with_capacity(p.len() + q.len() - 2);
+    merged.extend((1..=p.len()).map(|k| p[(i + k) % p.len()]));
+    merged.extend((2..q.len()).map(|k| q[(j + k) % q.len()]));
+
+    let n = merged.len();
+    let convex = (0..n).all(|k| {
+        let (a, b, c) = (merged[(k + n - 1) % n], merged[k], merged[(k + 1) % n]);
+        turn(points[a], points[b], points[c]) >= -EPSILON
+    });
+    convex.then_some(merged)
+}
+
+fn remove_collinear(points: &[Vec2], polygon: &mut Vec<usize>) {
+    let mut k = 0;
+    while polygon.len() > 3 && k < polygon.len() {
+        let n = polygon.len();
+        let (a, b, c) = (polygon[(k + n - 1) % n], polygon[k], polygon[(k + 1) % n]);
+        if turn(points[a], points[b], points[c]).abs() <= EPSILON {
+            polygon.remove(k);
+        } else {
+            k += 1;
+        }
+    }
+}
+
+#[test]
+fn test_convex_decomposition() {
+    let area = |points: &[Vec2], parts: &[Vec<usize>]| -> f32 {
+        parts.iter().map(|part| signed_area(points, part)).sum()
+    };
+
+    // convex outlines stay in one piece, regardless of the winding
+    let mut square = vec![
+        Vec2::new(0.0, 0.0),
+        Vec2::new(2.0, 0.0),
+        Vec2::new(2.0, 2.0),
+        Vec2::new(0.0, 2.0),
+    ];
+    let parts = convex_decomposition(&square).unwrap();
+    assert_eq!(parts.len(), 1);
+    assert_eq!(parts[0].len(), 4);
+    square.reverse();
+    let parts = convex_decomposition(&square).unwrap();
+    assert_eq!(parts.len(), 1);
+    assert!((area(&square, &parts) - 4.0).abs() < 1e-4);
+
+    // L-shape needs two parts
+    let l_shape = [
+        Vec2::new(0.0, 0.0),
+        Vec2::new(3.0, 0.0),
+        Vec2::new(3.0, 1.0),
+        Vec2::new(1.0, 1.0),
+        Vec2::new(1.0, 3.0),
+        Vec2::new(0.0, 3.0),
+    ];
+    let parts = convex_decomposition(&l_shape).unwrap();
+    assert_eq!(parts.len(), 2);
+    assert!((area(&l_shape, &parts) - 5.0).abs() < 1e-4);
+
+    // self-intersecting
+    let bow_tie = [
+        Vec2::new(0.0, 0.0),
+        Vec2::new(2.0, 2.0),
+        Vec2::new(2.0, 0.0),
+        Vec2::new(0.0, 2.0),
+    ];
+    assert!(convex_decomposition(&bow_tie).is_none());
+}
@@ -42,7 +42,7 @@
 // Original code and comments copyright (c) 2011 Evan Wallace (http://madebyevan.com/), under the MIT license.
 
 use bevy::{
-    math::FloatOrd,
+    math::{Affine3A, FloatOrd, Vec3A},
     prelude::*,
     render::{mesh::Indices, primitives::Aabb, render_resource::PrimitiveTopology},
     utils::HashMap,
@@ -62,7 +62,13 @@ use serde::{Deserialize, Serialize};
 pub use sphere::Sphere;
 
 mod brush;
-pub use brush::Brush;
+pub use brush::{Brush, BrushError};
+
+mod decompose;
+pub use decompose::convex_decomposition;
+
+mod topology;
+pub use topology::BrushTopology;
 
 pub mod texgen;
 use self::texgen::Texgen;
@@ -167,6 +173,18 @@ impl Plane {
         }
     }
 
+    /// plane transformed by an affine transform. Normals are transformed by the inverse transpose, so this also
+    /// works for non-uniform (and mirroring) scale.
+    pub fn transformed(&self, transform: &Affine3A) -> Plane {
+        let normal_matrix = transform.matrix3.inverse().transpose();
+        let normal = Vec3::from(normal_matrix * Vec3A::from(self.normal)).normalize();
+        let point = transform.transform_point3(self.normal * self.w);
+        Plane {
+            normal,
+            w: normal.dot(point),
+        }
+    }
+
     pub fn location_of_polygon(&self, polygon: &Polygon) -> Location {
         let mut polygon_type = Location::NONE;
 
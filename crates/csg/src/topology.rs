@@ -0,0 +1,246 @@
+use super::{Brush, BrushError, Csg, Plane, PLANE_EPSILON};
+use bevy::prelude::*;
+
+// the polygons of a brush are cut from huge base polygons, so the vertices of neighboring faces can be off by more
+// than PLANE_EPSILON
+const VERTEX_EPSILON: f32 = 1e-2;
+
+/// vertices, edges and faces of a brush, derived from its planes
+#[derive(Debug, Clone, Default)]
+pub struct BrushTopology {
+    pub vertices: Vec<Vec3>,
+    pub edges: Vec<(usize, usize)>,
+    /// index of the brush plane and the counter clockwise (seen from the outside) vertex loop of each face
+    pub faces: Vec<(usize, Vec<usize>)>,
+}
+
+impl BrushTopology {
+    fn vertex_index(&mut self, position: Vec3) -> usize {
+        if let Some(i) = self
+            .vertices
+            .iter()
+            .position(|v| v.distance(position) < VERTEX_EPSILON)
+        {
+            return i;
+        }
+        self.vertices.push(position);
+        self.vertices.len() - 1
+    }
+}
+
+impl Brush {
+    pub fn topology(&self) -> BrushTopology {
+        let (polygons, degenerated) = self.get_polygons();
+        let plane_indices = (0..self.planes.len()).filter(|i| !degenerated.contains(i));
+
+        let mut topology = BrushTopology::default();
+        for (plane, polygon) in plane_indices.zip(&polygons) {
+            let mut face = polygon
+                .vertices
+                .iter()
+                .map(|v| topology.vertex_index(v.position))
+                .collect::<Vec<_>>();
+            face.dedup();
+            if face.len() > 1 && face.first() == face.last() {
+                face.pop();
+            }
+            if face.len() < 3 {
+                continue;
+            }
+            for (i, a) in face.iter().enumerate() {
+                let b = face[(i + 1) % face.len()];
+                let edge = ((*a).min(b), (*a).max(b));
+                if !topology.edges.contains(&edge) {
+                    topology.edges.push(edge);
+                }
+            }
+            topology.faces.push((plane, face));
+        }
+
+        // exact positions from the intersection of three of the adjacent planes
+        for (i, vertex) in topology.vertices.iter_mut().enumerate() {
+            let planes = topology
+                .faces
+                .iter()
+                .filter(|(_, face)| face.contains(&i))
+                .map(|(plane, _)| &self.planes[*plane])
+                .collect::<Vec<_>>();
+            let n = planes.len();
+            let exact = (0..n)
+                .flat_map(|a| ((a + 1)..n).flat_map(move |b| ((b + 1)..n).map(move |c| (a, b, c))))
+                .find_map(|(a, b, c)| {
+                    let (a, b, c) = (planes[a], planes[b], planes[c]);
+                    let m = Mat3::from_cols(a.normal, b.normal, c.normal).transpose();
+                    (m.determinant().abs() > 0.01).then(|| m.inverse() * Vec3::new(a.w, b.w, c.w))
+                });
+            if let Some(exact) = exact {
+                *vertex = exact;
+            }
+        }
+        topology
+    }
+
+    /// rebuild the planes after moving some vertices of `topology` (which must be the topology of this brush). Faces
+    /// that are no longer planar are split into triangles. Fails if the result would be non-convex or degenerated.
+    pub fn with_moved_vertices(
+        &self,
+        topology: &BrushTopology,
+        moved: &[(usize, Vec3)],
+    ) -> Result<Brush, BrushError> {
+        let mut vertices = topology.vertices.clone();
+        for (i, position) in moved {
+            vertices[*i] = *position;
+        }
+
+        let mut brush = Brush {
+            planes: Vec::new(),
+            appearances: Vec::new(),
+        };
+        for (plane_index, face) in &topology.faces {
+            let points = face.iter().map(|i| vertices[*i]).collect::<Vec<_>>();
+            let Some(normal) = face_normal(&points) else {
+                // collapsed face
+                return Err(BrushError::Degenerated(self.clone()));
+            };
+            let Some(planes) = face_planes(&points, normal) else {
+                return Err(BrushError::NonConvex);
+            };
+            for plane in planes {
+                // a face that is turned inside out folds over the rest of the brush
+                if plane.normal.dot(self.planes[*plane_index].normal) <= 0.0 {
+                    return Err(BrushError::NonConvex);
+                }
+                // faces that became coplanar are merged
+                let duplicate = brush.planes.iter().any(|p| {
+                    p.normal.distance(plane.normal) < PLANE_EPSILON
+                        && (p.w - plane.w).abs() < PLANE_EPSILON
+                });
+                if !duplicate {
+                    brush.planes.push(plane);
+                    brush.appearances.push(self.appearances[*plane_index]);
+                }
+            }
+        }
+
+        let convex = brush
+            .planes
+            .iter()
+            .all(|plane| vertices.iter().all(|v| behind(plane, *v)));
+        if !convex {
+            return Err(BrushError::NonConvex);
+        }
+        let (_, degenerated) = brush.get_polygons();
+        if !degenerated.is_empty() || Csg::try_from(brush.clone()).is_err() {
+            return Err(BrushError::Degenerated(brush));
+        }
+        Ok(brush)
+    }
+}
+
+fn behind(plane: &Plane, point: Vec3) -> bool {
+    plane.normal.dot(point) - plane.w < PLANE_EPSILON
+}
+
+// Newell's method, robust for (almost) collinear vertices. None if the face has no area.
+fn face_normal(points: &[Vec3]) -> Option<Vec3> {
+    points
+        .iter()
+        .enumerate()
+        .map(|(i, p)| p.cross(points[(i + 1) % points.len()]))
+        .sum::<Vec3>()
+        .try_normalize()
+}
+
+// a single plane for planar faces, otherwise a convex fan of triangles. None if the face can not be split
+// into a convex fan.
+fn face_planes(points: &[Vec3], normal: Vec3) -> Option<Vec<Plane>> {
+    let center = points.iter().sum::<Vec3>() / points.len() as f32;
+    let plane = Plane::new(normal, normal.dot(center));
+    if points
+        .iter()
+        .all(|p| (plane.normal.dot(*p) - plane.w).abs() < PLANE_EPSILON)
+    {
+        return Some(vec![plane]);
+    }
+
+    // the diagonals of the fan decide whether the face folds outwards, so try all starting points
+    let n = points.len();
+    (0..n).find_map(|start| {
+        let fan = (1..n - 1)
+            .filter_map(|k| {
+                let a = points[start];
+                let b = points[(start + k) % n];
+                let c = points[(start + k + 1) % n];
+                // skip collinear triangles
+                (b - a)
+                    .cross(c - a)
+                    .try_normalize()
+                    .map(|normal| Plane::new(normal, normal.dot(a)))
+            })
+            .collect::<Vec<_>>();
+        fan.iter()
+            .all(|plane| points.iter().all(|p| behind(plane, *p)))
+            .then_some(fan)
+    })
+}
+
+#[test]
+fn test_brush_topology() {
+    let brush = Brush::default();
+    let topology = brush.topology();
+    assert_eq!(topology.vertices.len(), 8);
+    assert_eq!(topology.edges.len(), 12);
+    assert_eq!(topology.faces.len(), 6);
+    assert!(topology.faces.iter().all(|(_, face)| face.len() == 4));
+}
+
+#[test]
+fn test_brush_with_moved_vertices() {
+    let brush = Brush::default();
+    let topology = brush.topology();
+    let vertex = |p: Vec3| {
+        topology
+            .vertices
+            .iter()
+            .position(|v| v.distance(p) < 1e-3)
+            .unwrap()
+    };
+
+    // moving the whole top face keeps the box
+    let moved = topology
+        .vertices
+        .iter()
+        .enumerate()
+        .filter(|(_, v)| v.y > 0.0)
+        .map(|(i, v)| (i, *v + Vec3::Y))
+        .collect::<Vec<_>>();
+    let moved_brush = brush.with_moved_vertices(&topology, &moved).unwrap();
+    assert_eq!(moved_brush.planes.len(), 6);
+    let csg: Csg = moved_brush.try_into().unwrap();
+    let (center, _) = csg.bounding_sphere();
+    assert!((center - Vec3::new(0.0, 0.5, 0.0)).length() < 1e-4);
+
+    // pulling a corner outwards splits the three adjacent faces into triangles
+    let corner = vertex(Vec3::ONE);
+    let moved_brush = brush
+        .with_moved_vertices(&topology, &[(corner, Vec3::splat(2.0))])
+        .unwrap();
+    assert_eq!(moved_brush.planes.len(), 9);
+    assert_eq!(moved_brush.topology().vertices.len(), 8);
+
+    // pushing it to the center makes the brush concave
+    assert!(matches!(
+        brush.with_moved_vertices(&topology, &[(corner, Vec3::ZERO)]),
+        Err(BrushError::NonConvex)
+    ));
+
+    // collapsing the top face onto the bottom one
+    let flattened = topology
+        .vertices
+        .iter()
+        .enumerate()
+        .filter(|(_, v)| v.y > 0.0)
+        .map(|(i, v)| (i, *v - Vec3::Y * 2.0))
+        .collect::<Vec<_>>();
+    assert!(brush.with_moved_vertices(&topology, &flattened).is_err());
+}
@@ -1,8 +1,9 @@
-use crate::{edit_commands, util::SnapToGrid};
+use crate::{edit_commands, geometry_snap::GeometrySnap, util::SnapToGrid};
 
 use super::{components, edit_commands::EditCommands, resources, util};
 use bevy::prelude::*;
 use shared::render_layers;
+use sstree::SpatialIndex;
 
 pub fn clip_plane_setup_system(
     mut commands: Commands,
@@ -38,6 +39,8 @@ pub fn clip_plane_control_system(
     mut event_reader: EventReader<util::WmEvent>,
     mut clip_plane_query: Query<&mut components::ClipPlane>,
     editor_windows_2d: Res<resources::EditorWindows2d>,
+    grid_settings: Res<resources::GridSettings>,
+    mut geometry_snap: GeometrySnap,
     camera_query: Query<(&GlobalTransform, &Camera)>,
     mut next_clip_point: Local<NextClipPoint>,
 ) {
@@ -72,27 +75,30 @@ pub fn clip_plane_control_system(
                 continue;
             };
 
-            const SNAP: f32 = 0.5;
+            // alt is the modifier for clip points, so there is no coarse snap
+            let snap = grid_settings.grid_size();
+            let mut snap_point = |pos: Vec3| {
+                geometry_snap
+                    .snap(pos, &window.orientation, &[])
+                    .unwrap_or(pos.snap(snap))
+            };
             match *next_clip_point {
                 NextClipPoint::Point0 => {
                     info!("set clip point 0");
-                    clip_plane.points[0] = window
-                        .orientation
-                        .mix(ray.origin, clip_plane.points[0])
-                        .snap(SNAP);
+                    clip_plane.points[0] =
+                        snap_point(window.orientation.mix(ray.origin, clip_plane.points[0]));
                     // clip_plane.points[0] = ray.origin;
                     *next_clip_point = NextClipPoint::Point1;
                 }
                 NextClipPoint::Point1 => {
                     info!("set clip point 1 & 2");
 
-                    clip_plane.points[1] = window
-                        .orientation
-                        .mix(ray.origin, clip_plane.points[1])
-                        .snap(SNAP);
+                    let point =
+                        snap_point(window.orientation.mix(ray.origin, clip_plane.points[1]));
+                    clip_plane.points[1] = point;
+                    // same position in the view, offset along the view direction
                     clip_plane.points[2] =
-                        (window.orientation.mix(ray.origin, clip_plane.points[2]) + *ray.direction)
-                            .snap(SNAP);
+                        window.orientation.mix(point, clip_plane.points[2]) + *ray.direction;
 
                     *next_clip_point = NextClipPoint::Point0;
                 }
@@ -138,12 +144,18 @@ pub fn clip_preview_system(
     >,
     clip_plane_query: Query<&components::ClipPlane>,
     clip_plane_changed_query: Query<(), Changed<components::ClipPlane>>,
+    spatial_index: Res<SpatialIndex>,
+    egui_keyboard: Res<resources::EguiKeyboard>,
 ) {
+    let keyboard = !egui_keyboard.0;
+    let just_pressed = |key| keyboard && keycodes.just_pressed(key);
+
     if brush_changed_query.is_empty()
         && clip_plane_changed_query.is_empty()
         && clip_state.clip_mode == clip_state.last_clip_mode
-        && !keycodes.just_pressed(KeyCode::KeyR)
-        && !keycodes.just_pressed(KeyCode::KeyG)
+        && !just_pressed(KeyCode::KeyR)
+        && !just_pressed(KeyCode::KeyG)
+        && !just_pressed(KeyCode::KeyT)
     {
         return;
     }
@@ -200,18 +212,25 @@ pub fn clip_preview_system(
 
     // let plane = csg::Plane::from_points_slice(&clip_state.plane_points);
     info!("plane: {:?} {:?}", clip_state.plane_points, clip_plane);
-    let clipped1 = clipped_brush(
-        brush.clone(),
-        clip_plane,
-        material_props,
-        &material_browser.selected_material,
-    );
-    let clipped2 = clipped_brush(
-        brush.clone(),
-        clip_plane.flipped(),
-        material_props,
-        &material_browser.selected_material,
-    );
+    // a half can only be left if the brush reaches behind the plane that is added
+    let reaches_behind = |plane: csg::Plane| {
+        spatial_index
+            .query_half_space(-plane.normal, -plane.w)
+            .any(|entity| entity == selected_entity)
+    };
+    let clip = |plane: csg::Plane| {
+        if !reaches_behind(plane) {
+            return None;
+        }
+        clipped_brush(
+            brush.clone(),
+            plane,
+            material_props,
+            &material_browser.selected_material,
+        )
+    };
+    let clipped1 = clip(clip_plane);
+    let clipped2 = clip(clip_plane.flipped());
 
     // info!("res: {:?}", res);
     let brushes = [
@@ -246,16 +265,18 @@ pub fn clip_preview_system(
         }
     }
 
-    if keycodes.just_pressed(KeyCode::KeyR) {
+    if just_pressed(KeyCode::KeyR) {
         info!("use red: {:?} -> {:?}", brush, clipped1);
         // let mut new_material_props = material_props.clone();
-        if let Some((brush, material_props)) = clipped1 {
+        if let Some((clipped_brush, clipped_material_props)) = clipped1 {
+            let start_brush = brush.clone();
+            let start_material_props = material_props.clone();
             let res = edit_commands.apply(edit_commands::clip_brush::Command {
                 entity: selected_entity,
-                start_brush: brush.clone(),
-                start_material_props: material_props.clone(),
-                brush,
-                material_props,
+                start_brush,
+                start_material_props,
+                brush: clipped_brush,
+                material_props: clipped_material_props,
             });
             if let Err(err) = res {
                 warn!("failed to update brush after clip: {:?}", err);
@@ -263,27 +284,54 @@ pub fn clip_preview_system(
         }
 
         clip_state.clip_mode = false;
-    } else if keycodes.just_pressed(KeyCode::KeyG) {
+    } else if just_pressed(KeyCode::KeyG) {
         info!("use green: {:?} -> {:?}", brush, clipped2);
         // let mut new_material_props = material_props.clone();
-        if let Some((brush, material_props)) = clipped2 {
+        if let Some((clipped_brush, clipped_material_props)) = clipped2 {
+            let start_brush = brush.clone();
+            let start_material_props = material_props.clone();
             let res = edit_commands.apply(edit_commands::clip_brush::Command {
                 entity: selected_entity,
-                start_brush: brush.clone(),
-                start_material_props: material_props.clone(),
-                brush,
-                material_props,
+                start_brush,
+                start_material_props,
+                brush: clipped_brush,
+                material_props: clipped_material_props,
             });
             if let Err(err) = res {
                 warn!("failed to update brush after clip: {:?}", err);
             }
         }
 
+        clip_state.clip_mode = false;
+    } else if just_pressed(KeyCode::KeyT) {
+        info!("split: {:?} -> {:?} {:?}", brush, clipped1, clipped2);
+        // keep red half in the original brush, green half goes into a new brush
+        if let (Some((brush1, material_props1)), Some((brush2, material_props2))) =
+            (clipped1, clipped2)
+        {
+            let start_brush = brush.clone();
+            let start_material_props = material_props.clone();
+            let res = edit_commands.apply(edit_commands::split_brush::Command {
+                entity: selected_entity,
+                start_brush,
+                start_material_props,
+                brush: brush1,
+                material_props: material_props1,
+                split_brush: brush2,
+                split_material_props: material_props2,
+            });
+            if let Err(err) = res {
+                warn!("failed to split brush: {:?}", err);
+            }
+        } else {
+            warn!("split failed: clip plane does not intersect brush");
+        }
+
         clip_state.clip_mode = false;
     }
 }
 
-fn clipped_brush(
+pub(crate) fn clipped_brush(
     mut brush: csg::Brush,
     clip_plane: csg::Plane,
     material_props: &components::BrushMaterialProperties,
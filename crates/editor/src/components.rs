@@ -63,15 +63,15 @@ pub struct EditorObjectBrushBundle {
 }
 
 impl EditorObjectBrushBundle {
-    pub fn from_brush(brush: Brush) -> Self {
-        let csg: csg::Csg = brush.clone().try_into().unwrap();
+    pub fn from_brush(brush: Brush) -> Result<Self, csg::BrushError> {
+        let csg: csg::Csg = brush.clone().try_into()?;
         let (center, radius) = csg.bounding_sphere();
 
         let csg_representation = CsgRepresentation {
             bounds: SpatialBounds { center, radius },
             csg,
         };
-        EditorObjectBrushBundle {
+        Ok(EditorObjectBrushBundle {
             spatial_bundle: default(),
             material_properties: BrushMaterialProperties {
                 materials: std::iter::repeat(String::from("material/architecture/woodframe1"))
@@ -87,7 +87,7 @@ impl EditorObjectBrushBundle {
             // ]),
             name: Name::new("Brush"),
             csg_dirty: CsgDirty,
-        }
+        })
     }
 
     pub fn with_material_properties(
@@ -165,6 +165,14 @@ impl Default for EditorObjectDirectionalLightBundle {
     }
 }
 
+// stable identity of an editor object. In contrast to the Entity it survives despawn / re-spawn cycles (e.g. undo of a removal),
+// so undo entries should always refer to objects by id.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Component, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct EditorObjectId(pub u64);
+
 #[derive(Component)]
 pub struct EditablePoint;
 
@@ -183,9 +191,58 @@ pub struct DragAction {
 }
 
 pub enum DragActionType {
-    Face { affected_faces: Vec<(usize, f32)> },
-    WholeBrush { affected_faces: Vec<(usize, f32)> },
-    NonBrush { start_translation: Vec3 },
+    Face {
+        affected_faces: Vec<(usize, f32)>,
+    },
+    WholeBrush {
+        affected_faces: Vec<(usize, f32)>,
+    },
+    NonBrush {
+        start_transform: Transform,
+    },
+    // rotate / scale around a pivot. The transform is always applied to the state at the start of the drag.
+    BrushTransform {
+        mode: TransformMode,
+        pivot: Vec3,
+        start_brush: csg::Brush,
+    },
+    PointTransform {
+        mode: TransformMode,
+        pivot: Vec3,
+        start_transform: Transform,
+    },
+    // vertex / edge handles of a brush, the planes are rebuilt from the moved vertices
+    Vertices {
+        start_brush: csg::Brush,
+        topology: csg::BrushTopology,
+        vertices: Vec<usize>,
+    },
+}
+
+// what a drag in the ortho views does with the selection
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TransformMode {
+    #[default]
+    Move,
+    Rotate,
+    Scale,
+    Vertices,
+}
+
+impl TransformMode {
+    pub fn next(self) -> Self {
+        match self {
+            TransformMode::Move => TransformMode::Rotate,
+            TransformMode::Rotate => TransformMode::Scale,
+            TransformMode::Scale => TransformMode::Vertices,
+            TransformMode::Vertices => TransformMode::Move,
+        }
+    }
+
+    // rotate and scale work around a pivot
+    pub fn uses_pivot(self) -> bool {
+        matches!(self, TransformMode::Rotate | TransformMode::Scale)
+    }
 }
 
 #[derive(Component)]
@@ -249,6 +306,9 @@ pub struct Despawn;
 #[derive(Component)]
 pub struct ClipPreview;
 
+#[derive(Component)]
+pub struct CreateBrushPreview;
+
 #[derive(Component)]
 pub struct ClipPoint0;
 
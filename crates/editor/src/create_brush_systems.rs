@@ -0,0 +1,343 @@
+use bevy::{color::palettes::tailwind, prelude::*};
+use shared::render_layers;
+
+use crate::{
+    components,
+    edit_commands::{add_brush, EditCommands},
+    geometry_snap::GeometrySnap,
+    resources,
+    util::{self, ortho_view_bounds, Orientation2d, SnapToGrid},
+};
+
+// tools that create brushes in the ortho views
+
+// draw-to-create: in create mode a plain left drag in an ortho view defines the footprint of a new brush. The depth
+// is taken from the last created brush, or from the extent of the other ortho view.
+
+#[allow(clippy::too_many_arguments)]
+pub fn create_brush_system(
+    mut commands: Commands,
+    mut edit_commands: EditCommands,
+    mut event_reader: EventReader<util::WmEvent>,
+    mut create_state: ResMut<resources::CreateBrushState>,
+    mut geometry_snap: GeometrySnap,
+    keycodes: Res<ButtonInput<KeyCode>>,
+    grid_settings: Res<resources::GridSettings>,
+    editor_windows_2d: Res<resources::EditorWindows2d>,
+    materials_res: Res<resources::Materials>,
+    material_browser: Res<resources::MaterialBrowser>,
+    camera_query: Query<(&GlobalTransform, &Camera)>,
+    selected_query: Query<Entity, With<components::Selected>>,
+    preview_query: Query<Entity, With<components::CreateBrushPreview>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !create_state.create_mode && create_state.drag.is_some() {
+        create_state.drag = None;
+    }
+
+    let snap = grid_settings.translation_snap(&keycodes);
+
+    for event in event_reader.read() {
+        if !create_state.create_mode {
+            continue;
+        }
+        match *event {
+            util::WmEvent::DragStart {
+                window: window_name,
+                button: util::WmMouseButton::Left,
+                pointer_state,
+            } if !pointer_state.modifiers.ctrl && !pointer_state.modifiers.alt => {
+                let Some(window) = editor_windows_2d.windows.get(window_name) else {
+                    continue;
+                };
+                let Some(pos) = cursor_pos(window, pointer_state, &camera_query) else {
+                    continue;
+                };
+                let Some(depth) = depth_range(
+                    &create_state,
+                    &editor_windows_2d,
+                    window_name,
+                    &window.orientation,
+                    &camera_query,
+                ) else {
+                    warn!("create brush: no depth range");
+                    continue;
+                };
+                let pos = geometry_snap
+                    .snap(pos, &window.orientation, &[])
+                    .unwrap_or(pos.snap(snap));
+                create_state.drag = Some(resources::CreateBrushDrag {
+                    window: window_name,
+                    start: pos,
+                    end: pos,
+                    depth: (depth.0.snap(snap), depth.1.snap(snap)),
+                });
+            }
+            util::WmEvent::DragUpdate {
+                window: window_name,
+                button: util::WmMouseButton::Left,
+                pointer_state,
+            } => {
+                let Some(window) = editor_windows_2d.windows.get(window_name) else {
+                    continue;
+                };
+                let pos = cursor_pos(window, pointer_state, &camera_query);
+                if let (Some(drag), Some(pos)) = (&mut create_state.drag, pos) {
+                    if drag.window == window_name {
+                        drag.end = geometry_snap
+                            .snap(pos, &window.orientation, &[])
+                            .unwrap_or(pos.snap(snap));
+                    }
+                }
+            }
+            util::WmEvent::DragEnd {
+                window: window_name,
+                ..
+            } => {
+                let Some(drag) = create_state.drag.take() else {
+                    continue;
+                };
+                let Some(window) = editor_windows_2d.windows.get(window_name) else {
+                    continue;
+                };
+                let Some((min, max)) = footprint_bounds(&drag, &window.orientation) else {
+                    info!("create brush: empty footprint");
+                    continue;
+                };
+                let material = (!material_browser.selected_material.is_empty())
+                    .then(|| material_browser.selected_material.clone());
+                let res = edit_commands.apply(add_brush::Command {
+                    brush: csg::Brush::from_bounds(min, max),
+                    material,
+                });
+                match res {
+                    Ok(()) => {
+                        // the new brush is the only selected one
+                        for entity in &selected_query {
+                            commands.entity(entity).remove::<components::Selected>();
+                        }
+                        create_state.last_bounds = Some((min, max));
+                    }
+                    Err(err) => warn!("failed to add brush: {:?}", err),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    if !create_state.is_changed() {
+        return;
+    }
+    for entity in &preview_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(drag) = &create_state.drag else {
+        return;
+    };
+    let Some(window) = editor_windows_2d.windows.get(drag.window) else {
+        return;
+    };
+    let Some((min, max)) = footprint_bounds(drag, &window.orientation) else {
+        return;
+    };
+    let Ok(csg) = csg::Csg::try_from(csg::Brush::from_bounds(min, max)) else {
+        return;
+    };
+    let (mesh, origin) = (&csg).into();
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(mesh),
+            material: materials_res.get_brush_2d_material(),
+            transform: Transform::from_translation(origin),
+            ..default()
+        },
+        render_layers::ortho_views(),
+        components::CreateBrushPreview,
+    ));
+}
+
+// polygon tool: clicks in an ortho view add outline points, enter (or clicking the first point again) extrudes the
+// outline into convex brushes, backspace removes the last point.
+#[allow(clippy::too_many_arguments)]
+pub fn polygon_tool_system(
+    mut commands: Commands,
+    mut edit_commands: EditCommands,
+    mut event_reader: EventReader<util::WmEvent>,
+    mut polygon_tool: ResMut<resources::PolygonToolState>,
+    mut create_state: ResMut<resources::CreateBrushState>,
+    mut gizmos: Gizmos<crate::SelectionGizmos>,
+    mut geometry_snap: GeometrySnap,
+    keycodes: Res<ButtonInput<KeyCode>>,
+    grid_settings: Res<resources::GridSettings>,
+    editor_windows_2d: Res<resources::EditorWindows2d>,
+    material_browser: Res<resources::MaterialBrowser>,
+    camera_query: Query<(&GlobalTransform, &Camera)>,
+    selected_query: Query<Entity, With<components::Selected>>,
+    egui_keyboard: Res<resources::EguiKeyboard>,
+) {
+    if !polygon_tool.active {
+        if !polygon_tool.points.is_empty() {
+            polygon_tool.points.clear();
+            polygon_tool.window = None;
+        }
+        return;
+    }
+
+    let snap = grid_settings.translation_snap(&keycodes);
+    let keyboard = !egui_keyboard.0;
+    let mut finish = keyboard && keycodes.just_pressed(KeyCode::Enter);
+    if keyboard && keycodes.just_pressed(KeyCode::Backspace) {
+        polygon_tool.points.pop();
+    }
+
+    for event in event_reader.read() {
+        let util::WmEvent::Clicked {
+            window: window_name,
+            button: util::WmMouseButton::Left,
+            pointer_state,
+        } = *event
+        else {
+            continue;
+        };
+        if polygon_tool.points.is_empty() {
+            polygon_tool.window = Some(window_name);
+        } else if polygon_tool.window != Some(window_name) {
+            info!(
+                "polygon tool: outline is drawn in {:?}",
+                polygon_tool.window
+            );
+            continue;
+        }
+        let Some(window) = editor_windows_2d.windows.get(window_name) else {
+            continue;
+        };
+        let Ok((global_transform, camera)) = camera_query.get(window.camera) else {
+            warn!("2d window camera not found: {:?}", window.camera);
+            continue;
+        };
+        let Some(ray) =
+            camera.viewport_to_world(global_transform, pointer_state.get_pos_origin_down())
+        else {
+            continue;
+        };
+        let pos = geometry_snap
+            .snap(ray.origin, &window.orientation, &[])
+            .unwrap_or(ray.origin.snap(snap));
+        // keep the points a bit in front of the near plane, so that the outline is not clipped
+        let pos = window.orientation.mix(pos, ray.origin + *ray.direction);
+
+        if polygon_tool.points.len() >= 3 && polygon_tool.points.first() == Some(&pos) {
+            finish = true;
+        } else if polygon_tool.points.last() != Some(&pos) {
+            polygon_tool.points.push(pos);
+        }
+    }
+
+    let Some(window) = polygon_tool
+        .window
+        .and_then(|name| editor_windows_2d.windows.get(name))
+    else {
+        return;
+    };
+    if !finish {
+        if let (Some(first), Some(last)) = (polygon_tool.points.first(), polygon_tool.points.last())
+        {
+            gizmos.linestrip(polygon_tool.points.iter().copied(), tailwind::ORANGE_500);
+            gizmos.line(*last, *first, tailwind::ORANGE_200);
+        }
+        return;
+    }
+
+    let points = std::mem::take(&mut polygon_tool.points);
+    let window_name = polygon_tool.window.take().unwrap_or_default();
+    let orientation = &window.orientation;
+    let outline = points
+        .iter()
+        .map(|p| Vec2::new(orientation.get_right_axis(*p), orientation.get_up_axis(*p)))
+        .collect::<Vec<_>>();
+    let Some(parts) = csg::convex_decomposition(&outline) else {
+        warn!("polygon tool: outline is self-intersecting or empty");
+        return;
+    };
+    let Some((depth_min, depth_max)) = depth_range(
+        &create_state,
+        &editor_windows_2d,
+        window_name,
+        orientation,
+        &camera_query,
+    ) else {
+        warn!("polygon tool: no depth range");
+        return;
+    };
+    let (depth_min, depth_max) = (depth_min.snap(snap), depth_max.snap(snap));
+    let axis = orientation.mix(Vec3::ZERO, Vec3::ONE);
+    let material = (!material_browser.selected_material.is_empty())
+        .then(|| material_browser.selected_material.clone());
+
+    // the new brushes become the selection
+    for entity in &selected_query {
+        commands.entity(entity).remove::<components::Selected>();
+    }
+    edit_commands.begin_group("extrude polygon");
+    for part in parts {
+        let part_outline = part.iter().map(|i| points[*i]).collect::<Vec<_>>();
+        let res = edit_commands.apply(add_brush::Command {
+            brush: csg::Brush::extruded(&part_outline, axis, depth_min, depth_max),
+            material: material.clone(),
+        });
+        if let Err(err) = res {
+            warn!("polygon tool: failed to add brush: {:?}", err);
+        }
+    }
+    edit_commands.end_group();
+
+    let min = points.iter().fold(Vec3::INFINITY, |acc, p| acc.min(*p));
+    let max = points.iter().fold(Vec3::NEG_INFINITY, |acc, p| acc.max(*p));
+    create_state.last_bounds = Some((
+        orientation.mix(min, Vec3::splat(depth_min)),
+        orientation.mix(max, Vec3::splat(depth_max)),
+    ));
+}
+
+fn cursor_pos(
+    window: &resources::EditorWindow2d,
+    pointer_state: util::WmEventPointerState,
+    camera_query: &Query<(&GlobalTransform, &Camera)>,
+) -> Option<Vec3> {
+    let (global_transform, camera) = camera_query.get(window.camera).ok()?;
+    let ray = camera.viewport_to_world(global_transform, pointer_state.get_pos_origin_down())?;
+    Some(ray.origin)
+}
+
+// range along the view direction of the given window
+fn depth_range(
+    create_state: &resources::CreateBrushState,
+    editor_windows_2d: &resources::EditorWindows2d,
+    window_name: &str,
+    orientation: &Orientation2d,
+    camera_query: &Query<(&GlobalTransform, &Camera)>,
+) -> Option<(f32, f32)> {
+    // unit vector along the view direction axis
+    let axis = orientation.mix(Vec3::ZERO, Vec3::ONE);
+    if let Some((min, max)) = create_state.last_bounds {
+        return Some((min.dot(axis), max.dot(axis)));
+    }
+    let (_, other) = editor_windows_2d
+        .windows
+        .iter()
+        .find(|(name, _)| *name != window_name)?;
+    let (global_transform, camera) = camera_query.get(other.camera).ok()?;
+    let (min, max) = ortho_view_bounds(camera, global_transform)?;
+    Some((min.dot(axis), max.dot(axis)))
+}
+
+// None if the box would be flat
+fn footprint_bounds(
+    drag: &resources::CreateBrushDrag,
+    orientation: &Orientation2d,
+) -> Option<(Vec3, Vec3)> {
+    let (depth_min, depth_max) = drag.depth;
+    let min = orientation.mix(drag.start.min(drag.end), Vec3::splat(depth_min));
+    let max = orientation.mix(drag.start.max(drag.end), Vec3::splat(depth_max));
+    ((max - min).min_element() > csg::PLANE_EPSILON).then_some((min, max))
+}
@@ -6,18 +6,22 @@ use bevy::{
 };
 
 use super::{
-    components,
+    components::{self, EditorObjectId},
+    resources::EditorObjects,
     undo::{UndoCommands, UndoStack},
 };
 use csg;
 use thiserror::Error;
 
 pub mod add_brush;
+pub mod add_directional_light;
 pub mod add_pointlight;
 pub mod clip_brush;
 pub mod duplicate_brush;
 pub mod remove_entity;
 pub mod set_brush_material;
+pub mod set_light_properties;
+pub mod split_brush;
 pub mod update_brush_drag;
 pub mod update_point_transform;
 
@@ -26,8 +30,14 @@ pub enum EditCommandError {
     #[error("Entity {0:?} is not available")]
     UnknownEntity(Entity),
 
+    #[error("Editor object {0:?} is not available")]
+    UnknownObject(EditorObjectId),
+
     #[error("Entity query error {0:?}")]
     EntityQueryError(#[from] QueryEntityError),
+
+    #[error("Brush snapshot is degenerated: {0}")]
+    DegeneratedBrush(#[from] csg::BrushError),
 }
 
 // pub type Result<T> = std::result::Result<T, EditCommandError>;
@@ -40,6 +50,10 @@ pub trait UndoDowncast {
 pub trait UndoCommand: UndoDowncast {
     fn try_merge(&mut self, other: &dyn UndoCommand) -> bool;
     fn undo(&self, undo_commands: &mut UndoCommands) -> Result<()>;
+    // re-apply the command after it has been undone
+    fn redo(&self, undo_commands: &mut UndoCommands) -> Result<()>;
+    // human readable description, shown in the undo history
+    fn description(&self) -> String;
 }
 
 impl<T: Any> UndoDowncast for T {
@@ -54,18 +68,95 @@ pub trait EditCommand {
 
 pub mod prelude {
     pub use super::{EditCommand, EditCommandError, EditCommands, Result, UndoCommand};
-    pub use crate::{components, undo::UndoCommands};
+    pub use crate::{
+        components::{self, EditorObjectId},
+        undo::UndoCommands,
+    };
     pub use anyhow::Context;
     pub use bevy::prelude::*;
 }
 
+// owned copy of an editor object, sufficient to re-create it after it was despawned (e.g. on undo of a removal)
+#[derive(Clone)]
+pub enum ObjectSnapshot {
+    Brush {
+        brush: csg::Brush,
+        material_props: components::BrushMaterialProperties,
+    },
+    PointLight {
+        transform: Transform,
+        light_properties: components::PointLightProperties,
+    },
+    DirectionalLight {
+        transform: Transform,
+        light_properties: components::DirectionalLightProperties,
+    },
+}
+
+impl ObjectSnapshot {
+    pub fn spawn(
+        &self,
+        id: EditorObjectId,
+        commands: &mut Commands,
+    ) -> Result<Entity, EditCommandError> {
+        let entity = match self {
+            ObjectSnapshot::Brush {
+                brush,
+                material_props,
+            } => commands
+                .spawn((
+                    components::EditorObjectBrushBundle::from_brush(brush.clone())?
+                        .with_material_properties(material_props.clone()),
+                    id,
+                ))
+                .id(),
+            ObjectSnapshot::PointLight {
+                transform,
+                light_properties,
+            } => commands
+                .spawn((
+                    components::EditorObjectPointlightBundle {
+                        spatial: SpatialBundle::from_transform(*transform),
+                        light_properties: light_properties.clone(),
+                        ..default()
+                    },
+                    id,
+                ))
+                .id(),
+            ObjectSnapshot::DirectionalLight {
+                transform,
+                light_properties,
+            } => commands
+                .spawn((
+                    components::EditorObjectDirectionalLightBundle {
+                        spatial: SpatialBundle::from_transform(*transform),
+                        light_properties: light_properties.clone(),
+                        ..default()
+                    },
+                    id,
+                ))
+                .id(),
+        };
+        Ok(entity)
+    }
+
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            ObjectSnapshot::Brush { .. } => "brush",
+            ObjectSnapshot::PointLight { .. } => "point light",
+            ObjectSnapshot::DirectionalLight { .. } => "directional light",
+        }
+    }
+}
+
 // generic undo for entity add. Can be re-used by all commands that just add an entity that can be removed by adding components::Despawn.
 // NOTE: make sure that there is a system that handles components::Despawn, since the actual despawn may need a specific implementation.
 pub mod add_entity {
 
     use super::prelude::*;
     pub struct Undo {
-        pub entity: Entity,
+        pub id: EditorObjectId,
+        pub snapshot: super::ObjectSnapshot,
     }
     impl UndoCommand for Undo {
         fn try_merge(&mut self, _other: &dyn UndoCommand) -> bool {
@@ -73,14 +164,20 @@ pub mod add_entity {
         }
 
         fn undo(&self, undo_commands: &mut UndoCommands) -> Result<()> {
-            let entity = undo_commands.undo_stack.remap_entity(self.entity);
-            if let Some(mut entity_commands) = undo_commands.commands.get_entity(entity) {
-                entity_commands.insert(components::Despawn);
-                Ok(())
-            } else {
-                error!( "failed to despawn {:?} to undo addition. Either missing remap or undo failed earlier", entity);
-                Err(EditCommandError::UnknownEntity(entity).into())
-            }
+            undo_commands
+                .despawn_object(self.id)
+                .context("undo add_entity")
+        }
+
+        fn redo(&self, undo_commands: &mut UndoCommands) -> Result<()> {
+            undo_commands
+                .respawn_object(self.id, &self.snapshot)
+                .context("redo add_entity")?;
+            Ok(())
+        }
+
+        fn description(&self) -> String {
+            format!("add {}", self.snapshot.kind_name())
         }
     }
 }
@@ -89,6 +186,8 @@ pub mod add_entity {
 pub struct EditCommands<'w, 's> {
     commands: Commands<'w, 's>,
     undo_stack: ResMut<'w, UndoStack>,
+    editor_objects: ResMut<'w, EditorObjects>,
+    id_query: Query<'w, 's, &'static EditorObjectId>,
     pub brush_query: Query<
         'w,
         's,
@@ -98,6 +197,14 @@ pub struct EditCommands<'w, 's> {
         ),
     >,
     pub transform_query: Query<'w, 's, &'static mut Transform, With<components::EditablePoint>>,
+    pub light_query: Query<
+        'w,
+        's,
+        (
+            Option<&'static mut components::PointLightProperties>,
+            Option<&'static mut components::DirectionalLightProperties>,
+        ),
+    >,
 }
 impl<'w, 's> EditCommands<'w, 's> {
     pub fn apply(&mut self, cmd: impl EditCommand) -> Result<()> {
@@ -106,6 +213,16 @@ impl<'w, 's> EditCommands<'w, 's> {
         Ok(())
     }
 
+    // group all following edits into one undo step (e.g. an operation on the whole selection), see
+    // UndoStack::begin_group
+    pub fn begin_group(&mut self, description: impl Into<String>) {
+        self.undo_stack.begin_group(description);
+    }
+
+    pub fn end_group(&mut self) {
+        self.undo_stack.end_group();
+    }
+
     pub fn end_drag(&mut self, entity: Entity) {
         self.commands
             .entity(entity)
@@ -113,4 +230,50 @@ impl<'w, 's> EditCommands<'w, 's> {
 
         self.undo_stack.commit();
     }
+
+    pub fn object_id(&self, entity: Entity) -> Result<EditorObjectId, EditCommandError> {
+        Ok(*self.id_query.get(entity)?)
+    }
+
+    // spawn a new editor object with a freshly allocated id
+    pub fn spawn_object(
+        &mut self,
+        snapshot: &ObjectSnapshot,
+    ) -> Result<(EditorObjectId, Entity), EditCommandError> {
+        let id = self.editor_objects.alloc_id();
+        let entity = snapshot.spawn(id, &mut self.commands)?;
+        self.editor_objects.insert(id, entity);
+        Ok((id, entity))
+    }
+
+    pub fn despawn_object(&mut self, entity: Entity) -> Result<EditorObjectId, EditCommandError> {
+        let id = self.object_id(entity)?;
+        self.commands
+            .get_entity(entity)
+            .ok_or(EditCommandError::UnknownEntity(entity))?
+            .insert(components::Despawn);
+        self.editor_objects.remove(id);
+        Ok(id)
+    }
+
+    pub fn snapshot(&self, entity: Entity) -> Result<ObjectSnapshot, EditCommandError> {
+        if let Ok((material_props, brush)) = self.brush_query.get(entity) {
+            return Ok(ObjectSnapshot::Brush {
+                brush: brush.clone(),
+                material_props: material_props.clone(),
+            });
+        }
+        let transform = *self.transform_query.get(entity)?;
+        match self.light_query.get(entity)? {
+            (Some(light_properties), _) => Ok(ObjectSnapshot::PointLight {
+                transform,
+                light_properties: light_properties.clone(),
+            }),
+            (_, Some(light_properties)) => Ok(ObjectSnapshot::DirectionalLight {
+                transform,
+                light_properties: light_properties.clone(),
+            }),
+            _ => Err(EditCommandError::UnknownEntity(entity)),
+        }
+    }
 }
@@ -2,19 +2,32 @@ use super::prelude::*;
 
 pub struct Command {
     pub brush: csg::Brush,
+    // material for all faces, default material if None
+    pub material: Option<String>,
 }
 pub use super::add_entity::Undo;
 
 impl EditCommand for Command {
     fn apply(self, commands: &mut EditCommands) -> Result<Box<dyn UndoCommand + Send + Sync>> {
-        let entity = commands
+        let mut bundle = components::EditorObjectBrushBundle::from_brush(self.brush)
+            .context("apply add_brush")?;
+        if let Some(material) = self.material {
+            for face_material in &mut bundle.material_properties.materials {
+                face_material.clone_from(&material);
+            }
+        }
+        let snapshot = super::ObjectSnapshot::Brush {
+            brush: bundle.brush,
+            material_props: bundle.material_properties,
+        };
+        let (id, entity) = commands
+            .spawn_object(&snapshot)
+            .context("apply add_brush")?;
+        commands
             .commands
-            .spawn((
-                components::EditorObjectBrushBundle::from_brush(self.brush),
-                components::Selected,
-            ))
-            .id();
+            .entity(entity)
+            .insert(components::Selected);
 
-        Ok(Box::new(Undo { entity }))
+        Ok(Box::new(Undo { id, snapshot }))
     }
 }
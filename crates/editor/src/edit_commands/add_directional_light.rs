@@ -0,0 +1,22 @@
+use super::prelude::*;
+
+pub struct Command;
+pub use super::add_entity::Undo;
+impl EditCommand for Command {
+    fn apply(self, commands: &mut EditCommands) -> Result<Box<dyn UndoCommand + Send + Sync>> {
+        let bundle = components::EditorObjectDirectionalLightBundle::default();
+        let snapshot = super::ObjectSnapshot::DirectionalLight {
+            transform: bundle.spatial.transform,
+            light_properties: bundle.light_properties,
+        };
+        let (id, entity) = commands
+            .spawn_object(&snapshot)
+            .context("apply add_directional_light")?;
+        commands
+            .commands
+            .entity(entity)
+            .insert(components::Selected);
+
+        Ok(Box::new(Undo { id, snapshot }))
+    }
+}
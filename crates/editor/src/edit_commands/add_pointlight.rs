@@ -4,14 +4,19 @@ pub struct Command;
 pub use super::add_entity::Undo;
 impl EditCommand for Command {
     fn apply(self, commands: &mut EditCommands) -> Result<Box<dyn UndoCommand + Send + Sync>> {
-        let entity = commands
+        let bundle = components::EditorObjectPointlightBundle::default();
+        let snapshot = super::ObjectSnapshot::PointLight {
+            transform: bundle.spatial.transform,
+            light_properties: bundle.light_properties,
+        };
+        let (id, entity) = commands
+            .spawn_object(&snapshot)
+            .context("apply add_pointlight")?;
+        commands
             .commands
-            .spawn((
-                components::EditorObjectPointlightBundle::default(),
-                components::Selected,
-            ))
-            .id();
+            .entity(entity)
+            .insert(components::Selected);
 
-        Ok(Box::new(Undo { entity }))
+        Ok(Box::new(Undo { id, snapshot }))
     }
 }
@@ -8,8 +8,19 @@ pub struct Command {
     pub material_props: components::BrushMaterialProperties,
 }
 
+pub struct Undo {
+    pub id: EditorObjectId,
+    pub start_brush: csg::Brush,
+    pub start_material_props: components::BrushMaterialProperties,
+    pub brush: csg::Brush,
+    pub material_props: components::BrushMaterialProperties,
+}
+
 impl EditCommand for Command {
     fn apply(self, commands: &mut EditCommands) -> Result<Box<dyn UndoCommand + Send + Sync>> {
+        let id = commands
+            .object_id(self.entity)
+            .context("apply brush_clip")?;
         let mut entity_commands = commands
             .commands
             .get_entity(self.entity)
@@ -26,23 +37,22 @@ impl EditCommand for Command {
             brush: self.brush.clone(),
         });
 
-        Ok(Box::new(self))
+        Ok(Box::new(Undo {
+            id,
+            start_brush: self.start_brush,
+            start_material_props: self.start_material_props,
+            brush: self.brush,
+            material_props: self.material_props,
+        }))
     }
 }
 
-impl UndoCommand for Command {
-    fn try_merge(&mut self, other: &dyn UndoCommand) -> bool {
-        if let Some(other) = other.as_any().downcast_ref::<Self>() {
-            if self.entity == other.entity {
-                self.brush = other.brush.clone();
-                info!("merged");
-                return true;
-            }
-        }
+impl UndoCommand for Undo {
+    fn try_merge(&mut self, _other: &dyn UndoCommand) -> bool {
         false
     }
     fn undo(&self, undo_commands: &mut UndoCommands) -> Result<()> {
-        let entity = undo_commands.undo_stack.remap_entity(self.entity);
+        let entity = undo_commands.entity(self.id)?;
         let mut entity_commands = undo_commands
             .commands
             .get_entity(entity)
@@ -51,7 +61,7 @@ impl UndoCommand for Command {
 
         let mut material_props = undo_commands
             .material_properties_query
-            .get_mut(self.entity)
+            .get_mut(entity)
             .context("undo brush_clip")?;
 
         *material_props = self.start_material_props.clone();
@@ -61,4 +71,29 @@ impl UndoCommand for Command {
         });
         Ok(())
     }
+
+    fn redo(&self, undo_commands: &mut UndoCommands) -> Result<()> {
+        let entity = undo_commands.entity(self.id)?;
+        let mut entity_commands = undo_commands
+            .commands
+            .get_entity(entity)
+            .ok_or(EditCommandError::UnknownEntity(entity))
+            .context("redo brush_clip")?;
+
+        let mut material_props = undo_commands
+            .material_properties_query
+            .get_mut(entity)
+            .context("redo brush_clip")?;
+
+        *material_props = self.material_props.clone();
+
+        entity_commands.insert(components::EditUpdate::BrushDrag {
+            brush: self.brush.clone(),
+        });
+        Ok(())
+    }
+
+    fn description(&self) -> String {
+        "clip brush".into()
+    }
 }
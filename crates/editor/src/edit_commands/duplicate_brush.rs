@@ -15,16 +15,19 @@ impl EditCommand for Command {
             .get(self.template_entity)
             .context("could not find template brush entity.")?;
 
-        let entity = commands
+        let snapshot = super::ObjectSnapshot::Brush {
+            brush: brush.clone(),
+            material_props: material_properties.clone(),
+        };
+        let (id, entity) = commands
+            .spawn_object(&snapshot)
+            .context("apply duplicate_brush")?;
+        commands
             .commands
-            .spawn((
-                components::EditorObjectBrushBundle::from_brush(brush.clone())
-                    .with_material_properties(material_properties.clone()),
-                components::Selected,
-            ))
-            .id();
+            .entity(entity)
+            .insert(components::Selected);
 
-        Ok(Box::new(Undo { entity }))
+        Ok(Box::new(Undo { id, snapshot }))
 
         // panic!("could not find template brush {:?}", self.template_entity);
     }
@@ -1,40 +1,26 @@
-use crate::components::BrushMaterialProperties;
-
 use super::prelude::*;
 
 pub struct Command {
     pub entity: Entity,
 }
 
-pub enum Undo {
-    Brush {
-        entity: Entity,
-        brush: csg::Brush,
-        material_props: BrushMaterialProperties,
-    },
-    NotImplemented,
+pub struct Undo {
+    pub id: EditorObjectId,
+    pub snapshot: super::ObjectSnapshot,
 }
 
 impl EditCommand for Command {
     fn apply(self, commands: &mut EditCommands) -> Result<Box<dyn UndoCommand + Send + Sync>> {
-        commands
-            .commands
-            .get_entity(self.entity)
-            .ok_or(EditCommandError::UnknownEntity(self.entity))
-            .context("apply remove_entity")?
-            .insert(components::Despawn);
-
-        let undo = if let Ok((material_props, brush)) = commands.brush_query.get(self.entity) {
-            Box::new(Undo::Brush {
-                entity: self.entity,
-                brush: brush.clone(),
-                material_props: material_props.clone(),
-            })
-        } else {
-            Box::new(Undo::NotImplemented)
-        };
-
-        Ok(undo)
+        // fallible stuff
+        let snapshot = commands
+            .snapshot(self.entity)
+            .context("apply remove_entity")?;
+        // point of no return
+        let id = commands
+            .despawn_object(self.entity)
+            .context("apply remove_entity")?;
+
+        Ok(Box::new(Undo { id, snapshot }))
     }
 }
 
@@ -44,26 +30,19 @@ impl UndoCommand for Undo {
     }
 
     fn undo(&self, undo_commands: &mut UndoCommands) -> Result<()> {
-        match self {
-            Undo::Brush {
-                entity,
-                brush,
-                material_props,
-            } => {
-                let new_entity = undo_commands
-                    .commands
-                    .spawn(
-                        components::EditorObjectBrushBundle::from_brush(brush.clone())
-                            .with_material_properties(material_props.clone()),
-                    )
-                    .id();
-                undo_commands
-                    .undo_stack
-                    .entity_recreate_map
-                    .insert(*entity, new_entity);
-            }
-            Undo::NotImplemented => warn!("undo not implemented for add entity."),
-        }
+        undo_commands
+            .respawn_object(self.id, &self.snapshot)
+            .context("undo remove_entity")?;
         Ok(())
     }
+
+    fn redo(&self, undo_commands: &mut UndoCommands) -> Result<()> {
+        undo_commands
+            .despawn_object(self.id)
+            .context("redo remove_entity")
+    }
+
+    fn description(&self) -> String {
+        format!("remove {}", self.snapshot.kind_name())
+    }
 }
@@ -7,14 +7,18 @@ pub struct Command {
 }
 
 pub struct Undo {
-    pub entity: Entity,
+    pub id: EditorObjectId,
     pub face: i32,
     pub old_material: String,
+    pub material: String,
 }
 
 impl EditCommand for Command {
     fn apply(self, commands: &mut EditCommands) -> Result<Box<dyn UndoCommand + Send + Sync>> {
         // fallible stuff
+        let id = commands
+            .object_id(self.entity)
+            .context("apply set_brush_material")?;
         let (mut material_props, _) = commands.brush_query.get_mut(self.entity)?;
         commands
             .commands
@@ -26,13 +30,14 @@ impl EditCommand for Command {
 
         let old_material = std::mem::replace(
             &mut material_props.materials[self.face as usize],
-            self.material,
+            self.material.clone(),
         );
 
         Ok(Box::new(Undo {
-            entity: self.entity,
+            id,
             face: self.face,
             old_material,
+            material: self.material,
         }))
 
         // panic!("material props not found for {:?}", self.entity);
@@ -45,7 +50,7 @@ impl UndoCommand for Undo {
     }
 
     fn undo(&self, undo_commands: &mut UndoCommands) -> Result<()> {
-        let entity = undo_commands.undo_stack.remap_entity(self.entity);
+        let entity = undo_commands.entity(self.id)?;
         // fallible stuff
         let mut material_props = undo_commands.material_properties_query.get_mut(entity)?;
         undo_commands
@@ -60,4 +65,25 @@ impl UndoCommand for Undo {
         material_props.materials[self.face as usize] = self.old_material.clone();
         Ok(())
     }
+
+    fn redo(&self, undo_commands: &mut UndoCommands) -> Result<()> {
+        let entity = undo_commands.entity(self.id)?;
+        // fallible stuff
+        let mut material_props = undo_commands.material_properties_query.get_mut(entity)?;
+        undo_commands
+            .commands
+            .get_entity(entity)
+            .ok_or(EditCommandError::UnknownEntity(entity))
+            .context("redo set_brush_material")?
+            .insert(components::CsgDirty);
+
+        // point of no return
+
+        material_props.materials[self.face as usize] = self.material.clone();
+        Ok(())
+    }
+
+    fn description(&self) -> String {
+        format!("set material {}", self.material)
+    }
 }
@@ -0,0 +1,93 @@
+use super::prelude::*;
+
+#[derive(Clone, Debug)]
+pub enum LightProperties {
+    Point(components::PointLightProperties),
+    Directional(components::DirectionalLightProperties),
+}
+
+pub struct Command {
+    pub entity: Entity,
+    pub properties: LightProperties,
+}
+
+pub struct Undo {
+    pub id: EditorObjectId,
+    pub old_properties: LightProperties,
+    pub properties: LightProperties,
+}
+
+impl EditCommand for Command {
+    fn apply(self, commands: &mut EditCommands) -> Result<Box<dyn UndoCommand + Send + Sync>> {
+        let id = commands
+            .object_id(self.entity)
+            .context("apply set_light_properties")?;
+        let (point_light, directional_light) = commands
+            .light_query
+            .get_mut(self.entity)
+            .context("apply set_light_properties")?;
+
+        let old_properties = match (&self.properties, point_light, directional_light) {
+            (LightProperties::Point(properties), Some(mut point_light), _) => {
+                LightProperties::Point(std::mem::replace(&mut *point_light, properties.clone()))
+            }
+            (LightProperties::Directional(properties), _, Some(mut directional_light)) => {
+                LightProperties::Directional(std::mem::replace(
+                    &mut *directional_light,
+                    properties.clone(),
+                ))
+            }
+            _ => return Err(EditCommandError::UnknownEntity(self.entity).into()),
+        };
+
+        Ok(Box::new(Undo {
+            id,
+            old_properties,
+            properties: self.properties,
+        }))
+    }
+}
+
+impl UndoCommand for Undo {
+    fn try_merge(&mut self, other: &dyn UndoCommand) -> bool {
+        // merge continuous edits (e.g. dragging a slider)
+        if let Some(other) = other.as_any().downcast_ref::<Self>() {
+            if self.id == other.id {
+                self.properties = other.properties.clone();
+                return true;
+            }
+        }
+        false
+    }
+
+    fn undo(&self, undo_commands: &mut UndoCommands) -> Result<()> {
+        set_properties(undo_commands, self.id, &self.old_properties)
+            .context("undo set_light_properties")
+    }
+
+    fn redo(&self, undo_commands: &mut UndoCommands) -> Result<()> {
+        set_properties(undo_commands, self.id, &self.properties)
+            .context("redo set_light_properties")
+    }
+
+    fn description(&self) -> String {
+        "set light properties".into()
+    }
+}
+
+fn set_properties(
+    undo_commands: &mut UndoCommands,
+    id: EditorObjectId,
+    properties: &LightProperties,
+) -> Result<()> {
+    let entity = undo_commands.entity(id)?;
+    match properties {
+        LightProperties::Point(properties) => {
+            *undo_commands.point_light_query.get_mut(entity)? = properties.clone();
+        }
+        LightProperties::Directional(properties) => {
+            *undo_commands.directional_light_query.get_mut(entity)? = properties.clone();
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use super::prelude::*;
+
+// split a brush into two halves: the original entity keeps the first half, the second half is
+// spawned as a new brush entity.
+pub struct Command {
+    pub entity: Entity,
+    pub start_brush: csg::Brush,
+    pub start_material_props: components::BrushMaterialProperties,
+    pub brush: csg::Brush,
+    pub material_props: components::BrushMaterialProperties,
+    pub split_brush: csg::Brush,
+    pub split_material_props: components::BrushMaterialProperties,
+}
+
+pub struct Undo {
+    pub id: EditorObjectId,
+    pub start_brush: csg::Brush,
+    pub start_material_props: components::BrushMaterialProperties,
+    pub brush: csg::Brush,
+    pub material_props: components::BrushMaterialProperties,
+    pub split_id: EditorObjectId,
+    pub split_snapshot: super::ObjectSnapshot,
+}
+
+impl EditCommand for Command {
+    fn apply(self, commands: &mut EditCommands) -> Result<Box<dyn UndoCommand + Send + Sync>> {
+        // fallible stuff
+        let id = commands
+            .object_id(self.entity)
+            .context("apply split_brush")?;
+        commands
+            .brush_query
+            .get(self.entity)
+            .context("apply split_brush")?;
+        commands
+            .commands
+            .get_entity(self.entity)
+            .ok_or(EditCommandError::UnknownEntity(self.entity))
+            .context("apply split_brush")?;
+
+        let split_snapshot = super::ObjectSnapshot::Brush {
+            brush: self.split_brush,
+            material_props: self.split_material_props,
+        };
+        let (split_id, _) = commands
+            .spawn_object(&split_snapshot)
+            .context("apply split_brush")?;
+        // point of no return
+
+        commands
+            .commands
+            .entity(self.entity)
+            .insert(components::EditUpdate::BrushDrag {
+                brush: self.brush.clone(),
+            });
+        let (mut material_props, _) = commands
+            .brush_query
+            .get_mut(self.entity)
+            .context("apply split_brush")?;
+        *material_props = self.material_props.clone();
+
+        Ok(Box::new(Undo {
+            id,
+            start_brush: self.start_brush,
+            start_material_props: self.start_material_props,
+            brush: self.brush,
+            material_props: self.material_props,
+            split_id,
+            split_snapshot,
+        }))
+    }
+}
+
+impl UndoCommand for Undo {
+    fn try_merge(&mut self, _other: &dyn UndoCommand) -> bool {
+        false
+    }
+
+    fn undo(&self, undo_commands: &mut UndoCommands) -> Result<()> {
+        let entity = undo_commands.entity(self.id)?;
+        undo_commands.entity(self.split_id)?;
+
+        // fallible stuff
+        let mut material_props = undo_commands
+            .material_properties_query
+            .get_mut(entity)
+            .context("undo split_brush")?;
+
+        undo_commands
+            .commands
+            .get_entity(entity)
+            .ok_or(EditCommandError::UnknownEntity(entity))
+            .context("undo split_brush")?
+            .insert(components::EditUpdate::BrushDrag {
+                brush: self.start_brush.clone(),
+            });
+        // point of no return
+
+        *material_props = self.start_material_props.clone();
+        undo_commands
+            .despawn_object(self.split_id)
+            .context("undo split_brush")?;
+        Ok(())
+    }
+
+    fn redo(&self, undo_commands: &mut UndoCommands) -> Result<()> {
+        let entity = undo_commands.entity(self.id)?;
+
+        // fallible stuff
+        undo_commands
+            .material_properties_query
+            .get(entity)
+            .context("redo split_brush")?;
+        undo_commands
+            .commands
+            .get_entity(entity)
+            .ok_or(EditCommandError::UnknownEntity(entity))
+            .context("redo split_brush")?;
+        undo_commands
+            .respawn_object(self.split_id, &self.split_snapshot)
+            .context("redo split_brush")?;
+        // point of no return
+
+        undo_commands
+            .commands
+            .entity(entity)
+            .insert(components::EditUpdate::BrushDrag {
+                brush: self.brush.clone(),
+            });
+        let mut material_props = undo_commands
+            .material_properties_query
+            .get_mut(entity)
+            .context("redo split_brush")?;
+        *material_props = self.material_props.clone();
+        Ok(())
+    }
+
+    fn description(&self) -> String {
+        "split brush".into()
+    }
+}
@@ -6,8 +6,17 @@ pub struct Command {
     pub brush: csg::Brush,
 }
 
+pub struct Undo {
+    pub id: EditorObjectId,
+    pub start_brush: csg::Brush,
+    pub brush: csg::Brush,
+}
+
 impl EditCommand for Command {
     fn apply(self, commands: &mut EditCommands) -> Result<Box<dyn UndoCommand + Send + Sync>> {
+        let id = commands
+            .object_id(self.entity)
+            .context("apply update_brush_drag")?;
         commands
             .commands
             .get_entity(self.entity)
@@ -16,14 +25,18 @@ impl EditCommand for Command {
             .insert(components::EditUpdate::BrushDrag {
                 brush: self.brush.clone(),
             });
-        Ok(Box::new(self))
+        Ok(Box::new(Undo {
+            id,
+            start_brush: self.start_brush,
+            brush: self.brush,
+        }))
     }
 }
 
-impl UndoCommand for Command {
+impl UndoCommand for Undo {
     fn try_merge(&mut self, other: &dyn UndoCommand) -> bool {
         if let Some(other) = other.as_any().downcast_ref::<Self>() {
-            if self.entity == other.entity {
+            if self.id == other.id {
                 self.brush = other.brush.clone();
                 info!("merged");
                 return true;
@@ -32,7 +45,7 @@ impl UndoCommand for Command {
         false
     }
     fn undo(&self, undo_commands: &mut UndoCommands) -> Result<()> {
-        let entity = undo_commands.undo_stack.remap_entity(self.entity);
+        let entity = undo_commands.entity(self.id)?;
         undo_commands
             .commands
             .get_entity(entity)
@@ -43,4 +56,21 @@ impl UndoCommand for Command {
             });
         Ok(())
     }
+
+    fn redo(&self, undo_commands: &mut UndoCommands) -> Result<()> {
+        let entity = undo_commands.entity(self.id)?;
+        undo_commands
+            .commands
+            .get_entity(entity)
+            .ok_or(EditCommandError::UnknownEntity(entity))
+            .context("redo update_brush_drag")?
+            .insert(components::EditUpdate::BrushDrag {
+                brush: self.brush.clone(),
+            });
+        Ok(())
+    }
+
+    fn description(&self) -> String {
+        "drag brush".into()
+    }
 }
@@ -6,22 +6,29 @@ pub struct Command {
 }
 
 pub struct Undo {
-    pub entity: Entity,
+    pub id: EditorObjectId,
     pub old_transform: Transform,
+    pub transform: Transform,
 }
 
 impl EditCommand for Command {
     fn apply(self, commands: &mut EditCommands) -> Result<Box<dyn UndoCommand + Send + Sync>> {
+        let id = commands
+            .object_id(self.entity)
+            .context("apply update_point_transform")?;
         let mut transform = commands
             .transform_query
             .get_mut(self.entity)
             .context("apply update_point_transform")?;
         let old_transform = *transform;
+        // scale is not used by editable points
         transform.translation = self.transform.translation;
+        transform.rotation = self.transform.rotation;
 
         Ok(Box::new(Undo {
-            entity: self.entity,
+            id,
             old_transform,
+            transform: *transform,
         }))
 
         // panic!("transform not found for {:?}", self.entity);
@@ -31,7 +38,8 @@ impl EditCommand for Command {
 impl UndoCommand for Undo {
     fn try_merge(&mut self, other: &dyn UndoCommand) -> bool {
         if let Some(other) = other.as_any().downcast_ref::<Self>() {
-            if self.entity == other.entity {
+            if self.id == other.id {
+                self.transform = other.transform;
                 info!("merged");
                 return true;
             }
@@ -40,13 +48,28 @@ impl UndoCommand for Undo {
     }
 
     fn undo(&self, undo_commands: &mut UndoCommands) -> Result<()> {
+        let entity = undo_commands.entity(self.id)?;
         let mut transform = undo_commands
             .transform_query
-            .get_mut(self.entity)
+            .get_mut(entity)
             .context("undo update_point_transform")?;
         *transform = self.old_transform;
         Ok(())
         // warn!("failed to undo point transform on {:?}", self.entity);
         // }
     }
+
+    fn redo(&self, undo_commands: &mut UndoCommands) -> Result<()> {
+        let entity = undo_commands.entity(self.id)?;
+        let mut transform = undo_commands
+            .transform_query
+            .get_mut(entity)
+            .context("redo update_point_transform")?;
+        *transform = self.transform;
+        Ok(())
+    }
+
+    fn description(&self) -> String {
+        "move point".into()
+    }
 }
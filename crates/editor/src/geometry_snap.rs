@@ -0,0 +1,174 @@
+use bevy::{color::palettes::tailwind, ecs::system::SystemParam, prelude::*};
+use sstree::{LineSegment, SpatialIndex};
+
+use crate::{resources, util::Orientation2d};
+
+// snapping of points in the ortho views to vertices, edge midpoints and faces of nearby brushes. Distances are
+// measured in the view plane, the depth of the snapped point is kept.
+
+#[derive(Debug, Clone, Copy)]
+pub enum SnapTargetKind {
+    Vertex,
+    EdgeMidpoint { a: Vec3, b: Vec3 },
+    // extent of a face that is seen edge-on in the view
+    Face { a: Vec3, b: Vec3 },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SnapTarget {
+    pub position: Vec3,
+    pub kind: SnapTargetKind,
+}
+
+// target of the last snap, highlighted for one frame
+#[derive(Resource, Default)]
+pub struct GeometrySnapTarget(pub Option<SnapTarget>);
+
+#[derive(SystemParam)]
+pub struct GeometrySnap<'w, 's> {
+    spatial_index: Res<'w, SpatialIndex>,
+    grid_settings: Res<'w, resources::GridSettings>,
+    editor_windows_2d: Res<'w, resources::EditorWindows2d>,
+    target: ResMut<'w, GeometrySnapTarget>,
+    brush_query: Query<'w, 's, &'static csg::Brush>,
+}
+
+impl<'w, 's> GeometrySnap<'w, 's> {
+    // `pos` snapped to geometry of the brushes not in `exclude`. None if geometry snapping is disabled or nothing is
+    // close enough, the caller should fall back to the grid then.
+    pub fn snap(
+        &mut self,
+        pos: Vec3,
+        orientation: &Orientation2d,
+        exclude: &[Entity],
+    ) -> Option<Vec3> {
+        if !self.grid_settings.snap_to_geometry {
+            return None;
+        }
+        let target = self.find_target(pos, orientation, exclude)?;
+        self.target.0 = Some(target);
+        Some(orientation.mix(target.position, pos))
+    }
+
+    fn find_target(
+        &self,
+        pos: Vec3,
+        orientation: &Orientation2d,
+        exclude: &[Entity],
+    ) -> Option<SnapTarget> {
+        let max_distance = self.grid_settings.geometry_snap_distance;
+        // unit vector along the view direction
+        let axis = orientation.mix(Vec3::ZERO, Vec3::ONE);
+        let in_plane = |v: Vec3| v - axis * axis.dot(v);
+
+        // candidates are measured to the line along the view direction through the cursor, limited to the visible
+        // depth. For everything in view that is the distance in the view plane.
+        let (view_min, view_max) = (
+            self.editor_windows_2d.view_min,
+            self.editor_windows_2d.view_max,
+        );
+        if !view_min.dot(axis).is_finite() || !view_max.dot(axis).is_finite() {
+            return None;
+        }
+        let column = LineSegment {
+            start: orientation.mix(pos, view_min),
+            end: orientation.mix(pos, view_max),
+        };
+
+        let mut nearest_point: Option<(f32, SnapTarget)> = None;
+        let mut nearest_face: Option<(f32, SnapTarget)> = None;
+        let consider = |nearest: &mut Option<(f32, SnapTarget)>, target: SnapTarget| {
+            let distance = in_plane(target.position - pos).length();
+            if distance < max_distance && nearest.iter().all(|(d, _)| distance < *d) {
+                *nearest = Some((distance, target));
+            }
+        };
+
+        // brushes come nearest first. Once their bounds are further away than the nearest vertex or midpoint nothing
+        // can win anymore, faces only count if there is no such point.
+        for (entity, bounds_distance) in
+            self.spatial_index
+                .find_k_nearest_within(column, usize::MAX, max_distance)
+        {
+            if nearest_point
+                .as_ref()
+                .is_some_and(|(distance, _)| bounds_distance >= *distance)
+            {
+                break;
+            }
+            if exclude.contains(&entity) {
+                continue;
+            }
+            let Ok(brush) = self.brush_query.get(entity) else {
+                continue;
+            };
+            let topology = brush.topology();
+            let visible = |v: &Vec3| self.editor_windows_2d.in_view_bounds(v);
+
+            for vertex in topology.vertices.iter().filter(|v| visible(v)) {
+                consider(
+                    &mut nearest_point,
+                    SnapTarget {
+                        position: *vertex,
+                        kind: SnapTargetKind::Vertex,
+                    },
+                );
+            }
+            for (a, b) in &topology.edges {
+                let (a, b) = (topology.vertices[*a], topology.vertices[*b]);
+                let midpoint = (a + b) * 0.5;
+                if visible(&midpoint) {
+                    consider(
+                        &mut nearest_point,
+                        SnapTarget {
+                            position: midpoint,
+                            kind: SnapTargetKind::EdgeMidpoint { a, b },
+                        },
+                    );
+                }
+            }
+            for (plane, face) in &topology.faces {
+                let plane = &brush.planes[*plane];
+                // only faces seen edge-on are lines in the view
+                if plane.normal.dot(axis).abs() > csg::PLANE_EPSILON {
+                    continue;
+                }
+                let tangent = axis.cross(plane.normal);
+                let (min, max) = face.iter().fold((f32::MAX, f32::MIN), |(min, max), v| {
+                    let t = topology.vertices[*v].dot(tangent);
+                    (min.min(t), max.max(t))
+                });
+                let depth = axis * axis.dot(topology.vertices[face[0]]);
+                let on_line = |t: f32| plane.normal * plane.w + tangent * t + depth;
+                let position = on_line(pos.dot(tangent).clamp(min, max));
+                consider(
+                    &mut nearest_face,
+                    SnapTarget {
+                        position,
+                        kind: SnapTargetKind::Face {
+                            a: on_line(min),
+                            b: on_line(max),
+                        },
+                    },
+                );
+            }
+        }
+        // vertices and midpoints win over faces
+        nearest_point.or(nearest_face).map(|(_, target)| target)
+    }
+}
+
+pub fn geometry_snap_highlight_system(
+    mut gizmos: Gizmos<crate::SelectionGizmos>,
+    mut target: ResMut<GeometrySnapTarget>,
+) {
+    let Some(target) = target.0.take() else {
+        return;
+    };
+    match target.kind {
+        SnapTargetKind::Vertex => (),
+        SnapTargetKind::EdgeMidpoint { a, b } => gizmos.line(a, b, tailwind::LIME_300),
+        SnapTargetKind::Face { a, b } => gizmos.line(a, b, tailwind::LIME_500),
+    }
+    gizmos.sphere(target.position, default(), 0.08, tailwind::LIME_500);
+}
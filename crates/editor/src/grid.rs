@@ -6,11 +6,15 @@ use crate::{
     util::{ortho_view_bounds, Orientation2d},
 };
 
+// upper limit of grid lines per view
+const MAX_GRID_LINES: f32 = 128.0;
+
 #[derive(Default, Reflect, GizmoConfigGroup)]
 struct GridGizmos {}
 fn gizmo_grid_system(
     mut gizmos: Gizmos<GridGizmos>,
     editor_windows_2d: Res<resources::EditorWindows2d>,
+    grid_settings: Res<resources::GridSettings>,
     camera_query: Query<(&GlobalTransform, &Camera)>,
 ) {
     // gizmos.line(Vec3::ZERO, Vec3::ONE, Color::BLACK);
@@ -38,48 +42,65 @@ fn gizmo_grid_system(
     let Some((lower_min, lower_max)) = ortho_view_bounds(lower_camera, lower_transform) else {
         return;
     };
+    // the grid gets coarser when zooming out, so that the lines do not get too dense
+    let extent = (upper_max - upper_min)
+        .max(lower_max - lower_min)
+        .max_element();
+    let mut step = grid_settings.grid_size();
+    while extent / step > MAX_GRID_LINES {
+        step *= 2.0;
+    }
+    let first_line = |min: f32| (min / step).floor() * step;
+    let num_lines = |min: f32, max: f32| ((max - min) / step) as i32 + 2;
+
     if lower.orientation == Orientation2d::Front {
-        let ystart = lower_min.y.floor();
-        let zstart = upper_min.z.floor();
-        let num_lines_yz = (upper_max.z - upper_min.z).max(lower_max.y - lower_min.y) as i32 + 1;
+        let ystart = first_line(lower_min.y);
+        let zstart = first_line(upper_min.z);
+        let num_lines_yz =
+            num_lines(upper_min.z, upper_max.z).max(num_lines(lower_min.y, lower_max.y));
 
         for yz in 0..num_lines_yz {
+            let offset = yz as f32 * step;
             gizmos.line(
-                Vec3::new(upper_min.x, yz as f32 + ystart, yz as f32 + zstart),
-                Vec3::new(upper_max.x, yz as f32 + ystart, yz as f32 + zstart),
+                Vec3::new(upper_min.x, ystart + offset, zstart + offset),
+                Vec3::new(upper_max.x, ystart + offset, zstart + offset),
                 tailwind::BLUE_500,
             );
         }
-        let num_lines_x = (upper_max.x - upper_min.x) as i32 + 1;
-        let xstart = upper_min.x.floor();
+        let num_lines_x = num_lines(upper_min.x, upper_max.x);
+        let xstart = first_line(upper_min.x);
         for x in 0..num_lines_x {
+            let x = xstart + x as f32 * step;
             gizmos.line(
-                Vec3::new(x as f32 + xstart, lower_min.y, upper_min.z),
-                Vec3::new(x as f32 + xstart, lower_max.y, upper_max.z),
+                Vec3::new(x, lower_min.y, upper_min.z),
+                Vec3::new(x, lower_max.y, upper_max.z),
                 tailwind::BLUE_500,
             );
         }
     } else {
-        let xstart = upper_min.x.floor();
-        let ystart = lower_min.y.floor();
-        let num_lines_xy = (upper_max.x - upper_min.x).max(lower_max.y - lower_min.y) as i32 + 1;
+        let xstart = first_line(upper_min.x);
+        let ystart = first_line(lower_min.y);
+        let num_lines_xy =
+            num_lines(upper_min.x, upper_max.x).max(num_lines(lower_min.y, lower_max.y));
 
         // gizmos.line(upper_min, upper_max, Color::GREEN);
         // gizmos.line(lower_min, lower_max, Color::YELLOW_GREEN);
         // info!("num_lines: {}", num_lines);
         for xy in 0..num_lines_xy {
+            let offset = xy as f32 * step;
             gizmos.line(
-                Vec3::new(xy as f32 + xstart, xy as f32 + ystart, upper_min.z),
-                Vec3::new(xy as f32 + xstart, xy as f32 + ystart, upper_max.z),
+                Vec3::new(xstart + offset, ystart + offset, upper_min.z),
+                Vec3::new(xstart + offset, ystart + offset, upper_max.z),
                 tailwind::BLUE_500,
             );
         }
-        let num_lines_z = (upper_max.z - upper_min.z) as i32 + 1;
-        let zstart = upper_min.z.floor();
+        let num_lines_z = num_lines(upper_min.z, upper_max.z);
+        let zstart = first_line(upper_min.z);
         for z in 0..num_lines_z {
+            let z = zstart + z as f32 * step;
             gizmos.line(
-                Vec3::new(upper_min.x, lower_min.y, z as f32 + zstart),
-                Vec3::new(upper_max.x, lower_max.y, z as f32 + zstart),
+                Vec3::new(upper_min.x, lower_min.y, z),
+                Vec3::new(upper_max.x, lower_max.y, z),
                 tailwind::BLUE_500,
             );
         }
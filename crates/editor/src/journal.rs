@@ -0,0 +1,506 @@
+// crash recovery: every add / update / removal of an editor object is mirrored into a sled db (one record per object
+// id), so the db always contains the current scene. If the editor did not exit cleanly the last session can be
+// restored from it on the next start. The journal is also the source for the periodic scene.ron autosave backups.
+
+use std::{path::PathBuf, time::Duration};
+
+use bevy::{app::AppExit, prelude::*, utils::HashMap};
+use bevy_egui::EguiContexts;
+use bevy_inspector_egui::egui;
+use sstree::SpatialIndex;
+
+use crate::{
+    components::{self, EditorObjectId},
+    resources,
+    scene::{self, write_scene_file, ExternalEditorObject, LoadedScene, QuarantinedObject},
+    systems::clear_scene,
+    undo::UndoStack,
+};
+
+const CLEAN_EXIT_KEY: &str = "clean_exit";
+const VERSION_KEY: &str = "version";
+
+// insert before adding the EditorPlugin to override the defaults
+#[derive(Resource, Clone, Debug)]
+pub struct JournalSettings {
+    pub db_path: PathBuf,
+    pub autosave_dir: PathBuf,
+    pub autosave_interval: Duration,
+    // number of rotated backups to keep, 0 disables autosave
+    pub autosave_backups: usize,
+}
+
+impl Default for JournalSettings {
+    fn default() -> Self {
+        Self {
+            db_path: "editor_journal".into(),
+            autosave_dir: "autosave".into(),
+            autosave_interval: Duration::from_secs(120),
+            autosave_backups: 5,
+        }
+    }
+}
+
+impl JournalSettings {
+    // backup 0 is the most recent one
+    pub fn autosave_path(&self, index: usize) -> PathBuf {
+        self.autosave_dir.join(format!("scene.{index}.ron"))
+    }
+}
+
+struct JournalDb {
+    objects: sled::Db,
+    meta: sled::Tree,
+}
+
+#[derive(Resource, Default)]
+pub struct Journal {
+    db: Option<JournalDb>,
+    // RemovedComponents only reports the entity, so remember which object id was logged for it
+    logged: HashMap<Entity, EditorObjectId>,
+    // objects of an unclean session, waiting for the user to decide. Logging is suspended meanwhile.
+    pending_restore: Option<LoadedScene>,
+    // changed since last autosave
+    dirty: bool,
+    // scene format version of the records
+    version: u32,
+}
+
+fn object_key(id: EditorObjectId) -> String {
+    ron::ser::to_string(&id).unwrap()
+}
+
+impl Journal {
+    fn open(settings: &JournalSettings) -> anyhow::Result<Self> {
+        let objects = sled::open(&settings.db_path)?;
+        let meta = objects.open_tree("meta")?;
+        // a missing flag means the journal is new
+        let clean_exit = meta.get(CLEAN_EXIT_KEY)?.as_deref() != Some(&b"0"[..]);
+        // journals from before the versioning contain version 0 records
+        let version = match meta.get(VERSION_KEY)? {
+            Some(version) => std::str::from_utf8(&version)?.parse()?,
+            None => 0,
+        };
+
+        let mut journal = Journal {
+            db: Some(JournalDb { objects, meta }),
+            version,
+            ..default()
+        };
+        let loaded_scene = journal.objects()?;
+        let has_records = !loaded_scene.objects.is_empty() || !loaded_scene.quarantine.is_empty();
+        if !clean_exit && has_records {
+            info!(
+                "unclean exit: {} objects in journal",
+                loaded_scene.objects.len()
+            );
+            journal.pending_restore = Some(loaded_scene);
+        } else {
+            journal.clear()?;
+        }
+        journal.set_clean_exit(false)?;
+        Ok(journal)
+    }
+
+    // all objects currently in the journal, ordered by id. Broken records end up in quarantine (index is the record
+    // position).
+    pub(crate) fn objects(&self) -> anyhow::Result<LoadedScene> {
+        let mut loaded_scene = LoadedScene::default();
+        let Some(db) = &self.db else {
+            return Ok(loaded_scene);
+        };
+        for (index, record) in db.objects.iter().enumerate() {
+            let (_, value) = record?;
+            let res = std::str::from_utf8(&value)
+                .map_err(anyhow::Error::from)
+                .and_then(|value| Ok(ron::from_str::<ron::Value>(value)?));
+            match res.and_then(|value| scene::parse_object(value, self.version)) {
+                Ok(object) => loaded_scene.objects.push((index, object)),
+                Err(err) => loaded_scene.quarantine.push(QuarantinedObject::new(
+                    index,
+                    &err,
+                    &String::from_utf8_lossy(&value),
+                )),
+            }
+        }
+        loaded_scene.objects.sort_by_key(|(_, object)| object.id());
+        Ok(loaded_scene)
+    }
+
+    pub(crate) fn pending_restore(&self) -> Option<&[(usize, ExternalEditorObject)]> {
+        self.pending_restore
+            .as_ref()
+            .map(|loaded_scene| &loaded_scene.objects[..])
+    }
+
+    // NOTE: also upgrades the journal to the current format version, since all old records are gone
+    fn clear(&mut self) -> anyhow::Result<()> {
+        if let Some(db) = &self.db {
+            db.objects.clear()?;
+            db.meta
+                .insert(VERSION_KEY, scene::SCENE_VERSION.to_string().as_bytes())?;
+            db.objects.flush()?;
+            db.meta.flush()?;
+        }
+        self.version = scene::SCENE_VERSION;
+        self.logged.clear();
+        Ok(())
+    }
+
+    fn set_clean_exit(&self, clean_exit: bool) -> anyhow::Result<()> {
+        if let Some(db) = &self.db {
+            db.meta
+                .insert(CLEAN_EXIT_KEY, if clean_exit { "1" } else { "0" })?;
+            db.meta.flush()?;
+            db.objects.flush()?;
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, entity: Entity, object: &ExternalEditorObject) -> anyhow::Result<()> {
+        let (Some(db), Some(id)) = (&self.db, object.id()) else {
+            return Ok(());
+        };
+        self.logged.insert(entity, id);
+        db.objects
+            .insert(object_key(id), ron::ser::to_string(object)?.as_bytes())?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn remove(&mut self, id: EditorObjectId) -> anyhow::Result<()> {
+        let Some(db) = &self.db else {
+            return Ok(());
+        };
+        db.objects.remove(object_key(id))?;
+        self.dirty = true;
+        Ok(())
+    }
+}
+
+pub fn setup_journal_system(mut commands: Commands, settings: Res<JournalSettings>) {
+    match Journal::open(&settings) {
+        Ok(journal) => commands.insert_resource(journal),
+        Err(err) => warn!(
+            "failed to open journal {:?}, crash recovery disabled: {:?}",
+            settings.db_path, err
+        ),
+    }
+}
+
+pub fn journal_active(journal: Res<Journal>) -> bool {
+    journal.db.is_some() && journal.pending_restore.is_none()
+}
+
+pub fn restore_pending(journal: Res<Journal>) -> bool {
+    journal.pending_restore.is_some()
+}
+
+// NOTE: must not run while a restore is pending (see journal_active): the Changed filters are relative to the last run
+// of the system, so the first run after the decision logs the complete scene.
+#[allow(clippy::type_complexity)]
+pub fn log_editor_objects(
+    mut journal: ResMut<Journal>,
+    editor_objects: Res<resources::EditorObjects>,
+    mut removed: RemovedComponents<EditorObjectId>,
+    brush_query: Query<
+        (
+            Entity,
+            &EditorObjectId,
+            &csg::Brush,
+            &components::BrushMaterialProperties,
+        ),
+        Or<(
+            Changed<csg::Brush>,
+            Changed<components::BrushMaterialProperties>,
+            Changed<EditorObjectId>,
+        )>,
+    >,
+    light_query: Query<
+        (
+            Entity,
+            &EditorObjectId,
+            &components::PointLightProperties,
+            &Transform,
+        ),
+        Or<(
+            Changed<components::PointLightProperties>,
+            Changed<Transform>,
+            Changed<EditorObjectId>,
+        )>,
+    >,
+    directional_light_query: Query<
+        (
+            Entity,
+            &EditorObjectId,
+            &components::DirectionalLightProperties,
+            &Transform,
+        ),
+        Or<(
+            Changed<components::DirectionalLightProperties>,
+            Changed<Transform>,
+            Changed<EditorObjectId>,
+        )>,
+    >,
+) {
+    // removals first: undo / redo re-spawn objects with the same id on a new entity, which is logged below
+    for entity in removed.read() {
+        let Some(id) = journal.logged.remove(&entity) else {
+            continue;
+        };
+        if editor_objects.get(id).is_some() {
+            continue;
+        }
+        debug!("journal remove: {:?}", id);
+        if let Err(err) = journal.remove(id) {
+            warn!("failed to journal removal of {:?}: {:?}", id, err);
+        }
+    }
+
+    let brushes = brush_query
+        .iter()
+        .map(|(entity, id, brush, material_properties)| {
+            (
+                entity,
+                ExternalEditorObject::Brush {
+                    id: Some(*id),
+                    brush: brush.clone(),
+                    material_properties: material_properties.clone(),
+                },
+            )
+        });
+    let lights = light_query
+        .iter()
+        .map(|(entity, id, light_properties, transform)| {
+            (
+                entity,
+                ExternalEditorObject::PointLight {
+                    id: Some(*id),
+                    translation: transform.translation,
+                    light_properties: light_properties.clone(),
+                },
+            )
+        });
+    let directional_lights =
+        directional_light_query
+            .iter()
+            .map(|(entity, id, light_properties, transform)| {
+                (
+                    entity,
+                    ExternalEditorObject::DirectionalLight {
+                        id: Some(*id),
+                        translation: transform.translation,
+                        rotation: transform.rotation,
+                        light_properties: light_properties.clone(),
+                    },
+                )
+            });
+
+    for (entity, object) in brushes.chain(lights).chain(directional_lights) {
+        debug!("journal update: {:?}", object.id());
+        if let Err(err) = journal.insert(entity, &object) {
+            warn!("failed to journal update of {:?}: {:?}", object.id(), err);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn journal_restore_ui_system(
+    mut commands: Commands,
+    mut egui_context: EguiContexts,
+    mut journal: ResMut<Journal>,
+    mut editor_objects: ResMut<resources::EditorObjects>,
+    mut undo_stack: ResMut<UndoStack>,
+    mut spatial_index: ResMut<SpatialIndex>,
+    mut scene_file: ResMut<resources::SceneFile>,
+    object_query: Query<
+        Entity,
+        Or<(
+            With<csg::Brush>,
+            With<components::PointLightProperties>,
+            With<components::DirectionalLightProperties>,
+        )>,
+    >,
+) {
+    let Some(num_objects) = journal.pending_restore().map(|objects| objects.len()) else {
+        return;
+    };
+
+    let mut restore = None;
+    egui::Window::new("Restore last session")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.label(format!(
+                "The last session did not exit cleanly. Restore {num_objects} objects from the recovery journal?"
+            ));
+            ui.horizontal(|ui| {
+                if ui.button("Restore").clicked() {
+                    restore = Some(true);
+                }
+                if ui.button("Discard").clicked() {
+                    restore = Some(false);
+                }
+            });
+        });
+
+    let Some(restore) = restore else {
+        return;
+    };
+    let loaded_scene = journal.pending_restore.take().unwrap_or_default();
+    // the journal is re-populated from the scene as soon as logging resumes
+    if let Err(err) = journal.clear() {
+        warn!("failed to clear journal: {:?}", err);
+    }
+    if !restore {
+        return;
+    }
+
+    // replace the current scene, same as loading a scene file
+    clear_scene(
+        &mut commands,
+        object_query.iter(),
+        &mut spatial_index,
+        &mut editor_objects,
+        &mut undo_stack,
+    );
+    info!(
+        "restoring {} objects from journal",
+        loaded_scene.objects.len()
+    );
+    scene_file.quarantine =
+        loaded_scene.spawn(&mut commands, &mut editor_objects, &mut spatial_index);
+    // the restored scene does not correspond to any file
+    scene_file.path = None;
+    scene_file.mark_unsaved();
+}
+
+pub fn autosave_system(
+    time: Res<Time>,
+    settings: Res<JournalSettings>,
+    mut journal: ResMut<Journal>,
+    mut since_autosave: Local<Duration>,
+) {
+    *since_autosave += time.delta();
+    if settings.autosave_backups == 0
+        || *since_autosave < settings.autosave_interval
+        || !journal.dirty
+    {
+        return;
+    }
+    *since_autosave = Duration::ZERO;
+    journal.dirty = false;
+
+    let objects = match journal.objects() {
+        Ok(loaded_scene) => loaded_scene
+            .objects
+            .into_iter()
+            .map(|(_, object)| object)
+            .collect::<Vec<_>>(),
+        Err(err) => {
+            warn!("autosave: failed to read journal: {:?}", err);
+            return;
+        }
+    };
+    if let Err(err) = rotate_autosave_backups(&settings) {
+        warn!("autosave: failed to rotate backups: {:?}", err);
+    }
+    let path = settings.autosave_path(0);
+    match write_scene_file(&path, &objects, &[]) {
+        Ok(()) => info!("autosave: {} objects written to {:?}", objects.len(), path),
+        Err(err) => warn!("autosave: failed to write {:?}: {:?}", path, err),
+    }
+}
+
+fn rotate_autosave_backups(settings: &JournalSettings) -> std::io::Result<()> {
+    std::fs::create_dir_all(&settings.autosave_dir)?;
+    // the oldest backup is overwritten by its successor
+    for index in (1..settings.autosave_backups).rev() {
+        let from = settings.autosave_path(index - 1);
+        if from.exists() {
+            std::fs::rename(from, settings.autosave_path(index))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn journal_exit_system(mut exit_events: EventReader<AppExit>, journal: Res<Journal>) {
+    if exit_events.read().next().is_none() {
+        return;
+    }
+    // keep the journal for the next start if the user did not decide about the restore yet
+    if journal.pending_restore.is_some() {
+        return;
+    }
+    if let Err(err) = journal.set_clean_exit(true) {
+        warn!("failed to mark clean exit in journal: {:?}", err);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn point_light(id: u64) -> ExternalEditorObject {
+        ExternalEditorObject::PointLight {
+            id: Some(EditorObjectId(id)),
+            translation: Vec3::new(id as f32, 0.0, 0.0),
+            light_properties: default(),
+        }
+    }
+
+    #[test]
+    fn test_journal_unclean_exit() {
+        let dir = std::env::temp_dir().join(format!("editor_journal_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let settings = JournalSettings {
+            db_path: dir.join("journal"),
+            autosave_dir: dir.join("autosave"),
+            autosave_backups: 2,
+            ..default()
+        };
+
+        // first session: crashes with two objects left in the journal
+        {
+            let mut journal = Journal::open(&settings).unwrap();
+            assert!(journal.pending_restore().is_none());
+            for id in [3, 1, 2] {
+                journal
+                    .insert(Entity::from_raw(id as u32), &point_light(id))
+                    .unwrap();
+            }
+            journal.remove(EditorObjectId(2)).unwrap();
+        }
+
+        // second session: offers the objects of the first one, then exits cleanly
+        {
+            let journal = Journal::open(&settings).unwrap();
+            let ids = journal
+                .pending_restore()
+                .unwrap()
+                .iter()
+                .map(|(_, object)| object.id())
+                .collect::<Vec<_>>();
+            assert_eq!(ids, [Some(EditorObjectId(1)), Some(EditorObjectId(3))]);
+            journal.set_clean_exit(true).unwrap();
+        }
+
+        // third session: nothing to restore
+        {
+            let journal = Journal::open(&settings).unwrap();
+            assert!(journal.pending_restore().is_none());
+            assert!(journal.objects().unwrap().objects.is_empty());
+        }
+
+        for _ in 0..3 {
+            rotate_autosave_backups(&settings).unwrap();
+            write_scene_file(&settings.autosave_path(0), &[point_light(1)], &[]).unwrap();
+        }
+        assert!(settings.autosave_path(0).exists());
+        assert!(settings.autosave_path(1).exists());
+        assert!(!settings.autosave_path(2).exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
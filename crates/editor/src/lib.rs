@@ -9,12 +9,16 @@ use shared::AppState;
 
 pub mod clip_systems;
 pub mod components;
+pub mod create_brush_systems;
 pub mod edit_commands;
+pub mod geometry_snap;
 pub mod grid;
 pub mod gui_systems;
+pub mod journal;
 pub mod main3d_systems;
 pub mod ortho_systems;
 pub mod resources;
+pub mod scene;
 pub mod systems;
 pub mod undo;
 pub mod util;
@@ -50,23 +54,37 @@ impl Plugin for EditorPlugin {
                 systems::setup_selection_gizmos,
                 ortho_systems::setup_editor_system,
                 clip_systems::clip_plane_setup_system.after(systems::setup),
+                journal::setup_journal_system,
             ),
         ); // uses resources::materials
 
         app.init_resource::<undo::UndoStack>();
+        app.init_resource::<resources::EditorObjects>();
+        app.init_resource::<resources::SceneFile>();
         app.init_resource::<resources::SelectionPickSet>();
+        app.init_resource::<resources::RubberBand>();
+        app.init_resource::<resources::CreateBrushState>();
+        app.init_resource::<resources::PolygonToolState>();
+        app.init_resource::<resources::TransformTool>();
+        app.init_resource::<resources::GridSettings>();
+        app.init_resource::<geometry_snap::GeometrySnapTarget>();
         app.init_resource::<resources::EditorWindows2d>();
         app.init_resource::<resources::Materials>();
         app.init_resource::<resources::MaterialBrowser>();
         app.init_resource::<sstree::SpatialIndex>();
         app.init_resource::<resources::ClipState>();
-        app.init_resource::<systems::LogSink>(); // TODO: move to resources
+        app.init_resource::<resources::EguiKeyboard>();
+        app.init_resource::<journal::JournalSettings>();
+        app.init_resource::<journal::Journal>();
         app.add_event::<CleanupCsgOutputEvent>();
 
         app.add_systems(
             Update,
-            systems::editor_input_system.run_if(in_state(AppState::Editor)),
+            systems::editor_input_system
+                .run_if(in_state(AppState::Editor))
+                .run_if(util::keyboard_free),
         );
+        app.add_systems(PreUpdate, util::track_egui_keyboard_system);
         app.add_systems(OnEnter(AppState::Editor), ortho_systems::enter_editor_state);
         app.add_systems(OnExit(AppState::Editor), ortho_systems::leave_editor_state);
 
@@ -107,10 +125,20 @@ impl Plugin for EditorPlugin {
                 ortho_systems::edit_input_system,
                 ortho_systems::control_input_wm_system,
                 ortho_systems::select_input_system,
+                ortho_systems::rubber_band_select_system,
+                ortho_systems::transform_pivot_system,
+                ortho_systems::brush_handles_system,
+                geometry_snap::geometry_snap_highlight_system,
+                create_brush_systems::create_brush_system,
+                create_brush_systems::polygon_tool_system,
                 systems::load_save_editor_objects,
                 clip_systems::clip_plane_control_system,
                 main3d_systems::select_input_system,
-                systems::log_editor_objects,
+                journal::log_editor_objects.run_if(journal::journal_active),
+                journal::journal_restore_ui_system.run_if(journal::restore_pending),
+                journal::autosave_system.run_if(journal::journal_active),
+                undo::undo_system,
+                systems::assign_editor_object_ids_system,
             ),
         );
         app.add_systems(
@@ -161,6 +189,7 @@ impl Plugin for EditorPlugin {
             Update,
             wm_systems::write_view_settings.run_if(on_timer(Duration::from_millis(500))),
         );
+        app.add_systems(Last, journal::journal_exit_system);
         app.init_gizmo_group::<SelectionGizmos>();
     }
 }
@@ -1,4 +1,5 @@
 use bevy::prelude::*;
+use sstree::SpatialIndex;
 
 use super::{components, edit_commands::EditCommands, resources, util};
 use crate::edit_commands::set_brush_material;
@@ -9,7 +10,8 @@ pub fn select_input_system(
     mut event_reader: EventReader<util::WmEvent>,
     mut material_browser: ResMut<resources::MaterialBrowser>,
     camera_query: Query<(&GlobalTransform, &Camera), With<components::Main3dCamera>>,
-    processed_csg_query: Query<(Entity, &components::ProcessedCsg)>,
+    spatial_index: Res<SpatialIndex>,
+    processed_csg_query: Query<&components::ProcessedCsg>,
 ) {
     for event in event_reader.read() {
         if let util::WmEvent::Clicked {
@@ -46,7 +48,14 @@ pub fn select_input_system(
             // find clicked face
             let mut closest_hit = None;
             let mut closest_hit_distance = f32::INFINITY;
-            for (entity, processed_csg) in &processed_csg_query {
+            // candidates come nearest first, the rest can not be closer once their bounds are behind the closest hit
+            for (entity, bounds_distance) in spatial_index.raycast(ray, f32::INFINITY) {
+                if bounds_distance > closest_hit_distance {
+                    break;
+                }
+                let Ok(processed_csg) = processed_csg_query.get(entity) else {
+                    continue;
+                };
                 'poly_loop: for polygon in processed_csg.bsp.all_polygons() {
                     let mut res = Vec::new();
                     polygon.get_triangles(&mut res);
@@ -1,7 +1,10 @@
 use bevy::{
+    color::palettes::tailwind,
+    math::Affine3A,
     prelude::*,
     render::{
         camera::{Projection, RenderTarget, ScalingMode},
+        primitives::Frustum,
         view::RenderLayers,
     },
     utils::HashSet,
@@ -15,12 +18,17 @@ use super::{
 };
 use crate::{
     edit_commands::{update_brush_drag, update_point_transform},
+    geometry_snap::GeometrySnap,
     util::ortho_view_bounds,
 };
 
 use shared::render_layers;
+use sstree::SpatialIndex;
 
 use csg::PLANE_EPSILON;
+
+// max distance (in the view plane) of the cursor to a vertex or edge handle
+const HANDLE_PICK_DISTANCE: f32 = 0.2;
 // systems related to 2d windows
 
 pub fn setup_editor_system(mut editor_windows_2d: ResMut<resources::EditorWindows2d>) {
@@ -241,6 +249,7 @@ pub fn adjust_clip_planes_system(
 
     mut editor_windows_2d: ResMut<resources::EditorWindows2d>,
     mut camera_query: Query<(&GlobalTransform, &Camera, &mut Projection, &mut Transform)>,
+    egui_keyboard: Res<resources::EguiKeyboard>,
 ) {
     let editor_windows_2d = &mut *editor_windows_2d;
 
@@ -329,7 +338,7 @@ pub fn adjust_clip_planes_system(
         *lower_orientation.get_up_axis_mut(&mut editor_windows_2d.view_min) = min;
     }
 
-    if keycodes.just_pressed(KeyCode::F2) {
+    if keycodes.just_pressed(KeyCode::F2) && !egui_keyboard.0 {
         let mut right = 0.0;
         if let Some(window) = editor_windows_2d.windows.get_mut(resources::UPPER_WINDOW) {
             window.orientation = window.orientation.flipped();
@@ -357,9 +366,16 @@ pub fn edit_input_system(
     mut event_reader: EventReader<util::WmEvent>,
     keycodes: Res<ButtonInput<KeyCode>>,
     editor_windows_2d: Res<resources::EditorWindows2d>,
+    transform_tool: Res<resources::TransformTool>,
+    create_state: Res<resources::CreateBrushState>,
+    grid_settings: Res<resources::GridSettings>,
+    mut geometry_snap: GeometrySnap,
 
     camera_query: Query<(&GlobalTransform, &Camera)>,
-    brush_query: Query<&csg::Brush, Without<components::DragAction>>,
+    brush_query: Query<
+        (&csg::Brush, &components::CsgRepresentation),
+        Without<components::DragAction>,
+    >,
     brush_drag_query: Query<
         (
             Entity,
@@ -380,6 +396,10 @@ pub fn edit_input_system(
                 button: util::WmMouseButton::Left,
                 pointer_state,
             } => {
+                // ctrl-drag is the rubber band selection, plain drags create brushes in create mode
+                if pointer_state.modifiers.ctrl || create_state.create_mode {
+                    continue;
+                }
                 let Some(window) = editor_windows_2d.windows.get(focused_name) else {
                     continue;
                 };
@@ -397,17 +417,93 @@ pub fn edit_input_system(
 
                 info!("click ray {}: {:?}", focused_name, ray);
 
-                if let Ok(primary) = selected_query.get_single() {
-                    // match brush_query.get(primary) {
-                    if let Ok(brush) = brush_query.get(primary) {
-                        let affected_faces = brush.get_planes_behind_ray(ray);
+                if transform_tool.mode == components::TransformMode::Vertices {
+                    edit_commands.begin_group("drag vertices");
+                    for selected in &selected_query {
+                        let Ok((brush, _)) = brush_query.get(selected) else {
+                            continue;
+                        };
+                        let topology = brush.topology();
+                        let vertices = picked_vertices(&topology, ray, &editor_windows_2d);
+                        if vertices.is_empty() {
+                            continue;
+                        }
+                        info!("start vertex drag for {:?}: {:?}", selected, vertices);
+                        commands.entity(selected).insert(components::DragAction {
+                            start_ray: ray,
+                            action: components::DragActionType::Vertices {
+                                start_brush: brush.clone(),
+                                topology,
+                                vertices,
+                            },
+                        });
+                    }
+                    continue;
+                }
+
+                if transform_tool.mode.uses_pivot() {
+                    let center = mean_position(selected_query.iter().filter_map(|entity| {
+                        brush_query
+                            .get(entity)
+                            .map(|(_, csg_repr)| csg_repr.bounds.center)
+                            .or_else(|_| {
+                                edit_commands
+                                    .transform_query
+                                    .get(entity)
+                                    .map(|transform| transform.translation)
+                            })
+                            .ok()
+                    }));
+                    let Some(pivot) = transform_tool.pivot.or(center) else {
+                        continue;
+                    };
+                    let mode = transform_tool.mode;
+                    edit_commands.begin_group(if mode == components::TransformMode::Rotate {
+                        "rotate selection"
+                    } else {
+                        "scale selection"
+                    });
+                    for selected in &selected_query {
+                        let action = if let Ok((brush, _)) = brush_query.get(selected) {
+                            components::DragActionType::BrushTransform {
+                                mode,
+                                pivot,
+                                start_brush: brush.clone(),
+                            }
+                        } else if let Ok(transform) = edit_commands.transform_query.get(selected) {
+                            components::DragActionType::PointTransform {
+                                mode,
+                                pivot,
+                                start_transform: *transform,
+                            }
+                        } else {
+                            continue;
+                        };
+                        commands.entity(selected).insert(components::DragAction {
+                            start_ray: ray,
+                            action,
+                        });
+                    }
+                    continue;
+                }
+
+                // the whole selection is dragged as one undo step. Face drags only make sense for a single brush.
+                let single_selection = selected_query.iter().count() == 1;
+                edit_commands.begin_group("drag selection");
+                for selected in &selected_query {
+                    if let Ok((brush, _)) = brush_query.get(selected) {
+                        let affected_faces = if single_selection {
+                            brush.get_planes_behind_ray(ray)
+                        } else {
+                            Vec::new()
+                        };
 
                         if !affected_faces.is_empty() {
-                            commands.entity(primary).insert(components::DragAction {
+                            commands.entity(selected).insert(components::DragAction {
                                 start_ray: ray,
                                 action: components::DragActionType::Face { affected_faces },
                             });
-                            info!("start face drag for {:?}", primary); // the crowd put on their affected_faces as The Iron Sheik did his signature face-drag on el Pollo Loco
+                            info!("start face drag for {:?}", selected); // the crowd put on their affected_faces as The Iron Sheik did his signature face-drag on el Pollo Loco
                         } else {
                             let affected_faces = brush
                                 .planes
@@ -415,19 +511,19 @@ pub fn edit_input_system(
                                 .enumerate()
                                 .map(|(i, face)| (i, face.w))
                                 .collect();
-                            commands.entity(primary).insert(components::DragAction {
+                            commands.entity(selected).insert(components::DragAction {
                                 start_ray: ray,
                                 action: components::DragActionType::WholeBrush { affected_faces },
                             });
-                            info!("start whole-brush drag for {:?}", primary);
+                            info!("start whole-brush drag for {:?}", selected);
                         }
-                    } else if let Ok(transform) = edit_commands.transform_query.get(primary) {
+                    } else if let Ok(transform) = edit_commands.transform_query.get(selected) {
                         info!("light drag start");
 
-                        commands.entity(primary).insert(components::DragAction {
+                        commands.entity(selected).insert(components::DragAction {
                             start_ray: ray,
                             action: components::DragActionType::NonBrush {
-                                start_translation: transform.translation,
+                                start_transform: *transform,
                             },
                         });
                     }
@@ -453,6 +549,16 @@ pub fn edit_input_system(
                     continue;
                 };
 
+                // apply grid-snapping to drag-delta
+                let snap = grid_settings.translation_snap(&keycodes);
+                let angle_snap = grid_settings.rotation_snap(&keycodes);
+                // dragged objects do not snap to themselves
+                let dragged = brush_drag_query
+                    .iter()
+                    .map(|(entity, _, _, _)| entity)
+                    .chain(point_drag_query.iter().map(|(entity, _)| entity))
+                    .collect::<Vec<_>>();
+
                 // update dragged objects. Do this in two steps, only touch EditorObject as mutable if there is a relevant change
                 // to prevent triggering the bevy change detection.
                 // let mut csg_updates = Vec::new();
@@ -460,13 +566,21 @@ pub fn edit_input_system(
                     let drag_delta = ray.origin - drag_action.start_ray.origin;
                     debug!("drag: {:?} on brush {:?}", drag_delta, entity);
 
-                    // apply grid-snapping to drag-delta
-                    let snap = if keycodes.pressed(KeyCode::AltLeft) {
-                        0.5
-                    } else {
-                        0.1
+                    // the point under the cursor (or the dragged vertex) snaps to geometry, otherwise the
+                    // delta snaps to the grid
+                    let grab = match &drag_action.action {
+                        components::DragActionType::BrushTransform { .. } => None,
+                        components::DragActionType::Vertices {
+                            topology, vertices, ..
+                        } => Some(topology.vertices[vertices[0]]),
+                        _ => Some(drag_action.start_ray.origin),
                     };
-                    let drag_delta = drag_delta.snap(snap);
+                    let geometry_delta = grab.and_then(|grab| {
+                        geometry_snap
+                            .snap(grab + drag_delta, &window.orientation, &dragged)
+                            .map(|target| target - grab)
+                    });
+                    let drag_delta = geometry_delta.unwrap_or(drag_delta.snap(snap));
 
                     match &drag_action.action {
                         components::DragActionType::Face { affected_faces }
@@ -506,6 +620,73 @@ pub fn edit_input_system(
                                 }
                             }
                         }
+                        components::DragActionType::BrushTransform {
+                            mode,
+                            pivot,
+                            start_brush,
+                        } => {
+                            let Some(transform) = drag_transform(
+                                *mode,
+                                *pivot,
+                                drag_action.start_ray,
+                                ray,
+                                snap,
+                                angle_snap,
+                            ) else {
+                                continue;
+                            };
+                            let new_brush = start_brush.transformed(&transform);
+                            if !brush_changed(brush, &new_brush) {
+                                continue;
+                            }
+                            let res = edit_commands.apply(update_brush_drag::Command {
+                                entity,
+                                start_brush: brush.clone(),
+                                brush: new_brush,
+                            });
+                            if let Err(err) = res {
+                                warn!("update_brush_drag apply failed: {:?}", err);
+                            }
+                        }
+                        components::DragActionType::Vertices {
+                            start_brush,
+                            topology,
+                            vertices,
+                        } => {
+                            // the handles are snapped in the view plane, their depth is kept
+                            let moved = vertices
+                                .iter()
+                                .map(|i| {
+                                    let start = topology.vertices[*i];
+                                    let pos = match geometry_delta {
+                                        Some(delta) => start + delta,
+                                        None => (start + ray.origin - drag_action.start_ray.origin)
+                                            .snap(snap),
+                                    };
+                                    (*i, window.orientation.mix(pos, start))
+                                })
+                                .collect::<Vec<_>>();
+                            // invalid positions are skipped, the brush keeps the last valid shape
+                            let new_brush = match start_brush.with_moved_vertices(topology, &moved)
+                            {
+                                Ok(new_brush) => new_brush,
+                                Err(err) => {
+                                    debug!("vertex drag rejected: {}", err);
+                                    continue;
+                                }
+                            };
+                            if !brush_changed(brush, &new_brush) {
+                                continue;
+                            }
+                            let res = edit_commands.apply(update_brush_drag::Command {
+                                entity,
+                                start_brush: brush.clone(),
+                                brush: new_brush,
+                            });
+                            if let Err(err) = res {
+                                warn!("update_brush_drag apply failed: {:?}", err);
+                            }
+                        }
                         _ => warn!("invalid drag action in brush object"),
                     }
                 }
@@ -516,12 +697,51 @@ pub fn edit_input_system(
                     debug!("drag: {:?} on point {:?}", drag_delta, entity);
 
                     match &drag_action.action {
-                        components::DragActionType::NonBrush { start_translation } => {
+                        components::DragActionType::NonBrush { start_transform } => {
+                            let translation = start_transform.translation + drag_delta;
+                            let translation = geometry_snap
+                                .snap(translation, &window.orientation, &dragged)
+                                .unwrap_or(translation.snap(snap));
                             let res = edit_commands.apply(update_point_transform::Command {
                                 entity,
-                                transform: Transform::from_translation(
-                                    (*start_translation + drag_delta).snap(0.1),
-                                ),
+                                transform: Transform {
+                                    translation,
+                                    ..*start_transform
+                                },
+                            });
+                            if let Err(err) = res {
+                                warn!("update_point_transform apply failed: {:?}", err);
+                            }
+                        }
+                        components::DragActionType::PointTransform {
+                            mode,
+                            pivot,
+                            start_transform,
+                        } => {
+                            let Some(transform) = drag_transform(
+                                *mode,
+                                *pivot,
+                                drag_action.start_ray,
+                                ray,
+                                snap,
+                                angle_snap,
+                            ) else {
+                                continue;
+                            };
+                            // points are only moved by a scale, the rotation also turns e.g. directional lights
+                            let rotation = if *mode == components::TransformMode::Rotate {
+                                Quat::from_mat3a(&transform.matrix3) * start_transform.rotation
+                            } else {
+                                start_transform.rotation
+                            };
+                            let res = edit_commands.apply(update_point_transform::Command {
+                                entity,
+                                transform: Transform {
+                                    translation: transform
+                                        .transform_point3(start_transform.translation),
+                                    rotation,
+                                    ..*start_transform
+                                },
                             });
                             if let Err(err) = res {
                                 warn!("update_point_transform apply failed: {:?}", err);
@@ -545,24 +765,226 @@ pub fn edit_input_system(
                     edit_commands.end_drag(entity);
                     info!("stop drag for {:?}", entity);
                 }
+                edit_commands.end_group();
             }
             _ => (),
         }
     }
 }
 
+// rotation (around the view axis) or per axis scale of a drag from start_ray to ray around pivot. None if the drag
+// is degenerate, e.g. it starts on the pivot.
+fn drag_transform(
+    mode: components::TransformMode,
+    pivot: Vec3,
+    start_ray: Ray3d,
+    ray: Ray3d,
+    snap: f32,
+    angle_snap: f32,
+) -> Option<Affine3A> {
+    let axis = *start_ray.direction;
+    let linear = match mode {
+        components::TransformMode::Move | components::TransformMode::Vertices => return None,
+        components::TransformMode::Rotate => {
+            let v0 = (start_ray.origin - pivot).reject_from_normalized(axis);
+            let v1 = (ray.origin - pivot).reject_from_normalized(axis);
+            if v0.length() < PLANE_EPSILON || v1.length() < PLANE_EPSILON {
+                return None;
+            }
+            let angle = axis.dot(v0.cross(v1)).atan2(v0.dot(v1));
+            let step = angle_snap.to_radians();
+            let angle = if step > 0.0 {
+                (angle / step).round() * step
+            } else {
+                angle
+            };
+            Affine3A::from_axis_angle(axis, angle)
+        }
+        components::TransformMode::Scale => {
+            // the dragged point moves on the grid, the extent along the view axis is kept
+            let d0 = start_ray.origin.snap(snap) - pivot;
+            let d1 = ray.origin.snap(snap) - pivot;
+            let mut scale = Vec3::ONE;
+            for i in 0..3 {
+                if axis[i].abs() < 0.5 && d0[i].abs() > PLANE_EPSILON {
+                    scale[i] = d1[i] / d0[i];
+                }
+            }
+            if scale.abs().min_element() < PLANE_EPSILON {
+                return None;
+            }
+            Affine3A::from_scale(scale)
+        }
+    };
+    Some(Affine3A::from_translation(pivot) * linear * Affine3A::from_translation(-pivot))
+}
+
+fn brush_changed(brush: &csg::Brush, new_brush: &csg::Brush) -> bool {
+    brush.planes.len() != new_brush.planes.len()
+        || brush
+            .planes
+            .iter()
+            .zip(&new_brush.planes)
+            .any(|(current, new)| {
+                (new.w - current.w).abs() >= PLANE_EPSILON
+                    || new.normal.distance(current.normal) >= PLANE_EPSILON
+            })
+}
+
+// all handles under the ray are picked, i.e. also the ones hidden behind each other in the view. Vertices are
+// preferred over edges, for edges both end points are returned.
+fn picked_vertices(
+    topology: &csg::BrushTopology,
+    ray: Ray3d,
+    editor_windows_2d: &resources::EditorWindows2d,
+) -> Vec<usize> {
+    let visible = |i: &usize| editor_windows_2d.in_view_bounds(&topology.vertices[*i]);
+    let vertices = (0..topology.vertices.len())
+        .filter(visible)
+        .filter(|i| util::ray_point_distance(ray, topology.vertices[*i]) < HANDLE_PICK_DISTANCE)
+        .collect::<Vec<_>>();
+    if !vertices.is_empty() {
+        return vertices;
+    }
+    let mut vertices = Vec::new();
+    for (a, b) in &topology.edges {
+        if !visible(a)
+            || !visible(b)
+            || util::ray_segment_distance(ray, topology.vertices[*a], topology.vertices[*b])
+                >= HANDLE_PICK_DISTANCE
+        {
+            continue;
+        }
+        for i in [*a, *b] {
+            if !vertices.contains(&i) {
+                vertices.push(i);
+            }
+        }
+    }
+    vertices
+}
+
+fn mean_position(positions: impl Iterator<Item = Vec3>) -> Option<Vec3> {
+    let (sum, count) = positions.fold((Vec3::ZERO, 0), |(sum, count), p| (sum + p, count + 1));
+    (count > 0).then(|| sum / count as f32)
+}
+
+// ctrl-click places the pivot of rotate / scale drags. The pivot keeps its depth, since it is placed in one view
+// but used in all of them.
+#[allow(clippy::too_many_arguments)]
+pub fn transform_pivot_system(
+    mut event_reader: EventReader<util::WmEvent>,
+    mut transform_tool: ResMut<resources::TransformTool>,
+    mut gizmos: Gizmos<super::SelectionGizmos>,
+    keycodes: Res<ButtonInput<KeyCode>>,
+    grid_settings: Res<resources::GridSettings>,
+    editor_windows_2d: Res<resources::EditorWindows2d>,
+    camera_query: Query<(&GlobalTransform, &Camera)>,
+    selected_query: Query<
+        (Option<&components::CsgRepresentation>, &Transform),
+        With<components::Selected>,
+    >,
+) {
+    if !transform_tool.mode.uses_pivot() {
+        event_reader.clear();
+        return;
+    }
+    let center = || {
+        mean_position(selected_query.iter().map(|(csg_repr, transform)| {
+            csg_repr.map_or(transform.translation, |csg_repr| csg_repr.bounds.center)
+        }))
+    };
+    let snap = grid_settings.translation_snap(&keycodes);
+    for event in event_reader.read() {
+        let util::WmEvent::Clicked {
+            window: window_name,
+            button: util::WmMouseButton::Left,
+            pointer_state,
+        } = *event
+        else {
+            continue;
+        };
+        if !pointer_state.modifiers.ctrl {
+            continue;
+        }
+        let Some(window) = editor_windows_2d.windows.get(window_name) else {
+            continue;
+        };
+        let Ok((global_transform, camera)) = camera_query.get(window.camera) else {
+            warn!("2d window camera not found: {:?}", window.camera);
+            continue;
+        };
+        let Some(ray) =
+            camera.viewport_to_world(global_transform, pointer_state.get_pos_origin_down())
+        else {
+            continue;
+        };
+        let depth = transform_tool.pivot.or_else(center).unwrap_or_default();
+        let pivot = window.orientation.mix(ray.origin.snap(snap), depth);
+        info!("transform pivot: {:?}", pivot);
+        transform_tool.pivot = Some(pivot);
+    }
+
+    if let Some(pivot) = transform_tool.pivot.or_else(center) {
+        let color = if transform_tool.pivot.is_some() {
+            tailwind::ORANGE_500
+        } else {
+            tailwind::ORANGE_200
+        };
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            gizmos.line(pivot - axis * 0.25, pivot + axis * 0.25, color);
+        }
+    }
+}
+
+// vertex and edge handles of the selected brushes in vertex mode. The gizmos show up in all views.
+pub fn brush_handles_system(
+    mut gizmos: Gizmos<super::SelectionGizmos>,
+    transform_tool: Res<resources::TransformTool>,
+    brush_query: Query<&csg::Brush, With<components::Selected>>,
+) {
+    if transform_tool.mode != components::TransformMode::Vertices {
+        return;
+    }
+    for brush in &brush_query {
+        let topology = brush.topology();
+        for (a, b) in &topology.edges {
+            gizmos.line(
+                topology.vertices[*a],
+                topology.vertices[*b],
+                tailwind::SKY_300,
+            );
+        }
+        for vertex in &topology.vertices {
+            gizmos.sphere(*vertex, default(), 0.05, tailwind::SKY_500);
+        }
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn select_input_system(
     mut commands: Commands,
     mut event_reader: EventReader<util::WmEvent>,
     mut selection: ResMut<resources::SelectionPickSet>,
     editor_windows_2d: Res<resources::EditorWindows2d>,
-    camera_query: Query<(&GlobalTransform, &Camera)>,
-    brush_query: Query<(Entity, &csg::Brush, &components::CsgRepresentation)>,
-    point_query: Query<(Entity, &Transform), With<components::EditablePoint>>,
+    camera_query: Query<(&GlobalTransform, &Camera, &Frustum)>,
+    editor_objects: Res<resources::EditorObjects>,
+    spatial_index: Res<SpatialIndex>,
+    brush_query: Query<(
+        &components::EditorObjectId,
+        &csg::Brush,
+        &components::CsgRepresentation,
+    )>,
+    point_query: Query<(&components::EditorObjectId, &Transform), With<components::EditablePoint>>,
     selected_query: Query<Entity, With<components::Selected>>,
+    polygon_tool: Res<resources::PolygonToolState>,
+    transform_tool: Res<resources::TransformTool>,
 ) {
     for event in event_reader.read() {
+        // clicks are outline points while the polygon tool is active
+        if polygon_tool.active {
+            continue;
+        }
         if let util::WmEvent::Clicked {
             window: focused_name,
             button: util::WmMouseButton::Left,
@@ -572,6 +994,10 @@ pub fn select_input_system(
             if pointer_state.modifiers.alt {
                 continue;
             }
+            // ctrl-click places the transform pivot
+            if pointer_state.modifiers.ctrl && transform_tool.mode.uses_pivot() {
+                continue;
+            }
             info!("event: {:?}", event);
 
             info!(
@@ -583,7 +1009,7 @@ pub fn select_input_system(
                 warn!("no 2d window focused");
                 continue;
             };
-            let Ok((global_transform, camera)) = camera_query.get(window.camera) else {
+            let Ok((global_transform, camera, frustum)) = camera_query.get(window.camera) else {
                 warn!("2d window camera not found: {:?}", window.camera);
                 continue;
             };
@@ -595,28 +1021,32 @@ pub fn select_input_system(
                 continue;
             };
 
-            info!("brute force selection");
-            // TODO: brute force raycast against all brushes. can be accelerated by spatial index if necessary
-            let brush_selection = brush_query.iter().filter_map(|(entity, _brush, csg)| {
-                for tri in csg.csg.get_triangles() {
-                    info!("select check {entity:?}");
-                    // check against view bounds to only include visible brushes
-                    if !tri.0.iter().any(|v| editor_windows_2d.in_view_bounds(v)) {
-                        continue;
-                    }
-                    if util::raycast_moller_trumbore(&ray, &tri.0, false).is_some() {
-                        return Some(entity);
+            // only the brushes whose bounds are hit by the ray and reach into the view need the exact test
+            let in_view = spatial_index.query_frustum(frustum).collect::<HashSet<_>>();
+            let brush_selection = spatial_index
+                .raycast(ray, f32::INFINITY)
+                .filter(|(entity, _)| in_view.contains(entity))
+                .filter_map(|(entity, _)| {
+                    let (id, _brush, csg) = brush_query.get(entity).ok()?;
+                    for tri in csg.csg.get_triangles() {
+                        info!("select check {id:?}");
+                        // bounds are spheres, the triangle itself has to be visible
+                        if !tri.0.iter().any(|v| editor_windows_2d.in_view_bounds(v)) {
+                            continue;
+                        }
+                        if util::raycast_moller_trumbore(&ray, &tri.0, false).is_some() {
+                            return Some(*id);
+                        }
                     }
-                }
-                None
-            });
+                    None
+                });
 
-            let point_selection = point_query.iter().filter_map(|(entity, transform)| {
+            let point_selection = point_query.iter().filter_map(|(id, transform)| {
                 let pos = transform.translation;
                 if editor_windows_2d.in_view_bounds(&pos)
                     && util::ray_point_distance(ray, pos) < 0.2
                 {
-                    Some(entity)
+                    Some(*id)
                 } else {
                     None
                 }
@@ -626,21 +1056,35 @@ pub fn select_input_system(
 
             info!("selection set: {:?}", selection_set);
 
+            // additive clicks do not cycle, so a second shift-click on the same spot removes the object again
+            let additive = pointer_state.modifiers.shift;
             if selection_set != selection.last_set {
                 selection.last_set = selection_set;
                 selection.last_set_index = 0;
-            } else {
+            } else if !additive {
                 selection.last_set_index += 1;
             }
 
+            // the pick set is kept as object ids, so cycling through it keeps working if entities are re-spawned in between
             let mut primary_selection = None;
             if !selection.last_set.is_empty() {
-                primary_selection =
-                    Some(selection.last_set[selection.last_set_index % selection.last_set.len()]);
+                let id = selection.last_set[selection.last_set_index % selection.last_set.len()];
+                primary_selection = editor_objects.get(id);
             }
 
             let old_selection = selected_query.iter().collect::<HashSet<_>>();
 
+            if additive {
+                if let Some(entity) = primary_selection {
+                    if old_selection.contains(&entity) {
+                        commands.entity(entity).remove::<components::Selected>();
+                    } else {
+                        commands.entity(entity).insert(components::Selected);
+                    }
+                }
+                continue;
+            }
+
             for entity in &old_selection {
                 if Some(*entity) != primary_selection {
                     commands.entity(*entity).remove::<components::Selected>();
@@ -652,3 +1096,125 @@ pub fn select_input_system(
         }
     }
 }
+
+// ctrl-drag in the ortho views: select everything that is completely inside the band
+#[allow(clippy::too_many_arguments)]
+pub fn rubber_band_select_system(
+    mut commands: Commands,
+    mut event_reader: EventReader<util::WmEvent>,
+    mut rubber_band: ResMut<resources::RubberBand>,
+    create_state: Res<resources::CreateBrushState>,
+    mut gizmos: Gizmos<super::SelectionGizmos>,
+    editor_windows_2d: Res<resources::EditorWindows2d>,
+    spatial_index: Res<SpatialIndex>,
+    camera_query: Query<(&GlobalTransform, &Camera, &Projection)>,
+    brush_query: Query<&components::CsgRepresentation>,
+    point_query: Query<(Entity, &Transform), With<components::EditablePoint>>,
+    selected_query: Query<Entity, With<components::Selected>>,
+) {
+    for event in event_reader.read() {
+        let (window_name, pointer_state) = match *event {
+            util::WmEvent::DragStart {
+                window,
+                button: util::WmMouseButton::Left,
+                pointer_state,
+            } if pointer_state.modifiers.ctrl
+                && !pointer_state.modifiers.alt
+                && !create_state.create_mode =>
+            {
+                (window, pointer_state)
+            }
+            util::WmEvent::DragUpdate {
+                window,
+                button: util::WmMouseButton::Left,
+                pointer_state,
+            } if rubber_band.window == Some(window) => (window, pointer_state),
+            util::WmEvent::DragEnd { window, .. } if rubber_band.window == Some(window) => {
+                let (min, max) = rubber_band.bounds();
+                // only what is visible in the views can be selected
+                let min = min.max(editor_windows_2d.view_min);
+                let max = max.min(editor_windows_2d.view_max);
+                let inside = |v: &Vec3| v.cmpge(min).all() && v.cmple(max).all();
+
+                let brush_selection = spatial_index.query_aabb(min, max).filter(|entity| {
+                    brush_query.get(*entity).is_ok_and(|csg_repr| {
+                        csg_repr
+                            .csg
+                            .get_triangles()
+                            .iter()
+                            .all(|tri| tri.0.iter().all(inside))
+                    })
+                });
+                let point_selection = point_query
+                    .iter()
+                    .filter(|(_, transform)| inside(&transform.translation))
+                    .map(|(entity, _)| entity);
+                let new_selection = brush_selection
+                    .chain(point_selection)
+                    .collect::<HashSet<_>>();
+                info!("rubber band selection: {:?}", new_selection);
+
+                if !rubber_band.additive {
+                    for entity in &selected_query {
+                        if !new_selection.contains(&entity) {
+                            commands.entity(entity).remove::<components::Selected>();
+                        }
+                    }
+                }
+                for entity in new_selection {
+                    commands.entity(entity).insert(components::Selected);
+                }
+                rubber_band.window = None;
+                continue;
+            }
+            _ => continue,
+        };
+
+        let Some(window) = editor_windows_2d.windows.get(window_name) else {
+            continue;
+        };
+        let Ok((global_transform, camera, projection)) = camera_query.get(window.camera) else {
+            warn!("2d window camera not found: {:?}", window.camera);
+            continue;
+        };
+        let Some(ray) =
+            camera.viewport_to_world(global_transform, pointer_state.get_pos_origin_down())
+        else {
+            warn!("viewport_to_world failed in {}", window_name);
+            continue;
+        };
+
+        if rubber_band.window.is_none() {
+            let depth = match projection {
+                Projection::Orthographic(projection) => projection.far - projection.near,
+                Projection::Perspective(projection) => projection.far - projection.near,
+            };
+            *rubber_band = resources::RubberBand {
+                window: Some(window_name),
+                start: ray.origin,
+                end: ray.origin,
+                depth: *ray.direction * depth,
+                additive: pointer_state.modifiers.shift,
+            };
+        } else {
+            rubber_band.end = ray.origin;
+        }
+    }
+
+    if let Some(window) = rubber_band
+        .window
+        .and_then(|name| editor_windows_2d.windows.get(name))
+    {
+        // draw slightly behind the near plane, so that it does not get clipped
+        let offset = rubber_band.depth * 0.001;
+        let start = rubber_band.start + offset;
+        let end = rubber_band.end + offset;
+        // the other two corners take one of the view plane axes from each end
+        let mut corner0 = start;
+        *window.orientation.get_right_axis_mut(&mut corner0) =
+            window.orientation.get_right_axis(end);
+        let mut corner1 = start;
+        *window.orientation.get_up_axis_mut(&mut corner1) = window.orientation.get_up_axis(end);
+        gizmos.linestrip([start, corner0, end, corner1, start], tailwind::ORANGE_500);
+    }
+}
@@ -1,6 +1,13 @@
-use std::collections::BTreeMap;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    path::{Path, PathBuf},
+};
 
-use super::util::Orientation2d;
+use super::{
+    components::{EditorObjectId, TransformMode},
+    scene::QuarantinedObject,
+    util::Orientation2d,
+};
 use bevy::{
     prelude::*,
     utils::{hashbrown::hash_map, HashMap, HashSet},
@@ -10,10 +17,36 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Default, Resource)]
 pub struct SelectionPickSet {
-    pub last_set: Vec<Entity>,
+    pub last_set: Vec<EditorObjectId>,
     pub last_set_index: usize,
 }
 
+// rubber band selection in one of the ortho views. The band spans from the near to the far plane of the view.
+#[derive(Default, Resource)]
+pub struct RubberBand {
+    // view the band is dragged in, None if there is no band
+    pub window: Option<&'static str>,
+    // corners on the near plane
+    pub start: Vec3,
+    pub end: Vec3,
+    // view direction scaled to the depth of the view
+    pub depth: Vec3,
+    // add to the selection instead of replacing it
+    pub additive: bool,
+}
+
+impl RubberBand {
+    // axis aligned box covered by the band
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        let far_start = self.start + self.depth;
+        let far_end = self.end + self.depth;
+        (
+            self.start.min(self.end).min(far_start).min(far_end),
+            self.start.max(self.end).max(far_start).max(far_end),
+        )
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Default)]
 pub struct EditorWindowSettings {
     pub pos_x: i32,
@@ -258,12 +291,20 @@ pub enum WmSidpanelContent {
     Material,
     Miscsettings,
     Entities,
+    History,
+    File,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct WmSettings {
     pub ortho_separator: f32,
     pub sidepanel_separator: f32,
+    // most recent first
+    #[serde(default)]
+    pub recent_files: Vec<PathBuf>,
+    // copy of the GridSettings resource, only used for persisting them
+    #[serde(default)]
+    pub grid: GridSettings,
 }
 
 impl Default for WmSettings {
@@ -271,10 +312,79 @@ impl Default for WmSettings {
         Self {
             ortho_separator: 768.0,
             sidepanel_separator: 512.0,
+            recent_files: Vec::new(),
+            grid: default(),
+        }
+    }
+}
+
+// grid and snapping of the ortho views. Alt switches to the coarse snap values.
+#[derive(Resource, Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(default)]
+pub struct GridSettings {
+    // grid size (and translation snap) is 2^grid_exponent
+    pub grid_exponent: i32,
+    // degrees
+    pub rotation_snap: f32,
+    pub coarse_rotation_snap: f32,
+    // snap to vertices, edge midpoints and faces of other brushes within this distance
+    pub snap_to_geometry: bool,
+    pub geometry_snap_distance: f32,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            grid_exponent: -3,
+            rotation_snap: 15.0,
+            coarse_rotation_snap: 45.0,
+            snap_to_geometry: false,
+            geometry_snap_distance: 0.25,
+        }
+    }
+}
+
+impl GridSettings {
+    pub const MIN_GRID_EXPONENT: i32 = -6;
+    pub const MAX_GRID_EXPONENT: i32 = 6;
+    // the coarse translation snap is this many grid steps
+    const COARSE_GRID_STEPS: f32 = 4.0;
+
+    pub fn grid_size(&self) -> f32 {
+        2f32.powi(self.grid_exponent)
+    }
+    pub fn finer(&mut self) {
+        self.grid_exponent = (self.grid_exponent - 1).max(Self::MIN_GRID_EXPONENT);
+    }
+    pub fn coarser(&mut self) {
+        self.grid_exponent = (self.grid_exponent + 1).min(Self::MAX_GRID_EXPONENT);
+    }
+    pub fn translation_snap(&self, keycodes: &ButtonInput<KeyCode>) -> f32 {
+        if keycodes.pressed(KeyCode::AltLeft) {
+            self.grid_size() * Self::COARSE_GRID_STEPS
+        } else {
+            self.grid_size()
+        }
+    }
+    pub fn rotation_snap(&self, keycodes: &ButtonInput<KeyCode>) -> f32 {
+        if keycodes.pressed(KeyCode::AltLeft) {
+            self.coarse_rotation_snap
+        } else {
+            self.rotation_snap
         }
     }
 }
 
+impl WmSettings {
+    const MAX_RECENT_FILES: usize = 10;
+
+    pub fn add_recent_file(&mut self, path: &Path) {
+        self.recent_files.retain(|recent| recent != path);
+        self.recent_files.insert(0, path.to_path_buf());
+        self.recent_files.truncate(Self::MAX_RECENT_FILES);
+    }
+}
+
 #[derive(Resource, Default)]
 pub struct WmState {
     pub slot_upper2d: WmSlot,
@@ -284,6 +394,10 @@ pub struct WmState {
     pub settings: WmSettings,
 }
 
+// egui has the keyboard while one of its text fields has the focus, editor shortcuts must not see the keys then
+#[derive(Resource, Default)]
+pub struct EguiKeyboard(pub bool);
+
 #[derive(Resource, Default)]
 pub struct ClipState {
     pub plane_points: [Vec3; 3],
@@ -292,3 +406,166 @@ pub struct ClipState {
     pub clip_mode: bool,
     pub last_clip_mode: bool,
 }
+
+// draw-to-create brushes in the ortho views
+#[derive(Default, Resource)]
+pub struct CreateBrushState {
+    pub create_mode: bool,
+    pub drag: Option<CreateBrushDrag>,
+    // bounds of the last created brush, new brushes get their depth from it
+    pub last_bounds: Option<(Vec3, Vec3)>,
+}
+
+pub struct CreateBrushDrag {
+    pub window: &'static str,
+    // footprint corners, snapped to the grid
+    pub start: Vec3,
+    pub end: Vec3,
+    // range along the view direction
+    pub depth: (f32, f32),
+}
+
+// outline of the polygon tool. The outline is extruded into convex brushes when it is finished.
+#[derive(Default, Resource)]
+pub struct PolygonToolState {
+    pub active: bool,
+    // view the outline is clicked in
+    pub window: Option<&'static str>,
+    pub points: Vec<Vec3>,
+}
+
+// rotate / scale drags. Without a placed pivot the selection is transformed around its center.
+#[derive(Resource, Default)]
+pub struct TransformTool {
+    pub mode: TransformMode,
+    pub pivot: Option<Vec3>,
+}
+
+// maps editor object ids to the entity currently representing the object
+#[derive(Resource, Default)]
+pub struct EditorObjects {
+    next_id: u64,
+    entities: HashMap<EditorObjectId, Entity>,
+}
+
+impl EditorObjects {
+    pub fn alloc_id(&mut self) -> EditorObjectId {
+        self.next_id += 1;
+        EditorObjectId(self.next_id)
+    }
+
+    // ids may also come from outside (e.g. loaded from a scene file): make sure they are never handed out again
+    pub fn insert(&mut self, id: EditorObjectId, entity: Entity) {
+        self.next_id = self.next_id.max(id.0);
+        self.entities.insert(id, entity);
+    }
+
+    pub fn remove(&mut self, id: EditorObjectId) -> Option<Entity> {
+        self.entities.remove(&id)
+    }
+
+    pub fn get(&self, id: EditorObjectId) -> Option<Entity> {
+        self.entities.get(&id).copied()
+    }
+
+    // forget all objects, but keep allocating fresh ids so that stale references (e.g. from the undo stack) cannot
+    // resolve to new objects
+    pub fn clear(&mut self) {
+        self.entities.clear();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SceneFileRequest {
+    Open(PathBuf),
+    // wsx level from the original game
+    Import(PathBuf),
+    // save to the current path (scene.ron if there is none yet)
+    Save,
+    SaveAs(PathBuf),
+}
+
+impl SceneFileRequest {
+    pub fn discards_scene(&self) -> bool {
+        matches!(
+            self,
+            SceneFileRequest::Open(_) | SceneFileRequest::Import(_)
+        )
+    }
+}
+
+// the scene file currently edited. Requests are queued here by key bindings, the file panel and the command line
+// and processed by load_save_editor_objects.
+#[derive(Resource)]
+pub struct SceneFile {
+    pub path: Option<PathBuf>,
+    // undo stack revision at the last load / save, None if the scene was never saved
+    saved_revision: Option<u64>,
+    // requests with flag if discarding unsaved changes was already confirmed
+    requests: VecDeque<(SceneFileRequest, bool)>,
+    // request that would discard unsaved changes, waiting for the user to confirm
+    confirm_discard: Option<SceneFileRequest>,
+    // path text field of the file panel
+    pub path_edit: String,
+    // objects of the last load that could not be loaded
+    pub quarantine: Vec<QuarantinedObject>,
+}
+
+impl Default for SceneFile {
+    fn default() -> Self {
+        Self {
+            path: None,
+            // an empty scene is not worth a warning
+            saved_revision: Some(0),
+            requests: VecDeque::new(),
+            confirm_discard: None,
+            path_edit: "scene.ron".into(),
+            quarantine: Vec::new(),
+        }
+    }
+}
+
+impl SceneFile {
+    pub fn request(&mut self, request: SceneFileRequest) {
+        self.requests.push_back((request, false));
+    }
+
+    // next request that can be processed. Requests that would discard unsaved changes are parked until the user
+    // confirmed them.
+    pub fn next_request(&mut self, revision: u64) -> Option<SceneFileRequest> {
+        while let Some((request, discard_confirmed)) = self.requests.pop_front() {
+            if request.discards_scene() && !discard_confirmed && self.has_unsaved_changes(revision)
+            {
+                self.confirm_discard = Some(request);
+                continue;
+            }
+            return Some(request);
+        }
+        None
+    }
+
+    pub fn has_unsaved_changes(&self, revision: u64) -> bool {
+        self.saved_revision != Some(revision)
+    }
+
+    pub fn mark_saved(&mut self, revision: u64) {
+        self.saved_revision = Some(revision);
+    }
+
+    pub fn mark_unsaved(&mut self) {
+        self.saved_revision = None;
+    }
+
+    pub fn confirm_discard(&self) -> Option<&SceneFileRequest> {
+        self.confirm_discard.as_ref()
+    }
+
+    // answer of the confirmation dialog
+    pub fn resolve_discard(&mut self, discard: bool) {
+        if let Some(request) = self.confirm_discard.take() {
+            if discard {
+                self.requests.push_back((request, true));
+            }
+        }
+    }
+}
@@ -0,0 +1,470 @@
+// on-disk scene format. A scene file is a versioned envelope around the object list:
+//
+// (
+//     version: 1,
+//     objects: [
+//         (type: "Brush", id: Some(1), brush: (...), material_properties: (...)),
+//         ...
+//     ],
+// )
+//
+// Loading goes through ron::Value, so every object is migrated and deserialized on its own: a broken object ends up in
+// the quarantine instead of taking the whole scene down with it.
+
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context};
+use bevy::prelude::*;
+use ron::Value;
+use serde::{Deserialize, Serialize};
+use sstree::{SpatialBounds, SpatialIndex};
+
+use crate::{
+    components::{self, EditorObjectId},
+    resources,
+};
+
+pub const SCENE_VERSION: u32 = 1;
+
+type Migration = fn(Value) -> anyhow::Result<Value>;
+
+// MIGRATIONS[n] converts a single object from version n to n + 1
+const MIGRATIONS: [Migration; SCENE_VERSION as usize] = [migrate_v0_to_v1];
+
+// NOTE: the id is optional so that files written before ids existed can still be loaded. Objects without id (or with
+// an id that is already in use) get a fresh one on load.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub(crate) enum ExternalEditorObject {
+    Brush {
+        #[serde(default)]
+        id: Option<EditorObjectId>,
+        brush: csg::Brush,
+        material_properties: components::BrushMaterialProperties,
+    },
+    PointLight {
+        #[serde(default)]
+        id: Option<EditorObjectId>,
+        translation: Vec3,
+        light_properties: components::PointLightProperties,
+    },
+    DirectionalLight {
+        #[serde(default)]
+        id: Option<EditorObjectId>,
+        translation: Vec3,
+        rotation: Quat,
+        light_properties: components::DirectionalLightProperties,
+    },
+}
+
+impl ExternalEditorObject {
+    pub(crate) fn id(&self) -> Option<EditorObjectId> {
+        match self {
+            ExternalEditorObject::Brush { id, .. }
+            | ExternalEditorObject::PointLight { id, .. }
+            | ExternalEditorObject::DirectionalLight { id, .. } => *id,
+        }
+    }
+
+    // spawn as editor object, keeping the stored id if it is still free. Fails (without spawning anything) on
+    // degenerated brushes. Brushes also return their spatial bounds.
+    pub(crate) fn spawn(
+        &self,
+        commands: &mut Commands,
+        editor_objects: &mut resources::EditorObjects,
+    ) -> anyhow::Result<(Entity, Option<SpatialBounds>)> {
+        let mut bounds = None;
+        let mut entity_commands = match self {
+            ExternalEditorObject::Brush {
+                brush,
+                material_properties,
+                ..
+            } => {
+                let bundle = components::EditorObjectBrushBundle::from_brush(brush.clone())?
+                    .with_material_properties(material_properties.clone());
+                bounds = Some(bundle.csg_representation.bounds);
+                commands.spawn(bundle)
+            }
+            ExternalEditorObject::PointLight {
+                translation,
+                light_properties,
+                ..
+            } => commands.spawn(components::EditorObjectPointlightBundle {
+                spatial: SpatialBundle::from_transform(Transform::from_translation(*translation)),
+                light_properties: light_properties.clone(),
+                ..default()
+            }),
+            ExternalEditorObject::DirectionalLight {
+                translation,
+                rotation,
+                light_properties,
+                ..
+            } => commands.spawn(components::EditorObjectDirectionalLightBundle {
+                spatial: SpatialBundle::from_transform(
+                    Transform::from_translation(*translation).with_rotation(*rotation),
+                ),
+                light_properties: light_properties.clone(),
+                ..default()
+            }),
+        };
+        let id = match self.id() {
+            Some(id) if editor_objects.get(id).is_none() => id,
+            _ => editor_objects.alloc_id(),
+        };
+        let entity = entity_commands.insert(id).id();
+        editor_objects.insert(id, entity);
+        Ok((entity, bounds))
+    }
+}
+
+#[derive(Serialize)]
+struct SceneEnvelope<'a, T> {
+    version: u32,
+    objects: &'a [T],
+}
+
+// quarantined objects are written back as found, so saving does not lose them
+#[derive(Serialize)]
+#[serde(untagged)]
+enum SceneObject<'a> {
+    Object(&'a ExternalEditorObject),
+    Quarantined(Value),
+}
+
+// object that could not be loaded, kept around so the user can see what is missing
+#[derive(Debug, Clone)]
+pub struct QuarantinedObject {
+    // position in the object list of the file
+    pub index: usize,
+    pub reason: String,
+    // the object as found in the file
+    pub source: String,
+}
+
+impl QuarantinedObject {
+    pub(crate) fn new<T: Serialize>(index: usize, reason: &anyhow::Error, source: &T) -> Self {
+        let quarantined = QuarantinedObject {
+            index,
+            reason: format!("{:#}", reason),
+            source: ron::ser::to_string(source).unwrap_or_else(|err| err.to_string()),
+        };
+        warn!(
+            "skipping object {}: {}",
+            quarantined.index, quarantined.reason
+        );
+        quarantined
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct LoadedScene {
+    // with their position in the source, for reporting objects that fail to spawn
+    pub objects: Vec<(usize, ExternalEditorObject)>,
+    pub quarantine: Vec<QuarantinedObject>,
+}
+
+impl LoadedScene {
+    // spawn all objects, the ones that fail go into quarantine. The spatial index is rebuilt from the spawned brushes,
+    // building it in one go gives a much better tree than inserting thousands of brushes one by one.
+    pub(crate) fn spawn(
+        self,
+        commands: &mut Commands,
+        editor_objects: &mut resources::EditorObjects,
+        spatial_index: &mut SpatialIndex,
+    ) -> Vec<QuarantinedObject> {
+        let mut quarantine = self.quarantine;
+        let mut spatial_entries = Vec::new();
+        for (index, object) in &self.objects {
+            match object.spawn(commands, editor_objects) {
+                Ok((entity, Some(bounds))) => spatial_entries.push((entity, bounds)),
+                Ok(_) => (),
+                Err(err) => quarantine.push(QuarantinedObject::new(*index, &err, object)),
+            }
+        }
+        spatial_index.rebuild(spatial_entries);
+        quarantine
+    }
+}
+
+pub(crate) fn write_scene_file(
+    path: &Path,
+    objects: &[ExternalEditorObject],
+    quarantine: &[QuarantinedObject],
+) -> anyhow::Result<()> {
+    // before the file is truncated
+    let objects = scene_objects(objects, quarantine)?;
+    let file = std::fs::File::create(path)?;
+    write_scene(file, &objects)
+}
+
+fn scene_objects<'a>(
+    objects: &'a [ExternalEditorObject],
+    quarantine: &[QuarantinedObject],
+) -> anyhow::Result<Vec<SceneObject<'a>>> {
+    let mut scene_objects = objects.iter().map(SceneObject::Object).collect::<Vec<_>>();
+    for quarantined in quarantine {
+        let value = ron::from_str::<Value>(&quarantined.source)
+            .with_context(|| format!("quarantined object #{}", quarantined.index))?;
+        scene_objects.push(SceneObject::Quarantined(value));
+    }
+    Ok(scene_objects)
+}
+
+fn write_scene(writer: impl std::io::Write, objects: &[SceneObject]) -> anyhow::Result<()> {
+    ron::ser::to_writer_pretty(
+        writer,
+        &SceneEnvelope {
+            version: SCENE_VERSION,
+            objects,
+        },
+        ron::ser::PrettyConfig::default(), // .indentor(" ".to_string())
+                                           // .compact_arrays(true),
+    )?;
+    Ok(())
+}
+
+// only fails if the file as a whole is unusable (i.e. not readable, not ron, unknown version)
+pub(crate) fn read_scene_file(path: &Path) -> anyhow::Result<LoadedScene> {
+    let text = std::fs::read_to_string(path)?;
+    parse_scene(&text)
+}
+
+pub(crate) fn parse_scene(text: &str) -> anyhow::Result<LoadedScene> {
+    let (version, objects) = match ron::from_str::<Value>(text)? {
+        // version 0: bare object list
+        Value::Seq(objects) => (0, objects),
+        Value::Map(mut envelope) => {
+            let version = match envelope.remove(&Value::String("version".into())) {
+                Some(Value::Number(version)) => version
+                    .as_i64()
+                    .and_then(|version| u32::try_from(version).ok())
+                    .ok_or_else(|| anyhow!("bad scene version {:?}", version))?,
+                other => bail!("missing scene version: {:?}", other),
+            };
+            match envelope.remove(&Value::String("objects".into())) {
+                Some(Value::Seq(objects)) => (version, objects),
+                other => bail!("missing object list: {:?}", other),
+            }
+        }
+        _ => bail!("not a scene file"),
+    };
+    if version > SCENE_VERSION {
+        bail!(
+            "scene version {} is newer than supported version {}",
+            version,
+            SCENE_VERSION
+        );
+    }
+    if version < SCENE_VERSION {
+        info!(
+            "migrating scene from version {} to {}",
+            version, SCENE_VERSION
+        );
+    }
+
+    let mut scene = LoadedScene::default();
+    for (index, value) in objects.into_iter().enumerate() {
+        match parse_object(value.clone(), version) {
+            Ok(object) => scene.objects.push((index, object)),
+            Err(err) => scene
+                .quarantine
+                .push(QuarantinedObject::new(index, &err, &value)),
+        }
+    }
+    Ok(scene)
+}
+
+// migrate a single object from `version` to the current one and deserialize it
+pub(crate) fn parse_object(mut value: Value, version: u32) -> anyhow::Result<ExternalEditorObject> {
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        value = migration(value).with_context(|| format!("migration from version {}", from))?;
+    }
+    Ok(value.into_rust()?)
+}
+
+// version 0 objects were written as externally tagged enum variants (`Brush(...)`). ron::Value does not keep the
+// variant name, so the type tag is reconstructed from the fields.
+fn migrate_v0_to_v1(value: Value) -> anyhow::Result<Value> {
+    let Value::Map(mut map) = value else {
+        bail!("expected object, found {:?}", value);
+    };
+    let fields = map
+        .keys()
+        .filter_map(|key| match key {
+            Value::String(key) => Some(key.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let object_type = if fields.contains(&"brush") {
+        "Brush"
+    } else if fields.contains(&"rotation") {
+        "DirectionalLight"
+    } else if fields.contains(&"translation") {
+        "PointLight"
+    } else {
+        bail!("unknown object type with fields {:?}", fields);
+    };
+    map.insert(
+        Value::String("type".into()),
+        Value::String(object_type.into()),
+    );
+    Ok(Value::Map(map))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_v0_scene() {
+        let text = r#"[
+            Brush(
+                brush: (planes: [], appearances: []),
+                material_properties: (materials: []),
+            ),
+            PointLight(
+                translation: (1.0, 2.0, 3.0),
+                light_properties: (shadows_enabled: true, range: 5.0),
+            ),
+            PointLight(translation: "broken"),
+        ]"#;
+        let scene = parse_scene(text).unwrap();
+        assert_eq!(scene.objects.len(), 2);
+        assert!(matches!(
+            scene.objects[0].1,
+            ExternalEditorObject::Brush { id: None, .. }
+        ));
+        let ExternalEditorObject::PointLight {
+            translation,
+            light_properties,
+            ..
+        } = &scene.objects[1].1
+        else {
+            panic!("expected point light");
+        };
+        assert_eq!(*translation, Vec3::new(1.0, 2.0, 3.0));
+        assert!(light_properties.shadows_enabled);
+        assert_eq!(scene.quarantine.len(), 1);
+        assert_eq!(scene.quarantine[0].index, 2);
+    }
+
+    #[test]
+    fn test_quarantine_index() {
+        use bevy::ecs::world::CommandQueue;
+
+        // the degenerated brush only fails when spawned, it is reported with its position in the file
+        let text = r#"[
+            PointLight(translation: "broken"),
+            Brush(
+                brush: (planes: [], appearances: []),
+                material_properties: (materials: []),
+            ),
+            PointLight(
+                translation: (1.0, 2.0, 3.0),
+                light_properties: (shadows_enabled: true, range: 5.0),
+            ),
+        ]"#;
+        let scene = parse_scene(text).unwrap();
+        assert_eq!(
+            scene
+                .objects
+                .iter()
+                .map(|(index, _)| *index)
+                .collect::<Vec<_>>(),
+            [1, 2]
+        );
+
+        let world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        let mut editor_objects = resources::EditorObjects::default();
+        let mut spatial_index = SpatialIndex::default();
+        let quarantine = scene.spawn(&mut commands, &mut editor_objects, &mut spatial_index);
+        assert_eq!(
+            quarantine.iter().map(|q| q.index).collect::<Vec<_>>(),
+            [0, 1]
+        );
+        assert!(spatial_index.is_empty());
+    }
+
+    #[test]
+    fn test_quarantine_saved() {
+        let text = r#"(
+            version: 1,
+            objects: [
+                (type: "PointLight", translation: (1.0, 2.0, 3.0), light_properties: (shadows_enabled: true, range: 5.0)),
+                (type: "PointLight", translation: "broken"),
+            ],
+        )"#;
+        let scene = parse_scene(text).unwrap();
+        assert_eq!(scene.quarantine.len(), 1);
+        let objects = scene
+            .objects
+            .into_iter()
+            .map(|(_, object)| object)
+            .collect::<Vec<_>>();
+
+        let mut saved = Vec::new();
+        write_scene(
+            &mut saved,
+            &scene_objects(&objects, &scene.quarantine).unwrap(),
+        )
+        .unwrap();
+        let reloaded = parse_scene(std::str::from_utf8(&saved).unwrap()).unwrap();
+        assert_eq!(reloaded.objects.len(), 1);
+        assert_eq!(reloaded.quarantine.len(), 1);
+        assert_eq!(reloaded.quarantine[0].index, 1);
+        assert_eq!(reloaded.quarantine[0].source, scene.quarantine[0].source);
+    }
+
+    #[test]
+    fn test_scene_roundtrip() {
+        let objects = vec![
+            ExternalEditorObject::DirectionalLight {
+                id: Some(EditorObjectId(7)),
+                translation: Vec3::new(0.5, 1.0, -2.0),
+                rotation: Quat::from_rotation_x(0.3),
+                light_properties: default(),
+            },
+            ExternalEditorObject::Brush {
+                id: Some(EditorObjectId(3)),
+                brush: csg::Brush {
+                    planes: vec![csg::Plane {
+                        normal: Vec3::X,
+                        w: 2.0,
+                    }],
+                    appearances: vec![1],
+                },
+                material_properties: components::BrushMaterialProperties {
+                    materials: vec!["material/floors/bathroomtile1".into()],
+                },
+            },
+        ];
+        let text = ron::ser::to_string(&SceneEnvelope {
+            version: SCENE_VERSION,
+            objects: &objects,
+        })
+        .unwrap();
+        let scene = parse_scene(&text).unwrap();
+        assert!(scene.quarantine.is_empty());
+        assert_eq!(
+            scene
+                .objects
+                .iter()
+                .map(|(_, o)| o.id())
+                .collect::<Vec<_>>(),
+            [Some(EditorObjectId(7)), Some(EditorObjectId(3))]
+        );
+        let ExternalEditorObject::DirectionalLight { rotation, .. } = &scene.objects[0].1 else {
+            panic!("expected directional light");
+        };
+        assert_eq!(*rotation, Quat::from_rotation_x(0.3));
+
+        let newer = text.replacen(
+            &format!("version:{}", SCENE_VERSION),
+            &format!("version:{}", SCENE_VERSION + 1),
+            1,
+        );
+        assert!(parse_scene(&newer).is_err());
+    }
+}
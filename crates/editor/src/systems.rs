@@ -1,11 +1,16 @@
 use super::{
     components::{self, CsgOutput, CsgRepresentation},
-    edit_commands::{add_brush, add_pointlight, duplicate_brush, remove_entity, EditCommands},
+    edit_commands::{
+        add_brush, add_directional_light, add_pointlight, duplicate_brush, remove_entity,
+        set_brush_material, EditCommands,
+    },
     resources,
 };
 
 use crate::{
-    components::{BrushMaterialProperties, EditorObjectBrushBundle},
+    components::{BrushMaterialProperties, EditorObjectBrushBundle, EditorObjectId},
+    scene::{read_scene_file, write_scene_file, ExternalEditorObject},
+    undo::UndoStack,
     util::spawn_csg_split,
     wsx,
 };
@@ -14,15 +19,17 @@ use bevy::{
     pbr::{wireframe::Wireframe, VolumetricLight},
     prelude::*,
     render::view::RenderLayers,
-    utils::{HashSet, Instant},
+    utils::{HashMap, HashSet, Instant},
     window::PrimaryWindow,
 };
 use bevy_egui::EguiContexts;
 
-use serde::{Deserialize, Serialize};
 use shared::render_layers;
 use sstree::{SpatialBounds, SpatialIndex};
-use std::{collections::BTreeSet, path::PathBuf};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
 
 pub fn setup(
     mut materials_res: ResMut<resources::Materials>,
@@ -88,7 +95,13 @@ pub fn editor_input_system(
     mut primary_query: Query<&mut Window, With<PrimaryWindow>>,
     keycodes: Res<ButtonInput<KeyCode>>,
     selection_query: Query<Entity, With<components::Selected>>,
+    object_query: Query<(Entity, Has<components::Selected>), With<EditorObjectId>>,
+    material_browser: Res<resources::MaterialBrowser>,
     mut clip_state: ResMut<resources::ClipState>,
+    mut create_state: ResMut<resources::CreateBrushState>,
+    mut polygon_tool: ResMut<resources::PolygonToolState>,
+    mut transform_tool: ResMut<resources::TransformTool>,
+    mut grid_settings: ResMut<resources::GridSettings>,
 ) {
     {
         let Ok(mut window) = primary_query.get_single_mut() else {
@@ -107,7 +120,10 @@ pub fn editor_input_system(
 
     let mut clear_selection = false;
     if keycodes.just_pressed(KeyCode::KeyB) {
-        let res = edit_commands.apply(add_brush::Command { brush: default() });
+        let res = edit_commands.apply(add_brush::Command {
+            brush: default(),
+            material: None,
+        });
         if let Err(err) = res {
             warn!("failed to add brush: {:?}", err);
         }
@@ -118,15 +134,20 @@ pub fn editor_input_system(
     }
 
     if keycodes.just_pressed(KeyCode::KeyD) {
-        if let Ok(primary) = selection_query.get_single() {
-            let res = edit_commands.apply(duplicate_brush::Command {
-                template_entity: primary,
-            });
+        // the duplicates become the new selection. Only brushes can be duplicated.
+        let brushes = selection_query
+            .iter()
+            .filter(|entity| edit_commands.brush_query.contains(*entity))
+            .collect::<Vec<_>>();
+        edit_commands.begin_group("duplicate selection");
+        for &template_entity in &brushes {
+            let res = edit_commands.apply(duplicate_brush::Command { template_entity });
             if let Err(err) = res {
                 warn!("failed to duplicate brush: {:?}", err);
             }
-            clear_selection = true;
         }
+        edit_commands.end_group();
+        clear_selection = !brushes.is_empty();
     }
 
     if keycodes.just_pressed(KeyCode::KeyL) {
@@ -137,6 +158,14 @@ pub fn editor_input_system(
         clear_selection = true;
     }
 
+    if keycodes.just_pressed(KeyCode::KeyJ) {
+        let res = edit_commands.apply(add_directional_light::Command);
+        if let Err(err) = res {
+            warn!("failed to add directional light: {:?}", err);
+        }
+        clear_selection = true;
+    }
+
     if keycodes.just_pressed(KeyCode::KeyK) {
         commands
             .spawn((SpatialBundle::default(), components::EditablePoint))
@@ -168,18 +197,87 @@ pub fn editor_input_system(
     // }
 
     if keycodes.just_pressed(KeyCode::KeyX) {
-        if let Ok(primary) = selection_query.get_single() {
-            let res = edit_commands.apply(remove_entity::Command { entity: primary });
+        edit_commands.begin_group("remove selection");
+        for entity in &selection_query {
+            let res = edit_commands.apply(remove_entity::Command { entity });
             if let Err(err) = res {
                 warn!("failed to remove entity: {:?}", err);
             }
         }
+        edit_commands.end_group();
+    }
+
+    // assign the material of the browser to all faces of the selected brushes
+    if keycodes.just_pressed(KeyCode::KeyM) && !material_browser.selected_material.is_empty() {
+        edit_commands.begin_group(format!(
+            "set material {} on selection",
+            material_browser.selected_material
+        ));
+        for entity in &selection_query {
+            let Ok((material_props, _)) = edit_commands.brush_query.get(entity) else {
+                continue;
+            };
+            for face in 0..material_props.materials.len() {
+                let res = edit_commands.apply(set_brush_material::Command {
+                    entity,
+                    face: face as i32,
+                    material: material_browser.selected_material.clone(),
+                });
+                if let Err(err) = res {
+                    warn!("failed to set brush material: {:?}", err);
+                }
+            }
+        }
+        edit_commands.end_group();
+    }
+
+    // select all / invert selection
+    if keycodes.just_pressed(KeyCode::KeyA) {
+        for (entity, selected) in &object_query {
+            if !selected {
+                commands.entity(entity).insert(components::Selected);
+            }
+        }
+    }
+    if keycodes.just_pressed(KeyCode::KeyI) {
+        for (entity, selected) in &object_query {
+            if selected {
+                commands.entity(entity).remove::<components::Selected>();
+            } else {
+                commands.entity(entity).insert(components::Selected);
+            }
+        }
     }
 
     if keycodes.just_pressed(KeyCode::KeyC) {
         clip_state.clip_mode = !clip_state.clip_mode;
     }
 
+    if keycodes.just_pressed(KeyCode::KeyN) {
+        create_state.create_mode = !create_state.create_mode;
+        info!("create brush mode: {}", create_state.create_mode);
+    }
+
+    if keycodes.just_pressed(KeyCode::KeyP) {
+        polygon_tool.active = !polygon_tool.active;
+        info!("polygon tool: {}", polygon_tool.active);
+    }
+
+    if keycodes.just_pressed(KeyCode::BracketLeft) {
+        grid_settings.finer();
+        info!("grid size: {}", grid_settings.grid_size());
+    }
+    if keycodes.just_pressed(KeyCode::BracketRight) {
+        grid_settings.coarser();
+        info!("grid size: {}", grid_settings.grid_size());
+    }
+
+    // what drags in the ortho views do
+    if keycodes.just_pressed(KeyCode::KeyE) {
+        transform_tool.mode = transform_tool.mode.next();
+        info!("transform mode: {:?}", transform_tool.mode);
+    }
+
     if clear_selection {
         for entity in &selection_query {
             commands.entity(entity).remove::<components::Selected>();
@@ -277,7 +375,7 @@ pub fn create_brush_csg_system_inc(
 
     mut meshes: ResMut<Assets<Mesh>>,
 
-    mut query_changed: Query<(Entity, &CsgRepresentation, &Transform), With<components::CsgDirty>>,
+    query_changed: Query<Entity, With<components::CsgDirty>>,
     query_csg: Query<(
         &CsgRepresentation,
         &Transform,
@@ -321,12 +419,18 @@ pub fn create_brush_csg_system_inc(
     // 2. brushes overlapping (approximately, according to spatial index) with changed brushes
     // TODO: brushes overlapping the old geometry of changed brushes, otherwise fast moving (teleporting) brushes
     // can leave holes etc.
-    let mut affected = query_changed
-        .iter()
-        .map(|(e, _, _)| e)
-        .collect::<HashSet<_>>();
-    for (_entity, csg_repr, &_transform) in &mut query_changed {
-        affected.extend(spatial_index.query(csg_repr.bounds));
+    let changed = query_changed.iter().collect::<Vec<_>>();
+    let mut affected = changed.iter().copied().collect::<HashSet<_>>();
+    affected.extend(
+        spatial_index
+            .overlapping_pairs_of(changed)
+            .map(|(_, other)| other),
+    );
+
+    // (potentially) overlapping brushes of all affected brushes in one go
+    let mut overlapping = HashMap::<Entity, Vec<Entity>>::new();
+    for (entity, other) in spatial_index.overlapping_pairs_of(affected.iter().copied()) {
+        overlapping.entry(entity).or_default().push(other);
     }
 
     // re-create meshes for all affected brushes:
@@ -340,12 +444,11 @@ pub fn create_brush_csg_system_inc(
 
         debug!("csg changed: {:?}", entity);
 
-        let others = spatial_index
-            .query(csg_repr.bounds)
-            .filter_map(|entry| {
-                if entry == entity {
-                    return None;
-                }
+        let others = overlapping
+            .get(&entity)
+            .into_iter()
+            .flatten()
+            .filter_map(|&entry| {
                 let (other_csg, _, _) = query_csg.get(entry).ok()?;
                 let other_bsp = csg::Node::from_polygons(&other_csg.csg.polygons)?;
                 if !csg_repr.csg.intersects_or_touches(&other_csg.csg) {
@@ -429,7 +532,7 @@ pub fn create_brush_csg_system_inc(
         commands.entity(entity).push_children(&new_children);
     }
 
-    for (entity, _, _) in &query_changed {
+    for entity in &query_changed {
         commands.entity(entity).remove::<components::CsgDirty>();
     }
 
@@ -582,7 +685,7 @@ pub fn track_2d_vis_system(
     }
 }
 
-#[allow(clippy::type_complexity)]
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 pub fn track_lights_system(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -597,6 +700,11 @@ pub fn track_lights_system(
         Added<components::DirectionalLightProperties>,
     >,
     vis2d_query: Query<Entity, Added<components::EditablePoint>>,
+    changed_query: Query<
+        (&components::PointLightProperties, &Children),
+        Changed<components::PointLightProperties>,
+    >,
+    mut point_light_query: Query<&mut PointLight>,
     despawn_query: Query<
         Entity,
         (
@@ -687,11 +795,44 @@ pub fn track_lights_system(
 
         commands.entity(entity).add_child(light_entity);
     }
+    // property edits on existing lights (newly added lights are handled above, their bevy light is not spawned yet)
+    for (light_props, children) in &changed_query {
+        let mut lights = point_light_query.iter_many_mut(children);
+        while let Some(mut point_light) = lights.fetch_next() {
+            point_light.shadows_enabled = light_props.shadows_enabled;
+            point_light.range = light_props.range;
+        }
+    }
     for entity in &despawn_query {
         commands.entity(entity).despawn_recursive();
     }
 }
 
+// editor objects that were not spawned by an edit command (e.g. loaded from a file) get their id here
+#[allow(clippy::type_complexity)]
+pub fn assign_editor_object_ids_system(
+    mut commands: Commands,
+    mut editor_objects: ResMut<resources::EditorObjects>,
+    query: Query<
+        Entity,
+        (
+            Or<(
+                With<csg::Brush>,
+                With<components::PointLightProperties>,
+                With<components::DirectionalLightProperties>,
+            )>,
+            Without<components::EditorObjectId>,
+            Without<components::Despawn>,
+        ),
+    >,
+) {
+    for entity in &query {
+        let id = editor_objects.alloc_id();
+        commands.entity(entity).insert(id);
+        editor_objects.insert(id, entity);
+    }
+}
+
 // pub fn track_linked_transforms_system(
 //     query: Query<(&Transform, &EditorObjectLinkedBevyTransform)>,
 //     mut transform_query: Query<&mut Transform, Without<EditorObjectLinkedBevyTransform>>,
@@ -728,8 +869,14 @@ pub fn track_brush_updates(
 ) {
     let mut added_set = HashSet::new();
     for (entity, csg_repr) in &query_added {
-        spatial_index.update(entity, None, csg_repr.bounds);
         added_set.insert(entity);
+        // scene loads and imports rebuild the index with their brushes right away
+        if spatial_index.get_bounds(entity).is_some() {
+            continue;
+        }
+        if let Err(err) = spatial_index.update(entity, csg_repr.bounds) {
+            error!("failed to add brush to spatial index: {}", err);
+        }
     }
 
     let mut spatial_dirty_set = HashSet::new();
@@ -748,14 +895,17 @@ pub fn track_brush_updates(
                     // use center of spatial bounds as center for the whole entity.
                     // csg mesh and editor vis mesh need to be placed relative to it (ideally they use the same origin)
                     transform.translation = bounds.center;
-                    spatial_index.update(entity, Some(old_csg_repr.bounds), bounds);
+                    if let Err(err) = spatial_index.update(entity, bounds) {
+                        error!("failed to update brush in spatial index: {}", err);
+                    }
+                    let old_bounds = old_csg_repr.bounds;
                     *old_brush = brush.clone();
                     *old_csg_repr = components::CsgRepresentation { csg, bounds };
 
                     spatial_dirty_set.extend(
                         spatial_index
                             .query(bounds)
-                            .chain(spatial_index.query(old_csg_repr.bounds)),
+                            .chain(spatial_index.query(old_bounds)),
                     );
                 }
             }
@@ -765,179 +915,297 @@ pub fn track_brush_updates(
 
     for (entity, csg_repr) in &brush_despawn {
         commands.entity(entity).despawn_recursive();
-        spatial_index.remove(entity, csg_repr.bounds);
+        if let Err(err) = spatial_index.remove(entity) {
+            warn!("failed to remove brush from spatial index: {}", err);
+        }
         spatial_dirty_set.extend(spatial_index.query(csg_repr.bounds));
     }
+    // brushes despawned in this frame may have been collected by a query before their removal from the index
+    for (entity, _) in &brush_despawn {
+        spatial_dirty_set.remove(&entity);
+    }
 
     for dirty in spatial_dirty_set {
         commands.entity(dirty).insert(components::CsgDirty);
     }
 }
 
-#[derive(Serialize, Deserialize)]
-enum ExternalEditorObject {
-    Brush {
-        brush: csg::Brush,
-        material_properties: components::BrushMaterialProperties,
-    },
-    PointLight {
-        translation: Vec3,
-        light_properties: components::PointLightProperties,
-    },
+pub const DEFAULT_SCENE_FILE: &str = "scene.ron";
+
+pub(crate) fn clear_scene(
+    commands: &mut Commands,
+    entities: impl Iterator<Item = Entity>,
+    spatial_index: &mut SpatialIndex,
+    editor_objects: &mut resources::EditorObjects,
+    undo_stack: &mut UndoStack,
+) {
+    for entity in entities {
+        commands.entity(entity).despawn_recursive();
+    }
+    // TODO: think again if this is smart
+    spatial_index.clear();
+    editor_objects.clear();
+    // undo entries refer to objects of the old scene
+    undo_stack.clear();
 }
 
-#[allow(clippy::too_many_arguments)]
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn load_save_editor_objects(
     mut commands: Commands,
 
     keycodes: Res<ButtonInput<KeyCode>>,
-    brush_query: Query<(Entity, &csg::Brush, &components::BrushMaterialProperties)>,
-    light_query: Query<(Entity, &components::PointLightProperties, &Transform)>,
+    brush_query: Query<(
+        Entity,
+        &csg::Brush,
+        &components::BrushMaterialProperties,
+        Option<&EditorObjectId>,
+    )>,
+    light_query: Query<(
+        Entity,
+        &components::PointLightProperties,
+        &Transform,
+        Option<&EditorObjectId>,
+    )>,
+    directional_light_query: Query<(
+        Entity,
+        &components::DirectionalLightProperties,
+        &Transform,
+        Option<&EditorObjectId>,
+    )>,
     mut spatial_index: ResMut<SpatialIndex>,
     mut materials: ResMut<resources::Materials>,
+    mut editor_objects: ResMut<resources::EditorObjects>,
+    mut undo_stack: ResMut<UndoStack>,
+    mut scene_file: ResMut<resources::SceneFile>,
+    mut wm_state: ResMut<resources::WmState>,
+    egui_keyboard: Res<resources::EguiKeyboard>,
 ) {
-    if keycodes.just_pressed(KeyCode::F6) || keycodes.just_pressed(KeyCode::F7) {
-        let despawn = brush_query
-            .iter()
-            .map(|(entity, _, _)| entity)
-            .chain(light_query.iter().map(|(entity, _, _)| entity));
-
-        for entity in despawn {
-            commands.entity(entity).despawn_recursive();
-        }
-        // TODO: think again if this is smart
-        spatial_index.clear();
+    let keyboard = !egui_keyboard.0;
+    if keyboard && keycodes.just_pressed(KeyCode::F5) {
+        scene_file.request(resources::SceneFileRequest::Save);
     }
-
-    if keycodes.just_pressed(KeyCode::F5) {
-        let brushes =
-            brush_query.iter().map(
-                |(_, brush, material_properties)| ExternalEditorObject::Brush {
-                    brush: brush.clone(),
-                    material_properties: material_properties.clone(),
-                },
-            );
-
-        let lights = light_query.iter().map(|(_, light_properties, transform)| {
-            ExternalEditorObject::PointLight {
-                translation: transform.translation,
-                light_properties: light_properties.clone(),
-            }
-        });
-
-        if let Ok(file) = std::fs::File::create("scene.ron") {
-            let _ = ron::ser::to_writer_pretty(
-                file,
-                &brushes.chain(lights).collect::<Vec<_>>(),
-                ron::ser::PrettyConfig::default(), // .indentor(" ".to_string())
-                                                   // .compact_arrays(true),
-            );
-        }
-        // if let Ok(mut file) = std::fs::File::create("scene.bin") {
-        //     let v = flexbuffers::to_vec(&brushes.chain(lights).collect::<Vec<_>>()).unwrap();
-        //     file.write_all(&v[..]).unwrap();
-        // }
+    if keyboard && keycodes.just_pressed(KeyCode::F6) {
+        // re-load current scene
+        let path = scene_file
+            .path
+            .clone()
+            .unwrap_or_else(|| DEFAULT_SCENE_FILE.into());
+        scene_file.request(resources::SceneFileRequest::Open(path));
+    }
+    if keyboard && keycodes.just_pressed(KeyCode::F7) {
+        scene_file.request(resources::SceneFileRequest::Import("nav3.wsx".into()));
     }
 
-    if keycodes.just_pressed(KeyCode::F6) {
-        if let Ok(file) = std::fs::File::open("scene.ron") {
-            let objects: Vec<ExternalEditorObject> = ron::de::from_reader(file).unwrap_or_default();
-
-            for editor_object in objects {
-                match editor_object {
-                    ExternalEditorObject::Brush {
-                        brush,
-                        material_properties,
-                    } => commands.spawn(
-                        components::EditorObjectBrushBundle::from_brush(brush)
-                            .with_material_properties(material_properties),
-                    ),
-                    ExternalEditorObject::PointLight {
-                        translation,
-                        light_properties,
-                    } => commands.spawn(components::EditorObjectPointlightBundle {
-                        spatial: SpatialBundle::from_transform(Transform::from_translation(
-                            translation,
-                        )),
-                        light_properties,
-                        ..default()
-                    }),
+    let scene_entities = || {
+        brush_query
+            .iter()
+            .map(|(entity, _, _, _)| entity)
+            .chain(light_query.iter().map(|(entity, _, _, _)| entity))
+            .chain(
+                directional_light_query
+                    .iter()
+                    .map(|(entity, _, _, _)| entity),
+            )
+    };
+
+    while let Some(request) = scene_file.next_request(undo_stack.revision) {
+        info!("scene file request: {:?}", request);
+        let res = match &request {
+            resources::SceneFileRequest::Save | resources::SceneFileRequest::SaveAs(_) => {
+                let path = if let resources::SceneFileRequest::SaveAs(path) = &request {
+                    path.clone()
+                } else {
+                    scene_file
+                        .path
+                        .clone()
+                        .unwrap_or_else(|| DEFAULT_SCENE_FILE.into())
                 };
+                let brushes = brush_query
+                    .iter()
+                    .map(
+                        |(_, brush, material_properties, id)| ExternalEditorObject::Brush {
+                            id: id.copied(),
+                            brush: brush.clone(),
+                            material_properties: material_properties.clone(),
+                        },
+                    );
+                let lights = light_query
+                    .iter()
+                    .map(
+                        |(_, light_properties, transform, id)| ExternalEditorObject::PointLight {
+                            id: id.copied(),
+                            translation: transform.translation,
+                            light_properties: light_properties.clone(),
+                        },
+                    );
+                let directional_lights =
+                    directional_light_query
+                        .iter()
+                        .map(|(_, light_properties, transform, id)| {
+                            ExternalEditorObject::DirectionalLight {
+                                id: id.copied(),
+                                translation: transform.translation,
+                                rotation: transform.rotation,
+                                light_properties: light_properties.clone(),
+                            }
+                        });
+                let objects = brushes
+                    .chain(lights)
+                    .chain(directional_lights)
+                    .collect::<Vec<_>>();
+                // if let Ok(mut file) = std::fs::File::create("scene.bin") {
+                //     let v = flexbuffers::to_vec(&brushes.chain(lights).collect::<Vec<_>>()).unwrap();
+                //     file.write_all(&v[..]).unwrap();
+                // }
+                write_scene_file(&path, &objects, &scene_file.quarantine).map(|_| {
+                    scene_file.mark_saved(undo_stack.revision);
+                    wm_state.settings.add_recent_file(&path);
+                    scene_file.path = Some(path);
+                })
             }
+            resources::SceneFileRequest::Open(path) => {
+                // read before touching the current scene, so it survives a broken file
+                read_scene_file(path).map(|loaded_scene| {
+                    clear_scene(
+                        &mut commands,
+                        scene_entities(),
+                        &mut spatial_index,
+                        &mut editor_objects,
+                        &mut undo_stack,
+                    );
+                    scene_file.quarantine =
+                        loaded_scene.spawn(&mut commands, &mut editor_objects, &mut spatial_index);
+                    scene_file.mark_saved(undo_stack.revision);
+                    wm_state.settings.add_recent_file(path);
+                    scene_file.path = Some(path.clone());
+                })
+            }
+            resources::SceneFileRequest::Import(path) => {
+                // NOTE: wsx::load_brushes panics on missing files
+                std::fs::metadata(path)
+                    .map_err(anyhow::Error::from)
+                    .map(|_| {
+                        clear_scene(
+                            &mut commands,
+                            scene_entities(),
+                            &mut spatial_index,
+                            &mut editor_objects,
+                            &mut undo_stack,
+                        );
+                        import_wsx(
+                            path,
+                            &mut commands,
+                            &mut materials,
+                            &mut editor_objects,
+                            &mut spatial_index,
+                        );
+                        scene_file.quarantine.clear();
+                        // imported scenes have no scene file until saved
+                        scene_file.mark_unsaved();
+                        scene_file.path = None;
+                    })
+            }
+        };
+        if let Err(err) = res {
+            warn!("scene file request {:?} failed: {:?}", request, err);
+        }
+        // replacing the scene twice in one frame would miss the objects spawned by the first one (not flushed yet)
+        if request.discards_scene() {
+            break;
         }
     }
+}
 
-    if keycodes.just_pressed(KeyCode::F7) {
-        // let objects = existing_objects.iter().map(|(_,obj)| obj).collect::<Vec<_>>();
-
-        let materials = &mut *materials;
-        // let filename = &"t4.wsx";
-        // let filename = &"x8.wsx";
-        let filename = &"nav3.wsx";
-        let (brushes, appearance_map) = wsx::load_brushes(filename);
-        info!("appearance map: {:?}", appearance_map);
-
-        for mut brush in brushes {
-            let materials = brush
-                .appearances
-                .iter()
-                .map(|id| appearance_map.get(id).unwrap().clone())
-                .collect();
-            brush.appearances = (0..brush.planes.len() as i32).collect();
+fn import_wsx(
+    filename: &Path,
+    commands: &mut Commands,
+    materials: &mut resources::Materials,
+    editor_objects: &mut resources::EditorObjects,
+    spatial_index: &mut SpatialIndex,
+) {
+    // let filename = &"t4.wsx";
+    // let filename = &"x8.wsx";
+    let (brushes, appearance_map) = wsx::load_brushes(filename);
+    info!("appearance map: {:?}", appearance_map);
+
+    let mut spatial_entries = Vec::new();
+    for mut brush in brushes {
+        let materials = brush
+            .appearances
+            .iter()
+            .map(|id| appearance_map.get(id).unwrap().clone())
+            .collect();
+        brush.appearances = (0..brush.planes.len() as i32).collect();
 
-            commands.spawn(
-                EditorObjectBrushBundle::from_brush(brush)
-                    .with_material_properties(BrushMaterialProperties { materials }),
-            );
-        }
-        materials.id_to_name_map = appearance_map;
-
-        let appearance_names = materials.id_to_name_map.values().collect::<BTreeSet<_>>();
-        // let mut material_names = materials.material_defs.keys();
-        let mut material_names = [
-            "material/floors/bathroomtile2",
-            "material/floors/bathroomtile1",
-            "material/floors/rich-brown-tile",
-            "material/floors/modern-tile1",
-            "material/floors/green-shower-tile1",
-            "material/floors/green-ceramic-tiles",
-            "material/floors/industrial-tile1",
-            "material/floors/diamond-inlay-tile",
-            "material/floors/cheap-old-linoleum",
-            "material/floors/gross-dirty-tiles",
-            "material/floors/bathroomtile2",
-            "material/floors/bathroomtile1",
-            "material/floors/rich-brown-tile",
-            "material/floors/modern-tile1",
-            "material/floors/green-shower-tile1",
-            "material/floors/green-ceramic-tiles",
-            "material/floors/industrial-tile1",
-            "material/floors/diamond-inlay-tile",
-            "material/floors/cheap-old-linoleum",
-            "material/floors/gross-dirty-tiles",
-        ]
-        .iter();
-        for name in appearance_names {
-            match materials.symlinks.entry(name.clone()) {
-                bevy::utils::hashbrown::hash_map::Entry::Vacant(e) => {
-                    e.insert(material_names.next().unwrap().to_string());
-                }
-                bevy::utils::hashbrown::hash_map::Entry::Occupied(_) => (),
+        let bundle = match EditorObjectBrushBundle::from_brush(brush) {
+            Ok(bundle) => bundle.with_material_properties(BrushMaterialProperties { materials }),
+            Err(err) => {
+                warn!("skipping brush on import: {}", err);
+                continue;
+            }
+        };
+        let bounds = bundle.csg_representation.bounds;
+        let id = editor_objects.alloc_id();
+        let entity = commands.spawn((bundle, id)).id();
+        editor_objects.insert(id, entity);
+        spatial_entries.push((entity, bounds));
+    }
+    // in one go, same as for scene loads
+    spatial_index.rebuild(spatial_entries);
+    materials.id_to_name_map = appearance_map;
+
+    let appearance_names = materials.id_to_name_map.values().collect::<BTreeSet<_>>();
+    // let mut material_names = materials.material_defs.keys();
+    let mut material_names = [
+        "material/floors/bathroomtile2",
+        "material/floors/bathroomtile1",
+        "material/floors/rich-brown-tile",
+        "material/floors/modern-tile1",
+        "material/floors/green-shower-tile1",
+        "material/floors/green-ceramic-tiles",
+        "material/floors/industrial-tile1",
+        "material/floors/diamond-inlay-tile",
+        "material/floors/cheap-old-linoleum",
+        "material/floors/gross-dirty-tiles",
+        "material/floors/bathroomtile2",
+        "material/floors/bathroomtile1",
+        "material/floors/rich-brown-tile",
+        "material/floors/modern-tile1",
+        "material/floors/green-shower-tile1",
+        "material/floors/green-ceramic-tiles",
+        "material/floors/industrial-tile1",
+        "material/floors/diamond-inlay-tile",
+        "material/floors/cheap-old-linoleum",
+        "material/floors/gross-dirty-tiles",
+    ]
+    .iter();
+    for name in appearance_names {
+        match materials.symlinks.entry(name.clone()) {
+            bevy::utils::hashbrown::hash_map::Entry::Vacant(e) => {
+                e.insert(material_names.next().unwrap().to_string());
             }
+            bevy::utils::hashbrown::hash_map::Entry::Occupied(_) => (),
         }
+    }
 
-        // TODO: do not load twice. Probably makes no difference, but I still hate it...
-        let pointlights = wsx::load_pointlights(filename);
-        for (pos, _range) in pointlights {
-            commands.spawn(components::EditorObjectPointlightBundle {
-                spatial: SpatialBundle::from_transform(Transform::from_translation(pos)),
-                light_properties: components::PointLightProperties {
-                    shadows_enabled: false,
-                    range: 5.0,
+    // TODO: do not load twice. Probably makes no difference, but I still hate it...
+    let pointlights = wsx::load_pointlights(filename);
+    for (pos, _range) in pointlights {
+        let id = editor_objects.alloc_id();
+        let entity = commands
+            .spawn((
+                components::EditorObjectPointlightBundle {
+                    spatial: SpatialBundle::from_transform(Transform::from_translation(pos)),
+                    light_properties: components::PointLightProperties {
+                        shadows_enabled: false,
+                        range: 5.0,
+                    },
+                    ..default()
                 },
-                ..default()
-            });
-        }
+                id,
+            ))
+            .id();
+        editor_objects.insert(id, entity);
     }
 }
 
@@ -968,57 +1236,3 @@ pub fn track_wireframe_system(
         }
     }
 }
-
-#[derive(Resource, Default)]
-pub struct LogSink {
-    db: Option<sled::Db>,
-}
-#[allow(clippy::type_complexity)]
-pub fn log_editor_objects(
-    mut log_sink: ResMut<LogSink>,
-    brush_query: Query<
-        (Entity, &csg::Brush, &components::BrushMaterialProperties),
-        Or<(
-            Changed<csg::Brush>,
-            Changed<components::BrushMaterialProperties>,
-        )>,
-    >,
-    light_query: Query<
-        (Entity, &components::PointLightProperties, &Transform),
-        Or<(
-            Changed<components::PointLightProperties>,
-            Changed<Transform>,
-        )>,
-    >,
-) {
-    //
-    if log_sink.db.is_none() {
-        // sled::Config::new().path("/tmp/physics.log").create_new()
-        log_sink.db = Some(sled::open("/tmp/physics.log").expect("failed to open log db"));
-    }
-    let Some(db) = log_sink.db.as_mut() else {
-        return;
-    };
-    for (e, brush, material_properties) in &brush_query {
-        info!("brush update: {:?}", e);
-        let o = ExternalEditorObject::Brush {
-            brush: brush.clone(),
-            material_properties: material_properties.clone(),
-        };
-
-        let k = ron::ser::to_string(&e.to_bits()).unwrap();
-        let v = ron::ser::to_string(&o).unwrap();
-        db.insert(k.as_bytes(), v.as_bytes()).unwrap();
-    }
-    for (e, light_properties, transform) in &light_query {
-        let o = ExternalEditorObject::PointLight {
-            translation: transform.translation,
-            light_properties: light_properties.clone(),
-        };
-
-        let k = ron::ser::to_string(&e.to_bits()).unwrap();
-        let v = ron::ser::to_string(&o).unwrap();
-        db.insert(k.as_bytes(), v.as_bytes()).unwrap();
-    }
-}
-// pub fn setup_log_editor_objects(db)
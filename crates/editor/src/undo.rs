@@ -1,41 +1,127 @@
-use super::edit_commands;
-use crate::components;
+use super::edit_commands::{self, EditCommandError, ObjectSnapshot};
+use crate::{
+    components::{self, EditorObjectId},
+    resources::{self, EditorObjects},
+};
 use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
-use bevy::utils::HashMap;
 
 // #[derive(Clone)]
 pub enum UndoEntry {
     Generic {
         cmd: Box<dyn edit_commands::UndoCommand + Send + Sync + 'static>,
     },
+    // several commands that are undone / redone as a single step, see UndoStack::begin_group
+    Group {
+        description: String,
+        cmds: Vec<Box<dyn edit_commands::UndoCommand + Send + Sync + 'static>>,
+    },
+}
+
+impl UndoEntry {
+    pub fn description(&self) -> String {
+        match self {
+            UndoEntry::Generic { cmd } => cmd.description(),
+            UndoEntry::Group { description, .. } => description.clone(),
+        }
+    }
+
+    fn undo(&self, undo_commands: &mut UndoCommands) -> edit_commands::Result<()> {
+        match self {
+            UndoEntry::Generic { cmd } => cmd.undo(undo_commands),
+            UndoEntry::Group { cmds, .. } => {
+                // keep going on errors, a single broken command should not leave half of the group applied
+                let mut res = Ok(());
+                for cmd in cmds.iter().rev() {
+                    if let Err(err) = cmd.undo(undo_commands) {
+                        res = Err(err);
+                    }
+                }
+                res
+            }
+        }
+    }
+
+    fn redo(&self, undo_commands: &mut UndoCommands) -> edit_commands::Result<()> {
+        match self {
+            UndoEntry::Generic { cmd } => cmd.redo(undo_commands),
+            UndoEntry::Group { cmds, .. } => {
+                let mut res = Ok(());
+                for cmd in cmds {
+                    if let Err(err) = cmd.redo(undo_commands) {
+                        res = Err(err);
+                    }
+                }
+                res
+            }
+        }
+    }
 }
 
 #[derive(Resource, Default)]
 pub struct UndoStack {
     pub stack: Vec<UndoEntry>,
+    // undone entries, top of the stack is the next one to redo. Cleared on every new edit.
+    pub redo_stack: Vec<UndoEntry>,
     pub open: bool,
-    pub entity_recreate_map: HashMap<Entity, Entity>,
+    // requested history position (i.e. number of entries on the undo stack), set by the history ui
+    pub jump_target: Option<usize>,
+    // bumped on every change of the scene done through the stack (edit, undo, redo). Used to detect unsaved changes.
+    pub revision: u64,
+    // group that is currently recorded (description and commands)
+    group: Option<(
+        String,
+        Vec<Box<dyn edit_commands::UndoCommand + Send + Sync + 'static>>,
+    )>,
 }
 
 impl UndoStack {
-    pub fn remap_entity(&self, entity: Entity) -> Entity {
-        match self.entity_recreate_map.get(&entity) {
-            Some(mapped_entity) => {
-                info!("remap {:?} {:?}", entity, mapped_entity);
-                *mapped_entity
-            }
-            None => entity,
-        }
-    }
     pub fn commit(&mut self) {
         info!("commit");
+        self.end_group();
+        self.open = false;
+    }
+
+    // start a transaction: everything pushed until end_group (or commit) becomes a single undo entry.
+    // NOTE: commands of a group are undone / redone within one frame, so they must not depend on each others deferred
+    // changes (e.g. one command touching an object that is re-spawned by another one of the same group).
+    pub fn begin_group(&mut self, description: impl Into<String>) {
+        self.end_group();
         self.open = false;
+        self.group = Some((description.into(), Vec::new()));
     }
+
+    pub fn end_group(&mut self) {
+        let Some((description, mut cmds)) = self.group.take() else {
+            return;
+        };
+        // single command groups look like the plain command in the history
+        match cmds.len() {
+            0 => (),
+            1 => self.stack.push(UndoEntry::Generic {
+                cmd: cmds.pop().unwrap(),
+            }),
+            _ => self.stack.push(UndoEntry::Group { description, cmds }),
+        }
+        self.open = false;
+    }
+
     pub fn push_generic(
         &mut self,
         cmd: Box<dyn edit_commands::UndoCommand + Send + Sync + 'static>,
     ) {
+        self.revision += 1;
+        self.redo_stack.clear();
+        if let Some((_, cmds)) = &mut self.group {
+            // merging works within the group, e.g. the per-frame updates of each object of a multi-object drag
+            if !cmds
+                .iter_mut()
+                .any(|group_cmd| group_cmd.try_merge(cmd.as_ref()))
+            {
+                cmds.push(cmd);
+            }
+            return;
+        }
         if let (true, Some(UndoEntry::Generic { cmd: top_cmd })) =
             (self.open, self.stack.last_mut())
         {
@@ -46,6 +132,20 @@ impl UndoStack {
         self.stack.push(UndoEntry::Generic { cmd });
         self.open = true;
     }
+
+    // history as seen by the user: applied entries (oldest first) followed by undone entries (next redo first)
+    pub fn history(&self) -> impl Iterator<Item = &UndoEntry> {
+        self.stack.iter().chain(self.redo_stack.iter().rev())
+    }
+
+    // drop the whole history, e.g. when the scene is replaced by a loaded one. Keeps the revision.
+    pub fn clear(&mut self) {
+        self.stack.clear();
+        self.redo_stack.clear();
+        self.open = false;
+        self.jump_target = None;
+        self.group = None;
+    }
 }
 
 #[derive(SystemParam)]
@@ -53,21 +153,553 @@ pub struct UndoCommands<'w, 's> {
     pub commands: Commands<'w, 's>,
     pub material_properties_query: Query<'w, 's, &'static mut components::BrushMaterialProperties>,
     pub transform_query: Query<'w, 's, &'static mut Transform>,
+    pub point_light_query: Query<'w, 's, &'static mut components::PointLightProperties>,
+    pub directional_light_query: Query<'w, 's, &'static mut components::DirectionalLightProperties>,
     pub undo_stack: ResMut<'w, UndoStack>,
+    pub editor_objects: ResMut<'w, EditorObjects>,
+}
+
+impl<'w, 's> UndoCommands<'w, 's> {
+    // entity currently representing the editor object
+    pub fn entity(&self, id: EditorObjectId) -> Result<Entity, EditCommandError> {
+        self.editor_objects
+            .get(id)
+            .ok_or(EditCommandError::UnknownObject(id))
+    }
+
+    // re-create a despawned editor object under its old id
+    pub fn respawn_object(
+        &mut self,
+        id: EditorObjectId,
+        snapshot: &ObjectSnapshot,
+    ) -> Result<Entity, EditCommandError> {
+        let entity = snapshot.spawn(id, &mut self.commands)?;
+        self.editor_objects.insert(id, entity);
+        Ok(entity)
+    }
+
+    pub fn despawn_object(&mut self, id: EditorObjectId) -> Result<(), EditCommandError> {
+        let entity = self.entity(id)?;
+        self.commands
+            .get_entity(entity)
+            .ok_or(EditCommandError::UnknownEntity(entity))?
+            .insert(components::Despawn);
+        self.editor_objects.remove(id);
+        Ok(())
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.undo_stack.commit();
+        let Some(undo_entry) = self.undo_stack.stack.pop() else {
+            info!("nothing to undo");
+            return false;
+        };
+        info!("undo: {}", undo_entry.description());
+        self.undo_stack.revision += 1;
+        let res = undo_entry.undo(self);
+        if let Err(err) = res {
+            warn!("error on undo apply: {:?}", err);
+        }
+        self.undo_stack.redo_stack.push(undo_entry);
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.undo_stack.commit();
+        let Some(redo_entry) = self.undo_stack.redo_stack.pop() else {
+            info!("nothing to redo");
+            return false;
+        };
+        info!("redo: {}", redo_entry.description());
+        self.undo_stack.revision += 1;
+        let res = redo_entry.redo(self);
+        if let Err(err) = res {
+            warn!("error on redo apply: {:?}", err);
+        }
+        self.undo_stack.stack.push(redo_entry);
+        true
+    }
+}
+
+pub fn undo_system(
+    mut undo_commands: UndoCommands,
+    keycodes: Res<ButtonInput<KeyCode>>,
+    egui_keyboard: Res<resources::EguiKeyboard>,
+) {
+    let keyboard = !egui_keyboard.0;
+    if keyboard && keycodes.just_pressed(KeyCode::KeyZ) {
+        undo_commands.undo_stack.jump_target = None;
+        undo_commands.undo();
+    } else if keyboard && keycodes.just_pressed(KeyCode::KeyY) {
+        undo_commands.undo_stack.jump_target = None;
+        undo_commands.redo();
+    }
+
+    // jumps in the history are done one step per frame, so that entities re-spawned by one step
+    // exist (i.e. commands are flushed) before the next step touches them.
+    if let Some(target) = undo_commands.undo_stack.jump_target {
+        let pos = undo_commands.undo_stack.stack.len();
+        let moved = if target < pos {
+            undo_commands.undo()
+        } else if target > pos {
+            undo_commands.redo()
+        } else {
+            false
+        };
+        if !moved {
+            undo_commands.undo_stack.jump_target = None;
+        }
+    }
 }
 
-pub fn undo_system(mut undo_commands: UndoCommands, keycodes: Res<ButtonInput<KeyCode>>) {
-    if keycodes.just_pressed(KeyCode::KeyZ) {
-        let undo_entry = undo_commands.undo_stack.stack.pop();
-        // info!("undo: {:?}", undo_entry);
-        match undo_entry {
-            Some(UndoEntry::Generic { cmd }) => {
-                let res = cmd.undo(&mut undo_commands);
-                if let Err(err) = res {
-                    warn!("error on undo apply: {:?}", err);
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use bevy::ecs::{schedule::ExecutorKind, system::SystemState};
+
+    use super::*;
+    use crate::{
+        clip_systems,
+        edit_commands::{
+            add_brush, add_directional_light, add_pointlight, clip_brush, duplicate_brush,
+            remove_entity, set_brush_material, set_light_properties, split_brush,
+            update_brush_drag, update_point_transform, EditCommand, EditCommands,
+        },
+        resources, systems,
+    };
+
+    // xorshift, good enough to generate edit sequences
+    struct Rng(u64);
+
+    impl Rng {
+        fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next_u64() % n as u64) as usize
+        }
+
+        fn range(&mut self, min: f32, max: f32) -> f32 {
+            min + (self.next_u64() % 1024) as f32 / 1024.0 * (max - min)
+        }
+
+        fn vec3(&mut self, size: f32) -> Vec3 {
+            Vec3::new(
+                self.range(-size, size),
+                self.range(-size, size),
+                self.range(-size, size),
+            )
+        }
+    }
+
+    // just enough of the editor to apply edit commands: the despawn / update handling of the track systems
+    struct TestEditor {
+        world: World,
+        schedule: Schedule,
+    }
+
+    impl TestEditor {
+        fn new() -> Self {
+            let mut world = World::new();
+            world.init_resource::<UndoStack>();
+            world.init_resource::<EditorObjects>();
+            world.init_resource::<resources::Materials>();
+            world.init_resource::<sstree::SpatialIndex>();
+            world.init_resource::<Assets<Mesh>>();
+            let mut schedule = Schedule::default();
+            schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+            schedule.add_systems((systems::track_brush_updates, systems::track_lights_system));
+            TestEditor { world, schedule }
+        }
+
+        fn apply(&mut self, cmd: impl EditCommand) {
+            let mut state: SystemState<EditCommands> = SystemState::new(&mut self.world);
+            let res = state.get_mut(&mut self.world).apply(cmd);
+            state.apply(&mut self.world);
+            res.expect("apply edit command");
+            self.world.resource_mut::<UndoStack>().commit();
+            self.schedule.run(&mut self.world);
+        }
+
+        // apply without committing, i.e. like the per-frame updates of a drag
+        fn apply_uncommitted(&mut self, cmds: Vec<impl EditCommand>) {
+            let mut state: SystemState<EditCommands> = SystemState::new(&mut self.world);
+            let mut edit_commands = state.get_mut(&mut self.world);
+            for cmd in cmds {
+                edit_commands.apply(cmd).expect("apply edit command");
+            }
+            state.apply(&mut self.world);
+            self.schedule.run(&mut self.world);
+        }
+
+        fn undo(&mut self) -> bool {
+            let mut state: SystemState<UndoCommands> = SystemState::new(&mut self.world);
+            let res = state.get_mut(&mut self.world).undo();
+            state.apply(&mut self.world);
+            self.schedule.run(&mut self.world);
+            res
+        }
+
+        fn redo(&mut self) -> bool {
+            let mut state: SystemState<UndoCommands> = SystemState::new(&mut self.world);
+            let res = state.get_mut(&mut self.world).redo();
+            state.apply(&mut self.world);
+            self.schedule.run(&mut self.world);
+            res
+        }
+
+        // all editor objects by id. Also checks that the ids resolve to the right entities.
+        fn objects(&mut self) -> BTreeMap<EditorObjectId, String> {
+            let mut query = self.world.query::<(
+                Entity,
+                &EditorObjectId,
+                Option<&csg::Brush>,
+                Option<&components::BrushMaterialProperties>,
+                Option<&components::PointLightProperties>,
+                Option<&components::DirectionalLightProperties>,
+                &Transform,
+            )>();
+            let editor_objects = self.world.resource::<EditorObjects>();
+            query
+                .iter(&self.world)
+                .map(
+                    |(
+                        entity,
+                        id,
+                        brush,
+                        material_props,
+                        point_light,
+                        directional_light,
+                        transform,
+                    )| {
+                        assert_eq!(editor_objects.get(*id), Some(entity));
+                        // the transform of brushes is derived from the geometry
+                        let desc = match brush {
+                            Some(brush) => format!("{:?} {:?}", brush, material_props),
+                            None => {
+                                format!("{:?} {:?} {:?}", point_light, directional_light, transform)
+                            }
+                        };
+                        (*id, desc)
+                    },
+                )
+                .collect()
+        }
+
+        fn brushes(&mut self) -> Vec<(Entity, csg::Brush, components::BrushMaterialProperties)> {
+            let mut query = self
+                .world
+                .query::<(Entity, &csg::Brush, &components::BrushMaterialProperties)>();
+            query
+                .iter(&self.world)
+                .map(|(entity, brush, material_props)| {
+                    (entity, brush.clone(), material_props.clone())
+                })
+                .collect()
+        }
+
+        fn lights(&mut self) -> Vec<(Entity, set_light_properties::LightProperties)> {
+            let mut query = self.world.query::<(
+                Entity,
+                Option<&components::PointLightProperties>,
+                Option<&components::DirectionalLightProperties>,
+            )>();
+            query
+                .iter(&self.world)
+                .filter_map(|(entity, point_light, directional_light)| {
+                    match (point_light, directional_light) {
+                        (Some(properties), _) => Some((
+                            entity,
+                            set_light_properties::LightProperties::Point(properties.clone()),
+                        )),
+                        (_, Some(properties)) => Some((
+                            entity,
+                            set_light_properties::LightProperties::Directional(properties.clone()),
+                        )),
+                        _ => None,
+                    }
+                })
+                .collect()
+        }
+
+        fn object_entities(&mut self) -> Vec<Entity> {
+            let mut query = self.world.query_filtered::<Entity, With<EditorObjectId>>();
+            query.iter(&self.world).collect()
+        }
+
+        // apply a random edit of the given kind. Returns false if there was no suitable object.
+        fn random_edit(&mut self, kind: usize, rng: &mut Rng) -> bool {
+            match kind {
+                0 => {
+                    let offset = rng.vec3(10.0);
+                    let mut brush = csg::Brush::default();
+                    for plane in &mut brush.planes {
+                        plane.w += plane.normal.dot(offset);
+                    }
+                    self.apply(add_brush::Command {
+                        brush,
+                        material: None,
+                    });
+                }
+                1 => self.apply(add_pointlight::Command),
+                2 => self.apply(add_directional_light::Command),
+                3 => {
+                    let brushes = self.brushes();
+                    if brushes.is_empty() {
+                        return false;
+                    }
+                    let (template_entity, _, _) = brushes[rng.below(brushes.len())];
+                    self.apply(duplicate_brush::Command { template_entity });
+                }
+                4 => {
+                    let entities = self.object_entities();
+                    if entities.is_empty() {
+                        return false;
+                    }
+                    let entity = entities[rng.below(entities.len())];
+                    self.apply(remove_entity::Command { entity });
+                }
+                5 => {
+                    let brushes = self.brushes();
+                    if brushes.is_empty() {
+                        return false;
+                    }
+                    let (entity, start_brush, _) = brushes[rng.below(brushes.len())].clone();
+                    let mut brush = start_brush.clone();
+                    let face = rng.below(brush.planes.len());
+                    brush.planes[face].w += rng.range(0.1, 1.0);
+                    self.apply(update_brush_drag::Command {
+                        entity,
+                        start_brush,
+                        brush,
+                    });
+                }
+                6 => {
+                    let lights = self.lights();
+                    if lights.is_empty() {
+                        return false;
+                    }
+                    let (entity, _) = lights[rng.below(lights.len())];
+                    self.apply(update_point_transform::Command {
+                        entity,
+                        transform: Transform::from_translation(rng.vec3(10.0)),
+                    });
                 }
+                7 => {
+                    let brushes = self.brushes();
+                    if brushes.is_empty() {
+                        return false;
+                    }
+                    let (entity, _, material_props) = &brushes[rng.below(brushes.len())];
+                    self.apply(set_brush_material::Command {
+                        entity: *entity,
+                        face: rng.below(material_props.materials.len()) as i32,
+                        material: format!("material/test/{}", rng.below(8)),
+                    });
+                }
+                8 | 9 => {
+                    let brushes = self.brushes();
+                    if brushes.is_empty() {
+                        return false;
+                    }
+                    let (entity, brush, material_props) = brushes[rng.below(brushes.len())].clone();
+                    let Ok(csg) = csg::Csg::try_from(brush.clone()) else {
+                        return false;
+                    };
+                    let (center, _) = csg.bounding_sphere();
+                    let normal = [Vec3::X, Vec3::Y, Vec3::Z][rng.below(3)];
+                    let plane = csg::Plane::new(normal, normal.dot(center));
+                    let clip = |plane| {
+                        clip_systems::clipped_brush(
+                            brush.clone(),
+                            plane,
+                            &material_props,
+                            "material/test/clip",
+                        )
+                        .filter(|(clipped, _)| {
+                            csg::Csg::try_from(clipped.clone())
+                                .is_ok_and(|csg| csg.bounding_sphere().1 > 0.1)
+                        })
+                    };
+                    let (Some((red, red_props)), Some((green, green_props))) =
+                        (clip(plane), clip(plane.flipped()))
+                    else {
+                        return false;
+                    };
+                    if kind == 8 {
+                        self.apply(clip_brush::Command {
+                            entity,
+                            start_brush: brush,
+                            start_material_props: material_props,
+                            brush: red,
+                            material_props: red_props,
+                        });
+                    } else {
+                        self.apply(split_brush::Command {
+                            entity,
+                            start_brush: brush,
+                            start_material_props: material_props,
+                            brush: red,
+                            material_props: red_props,
+                            split_brush: green,
+                            split_material_props: green_props,
+                        });
+                    }
+                }
+                _ => {
+                    let lights = self.lights();
+                    if lights.is_empty() {
+                        return false;
+                    }
+                    let (entity, properties) = lights[rng.below(lights.len())].clone();
+                    let properties = match properties {
+                        set_light_properties::LightProperties::Point(properties) => {
+                            set_light_properties::LightProperties::Point(
+                                components::PointLightProperties {
+                                    shadows_enabled: !properties.shadows_enabled,
+                                    range: rng.range(1.0, 20.0),
+                                },
+                            )
+                        }
+                        set_light_properties::LightProperties::Directional(properties) => {
+                            set_light_properties::LightProperties::Directional(
+                                components::DirectionalLightProperties {
+                                    shadows_enabled: !properties.shadows_enabled,
+                                    half_size: rng.range(1.0, 20.0),
+                                },
+                            )
+                        }
+                    };
+                    self.apply(set_light_properties::Command { entity, properties });
+                }
+            }
+            true
+        }
+    }
+
+    const NUM_EDIT_KINDS: usize = 11;
+
+    fn run_random_sequence(seed: u64, steps: usize) {
+        let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1);
+        let mut editor = TestEditor::new();
+
+        // world state after each applied edit, history[pos] is the expected current state
+        let mut history = vec![editor.objects()];
+        let mut pos: usize = 0;
+        for _ in 0..steps {
+            let kind = rng.below(NUM_EDIT_KINDS + 2);
+            if kind == NUM_EDIT_KINDS {
+                assert_eq!(editor.undo(), pos > 0);
+                pos = pos.saturating_sub(1);
+            } else if kind == NUM_EDIT_KINDS + 1 {
+                assert_eq!(editor.redo(), pos + 1 < history.len());
+                pos = (pos + 1).min(history.len() - 1);
+            } else if editor.random_edit(kind, &mut rng) {
+                history.truncate(pos + 1);
+                history.push(editor.objects());
+                pos += 1;
+            }
+            assert_eq!(editor.objects(), history[pos]);
+        }
+
+        // undo everything, the world must end up empty again
+        while editor.undo() {
+            pos -= 1;
+            assert_eq!(editor.objects(), history[pos]);
+        }
+        assert_eq!(pos, 0);
+        assert!(editor.objects().is_empty());
+
+        // and back, objects must be re-created under their old ids
+        while editor.redo() {
+            pos += 1;
+            assert_eq!(editor.objects(), history[pos]);
+        }
+        assert_eq!(pos, history.len() - 1);
+    }
+
+    #[test]
+    fn test_random_undo_redo() {
+        for seed in 1..=8 {
+            run_random_sequence(seed, 400);
+        }
+    }
+
+    #[test]
+    fn test_undo_remove_light() {
+        let mut editor = TestEditor::new();
+        editor.apply(add_directional_light::Command);
+        editor.apply(add_pointlight::Command);
+        let before = editor.objects();
+
+        for entity in editor.object_entities() {
+            editor.apply(remove_entity::Command { entity });
+        }
+        assert!(editor.objects().is_empty());
+
+        assert!(editor.undo());
+        assert!(editor.undo());
+        assert_eq!(editor.objects(), before);
+    }
+
+    #[test]
+    fn test_undo_group() {
+        let mut editor = TestEditor::new();
+        for x in [0.0, 5.0, 10.0] {
+            let mut brush = csg::Brush::default();
+            for plane in &mut brush.planes {
+                plane.w += plane.normal.dot(Vec3::X * x);
             }
-            None => info!("nothing to undo"),
+            editor.apply(add_brush::Command {
+                brush,
+                material: None,
+            });
         }
+        let before = editor.objects();
+
+        // two drag steps of all brushes, like a multi-object drag over two frames
+        let brushes = editor.brushes();
+        let drag = |offset: f32| {
+            brushes
+                .iter()
+                .map(|(entity, start_brush, _)| {
+                    let mut brush = start_brush.clone();
+                    for plane in &mut brush.planes {
+                        plane.w += plane.normal.dot(Vec3::Y * offset);
+                    }
+                    update_brush_drag::Command {
+                        entity: *entity,
+                        start_brush: start_brush.clone(),
+                        brush,
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        editor.world.resource_mut::<UndoStack>().begin_group("drag");
+        editor.apply_uncommitted(drag(1.0));
+        editor.apply_uncommitted(drag(2.0));
+        editor.world.resource_mut::<UndoStack>().end_group();
+        let after = editor.objects();
+        assert_ne!(before, after);
+
+        // the updates are merged per object into one entry
+        {
+            let undo_stack = editor.world.resource::<UndoStack>();
+            assert_eq!(undo_stack.stack.len(), 4);
+            let Some(UndoEntry::Group { cmds, .. }) = undo_stack.stack.last() else {
+                panic!("expected undo group");
+            };
+            assert_eq!(cmds.len(), 3);
+        }
+
+        assert!(editor.undo());
+        assert_eq!(editor.objects(), before);
+        assert!(editor.redo());
+        assert_eq!(editor.objects(), after);
     }
 }
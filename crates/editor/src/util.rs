@@ -1,7 +1,11 @@
-use super::components::{self, CsgOutput};
+use super::{
+    components::{self, CsgOutput},
+    resources,
+};
 use shared::render_layers;
 
 use bevy::{color::palettes::tailwind, prelude::*, render::view::RenderLayers};
+use bevy_egui::EguiContexts;
 use bevy_rapier3d::prelude::Collider;
 use csg::{self, Csg};
 use serde::{Deserialize, Serialize};
@@ -378,6 +382,21 @@ pub enum WmEvent {
     ZoomDelta(f32),
 }
 
+// once per frame, so that run conditions do not need the (mutable) egui context
+pub fn track_egui_keyboard_system(
+    mut egui_context: EguiContexts,
+    mut egui_keyboard: ResMut<resources::EguiKeyboard>,
+) {
+    egui_keyboard.0 = egui_context
+        .try_ctx_mut()
+        .is_some_and(|ctx| ctx.wants_keyboard_input());
+}
+
+// run condition for systems that only react to the keyboard
+pub fn keyboard_free(egui_keyboard: Res<resources::EguiKeyboard>) -> bool {
+    !egui_keyboard.0
+}
+
 // https://mathworld.wolfram.com/Point-LineDistance3-Dimensional.html
 pub fn ray_point_distance(ray: Ray3d, x0: Vec3) -> f32 {
     let x1 = ray.origin;
@@ -385,6 +404,21 @@ pub fn ray_point_distance(ray: Ray3d, x0: Vec3) -> f32 {
     (x0 - x1).cross(x0 - x2).length() / ray.direction.length()
 }
 
+// distance between the (infinite) line of the ray and the segment a-b, measured perpendicular to the ray. For the
+// rays of ortho views this is the distance in the view plane.
+pub fn ray_segment_distance(ray: Ray3d, a: Vec3, b: Vec3) -> f32 {
+    let direction = *ray.direction;
+    let a = (a - ray.origin).reject_from_normalized(direction);
+    let b = (b - ray.origin).reject_from_normalized(direction);
+    let ab = b - a;
+    let t = if ab.length_squared() > 0.0 {
+        (-a.dot(ab) / ab.length_squared()).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (a + ab * t).length()
+}
+
 pub trait TriangleTrait {
     fn v0(&self) -> Vec3;
     fn v1(&self) -> Vec3;
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use bevy::{ecs::system::SystemState, prelude::*};
 
 use bevy_egui::{egui::load::SizedTexture, EguiContexts};
@@ -7,14 +9,18 @@ use bevy_rapier3d::render::DebugRenderContext;
 use crate::util::WmMouseButton;
 
 use super::{
+    components,
+    edit_commands::{set_light_properties, EditCommands},
     gui_systems,
     resources::{self, WmSettings, WmSidpanelContent, WmSlot},
+    undo,
     util::{WmEvent, WmEventPointerState, WmModifiers},
 };
 
 pub fn wm_test_setup_system(
     mut egui_context: EguiContexts,
     mut wm_state: ResMut<resources::WmState>,
+    mut grid_settings: ResMut<resources::GridSettings>,
     mut image_assets: ResMut<Assets<Image>>,
 ) {
     wm_state.slot_upper2d = WmSlot::new(&mut image_assets, &mut egui_context);
@@ -25,6 +31,7 @@ pub fn wm_test_setup_system(
         // TODO: migration strategy
         wm_state.settings =
             serde_yaml::from_reader(file).expect("failed to deserialize wm settings");
+        *grid_settings = wm_state.settings.grid.clone();
     }
 }
 
@@ -38,6 +45,18 @@ pub fn wm_test_system(world: &mut World) {
         ResMut<Assets<Image>>,
         ResMut<resources::Materials>,
         ResMut<resources::MaterialBrowser>,
+        ResMut<undo::UndoStack>,
+        ResMut<resources::SceneFile>,
+        ResMut<resources::TransformTool>,
+        ResMut<resources::GridSettings>,
+        Query<
+            (
+                Entity,
+                Option<&components::PointLightProperties>,
+                Option<&components::DirectionalLightProperties>,
+            ),
+            With<components::Selected>,
+        >,
     )> = SystemState::new(world);
     let (
         mut egui_context,
@@ -47,7 +66,13 @@ pub fn wm_test_system(world: &mut World) {
         mut image_assets,
         mut materials_res,
         mut material_browser,
+        mut undo_stack,
+        mut scene_file,
+        mut transform_tool,
+        mut grid_settings,
+        selected_light_query,
     ) = system_state.get_mut(world);
+    let mut light_edit = None;
     egui::SidePanel::left("left side panel")
         .resizable(true)
         .default_width(wm_state.settings.sidepanel_separator)
@@ -92,6 +117,16 @@ pub fn wm_test_system(world: &mut World) {
                         WmSidpanelContent::Entities,
                         "Ent",
                     );
+                    ui.selectable_value(
+                        &mut wm_state.sidepanel_content,
+                        WmSidpanelContent::History,
+                        "Hist",
+                    );
+                    ui.selectable_value(
+                        &mut wm_state.sidepanel_content,
+                        WmSidpanelContent::File,
+                        "File",
+                    );
                 });
 
                 match wm_state.sidepanel_content {
@@ -129,9 +164,37 @@ pub fn wm_test_system(world: &mut World) {
                                 // ui.checkbox(&mut rapier_debug_context.always_on_top, "on top");
                             });
                         }
+                        grid_settings_ui(ui, &mut grid_settings);
+                        transform_tool_ui(ui, &mut transform_tool);
+                        if let Ok((entity, point_light, directional_light)) =
+                            selected_light_query.get_single()
+                        {
+                            light_edit =
+                                light_properties_ui(ui, entity, point_light, directional_light);
+                        }
                     }
                     WmSidpanelContent::Entities => { // meh, it is not really possible to integrate the world inspector here...
                     }
+                    WmSidpanelContent::History => {
+                        egui::ScrollArea::vertical()
+                            .id_salt("undo history")
+                            .show(ui, |ui| {
+                                if let Some(target) = undo_history_ui(ui, &undo_stack) {
+                                    undo_stack.jump_target = Some(target);
+                                }
+                            });
+                    }
+                    WmSidpanelContent::File => {
+                        let unsaved = scene_file.has_unsaved_changes(undo_stack.revision);
+                        if let Some(request) = scene_file_ui(
+                            ui,
+                            &mut scene_file,
+                            &wm_state.settings.recent_files,
+                            unsaved,
+                        ) {
+                            scene_file.request(request);
+                        }
+                    }
                 }
 
                 // ui.allocate_space(ui.available_size());
@@ -176,6 +239,9 @@ pub fn wm_test_system(world: &mut World) {
         }
         // wm_state.separator_bias += response.drag_delta().y;
     });
+    if let Some(discard) = confirm_discard_ui(egui_context.ctx_mut(), &scene_file) {
+        scene_file.resolve_discard(discard);
+    }
     wm_state.slot_main3d.check_resize(&mut image_assets);
     wm_state.slot_upper2d.check_resize(&mut image_assets);
     wm_state.slot_lower2d.check_resize(&mut image_assets);
@@ -194,6 +260,257 @@ pub fn wm_test_system(world: &mut World) {
                     })
             });
     }
+
+    if let Some((cmd, ongoing)) = light_edit {
+        let mut edit_state: SystemState<EditCommands> = SystemState::new(world);
+        let mut edit_commands = edit_state.get_mut(world);
+        if let Err(err) = edit_commands.apply(cmd) {
+            warn!("failed to set light properties: {:?}", err);
+        }
+        edit_state.apply(world);
+        if !ongoing {
+            world.resource_mut::<undo::UndoStack>().commit();
+        }
+    }
+}
+
+// scene file panel. Returns the request triggered by the user
+fn scene_file_ui(
+    ui: &mut egui::Ui,
+    scene_file: &mut resources::SceneFile,
+    recent_files: &[PathBuf],
+    unsaved: bool,
+) -> Option<resources::SceneFileRequest> {
+    let mut request = None;
+    let name = match &scene_file.path {
+        Some(path) => path.display().to_string(),
+        None => "<unnamed>".into(),
+    };
+    ui.label(format!(
+        "scene: {}{}",
+        name,
+        if unsaved { " *" } else { "" }
+    ));
+
+    ui.horizontal(|ui| {
+        ui.label("path");
+        ui.text_edit_singleline(&mut scene_file.path_edit);
+    });
+    let path = PathBuf::from(scene_file.path_edit.trim());
+    let has_path = !path.as_os_str().is_empty();
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(has_path, egui::Button::new("Open"))
+            .clicked()
+        {
+            request = Some(resources::SceneFileRequest::Open(path.clone()));
+        }
+        if ui.button("Save").clicked() {
+            request = Some(resources::SceneFileRequest::Save);
+        }
+        if ui
+            .add_enabled(has_path, egui::Button::new("Save as"))
+            .clicked()
+        {
+            request = Some(resources::SceneFileRequest::SaveAs(path.clone()));
+        }
+        if ui
+            .add_enabled(has_path, egui::Button::new("Import wsx"))
+            .clicked()
+        {
+            request = Some(resources::SceneFileRequest::Import(path.clone()));
+        }
+    });
+
+    ui.separator();
+    ui.label("recent files");
+    for recent in recent_files {
+        if ui.link(recent.display().to_string()).clicked() {
+            scene_file.path_edit = recent.display().to_string();
+            request = Some(resources::SceneFileRequest::Open(recent.clone()));
+        }
+    }
+
+    if !scene_file.quarantine.is_empty() {
+        ui.separator();
+        egui::CollapsingHeader::new(format!("quarantine ({})", scene_file.quarantine.len())).show(
+            ui,
+            |ui| {
+                ui.label("objects that could not be loaded (not part of the scene, saved back unchanged):");
+                for quarantined in &scene_file.quarantine {
+                    ui.label(format!("#{}: {}", quarantined.index, quarantined.reason));
+                    ui.monospace(&quarantined.source);
+                }
+                if ui.button("Clear").clicked() {
+                    scene_file.quarantine.clear();
+                }
+            },
+        );
+    }
+    request
+}
+
+// asks before a request discards unsaved changes. Returns the answer once the user clicked.
+fn confirm_discard_ui(ctx: &egui::Context, scene_file: &resources::SceneFile) -> Option<bool> {
+    let request = scene_file.confirm_discard()?;
+    let action = match request {
+        resources::SceneFileRequest::Open(path) => format!("Open {}", path.display()),
+        resources::SceneFileRequest::Import(path) => format!("Import {}", path.display()),
+        _ => format!("{:?}", request),
+    };
+    let mut answer = None;
+    egui::Window::new("Unsaved changes")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "{action}: unsaved changes of the current scene will be lost."
+            ));
+            ui.horizontal(|ui| {
+                if ui.button("Discard changes").clicked() {
+                    answer = Some(true);
+                }
+                if ui.button("Cancel").clicked() {
+                    answer = Some(false);
+                }
+            });
+        });
+    answer
+}
+
+fn grid_settings_ui(ui: &mut egui::Ui, grid_settings: &mut resources::GridSettings) {
+    ui.group(|ui| {
+        ui.label("grid");
+        ui.horizontal(|ui| {
+            if ui.button("[").clicked() {
+                grid_settings.finer();
+            }
+            ui.label(format!("size: {}", grid_settings.grid_size()));
+            if ui.button("]").clicked() {
+                grid_settings.coarser();
+            }
+        });
+        ui.add(
+            egui::Slider::new(&mut grid_settings.rotation_snap, 0.0..=90.0)
+                .step_by(5.0)
+                .text("rotation snap"),
+        );
+        ui.add(
+            egui::Slider::new(&mut grid_settings.coarse_rotation_snap, 0.0..=90.0)
+                .step_by(5.0)
+                .text("rotation snap (alt)"),
+        );
+        ui.checkbox(&mut grid_settings.snap_to_geometry, "snap to geometry");
+        ui.add_enabled(
+            grid_settings.snap_to_geometry,
+            egui::Slider::new(&mut grid_settings.geometry_snap_distance, 0.05..=2.0)
+                .text("snap distance"),
+        );
+    });
+}
+
+// mode of drags in the ortho views and the rotate / scale pivot
+fn transform_tool_ui(ui: &mut egui::Ui, transform_tool: &mut resources::TransformTool) {
+    ui.group(|ui| {
+        ui.label("transform (E)");
+        ui.horizontal(|ui| {
+            for (mode, label) in [
+                (components::TransformMode::Move, "move"),
+                (components::TransformMode::Rotate, "rotate"),
+                (components::TransformMode::Scale, "scale"),
+                (components::TransformMode::Vertices, "vertices"),
+            ] {
+                ui.radio_value(&mut transform_tool.mode, mode, label);
+            }
+        });
+        ui.horizontal(|ui| match transform_tool.pivot {
+            Some(pivot) => {
+                ui.label(format!(
+                    "pivot: {:.1} {:.1} {:.1}",
+                    pivot.x, pivot.y, pivot.z
+                ));
+                if ui.button("Clear").clicked() {
+                    transform_tool.pivot = None;
+                }
+            }
+            None => {
+                ui.label("pivot: selection center (ctrl-click to place)");
+            }
+        });
+    });
+}
+
+// property editor for the selected light. Returns the edit command and whether the edit is still ongoing
+// (i.e. should be merged with the following ones into a single undo step)
+fn light_properties_ui(
+    ui: &mut egui::Ui,
+    entity: Entity,
+    point_light: Option<&components::PointLightProperties>,
+    directional_light: Option<&components::DirectionalLightProperties>,
+) -> Option<(set_light_properties::Command, bool)> {
+    let (properties, changed, ongoing) = if let Some(point_light) = point_light {
+        let mut properties = point_light.clone();
+        let (changed, ongoing) = ui
+            .group(|ui| {
+                ui.label("point light");
+                let shadows = ui.checkbox(&mut properties.shadows_enabled, "shadows");
+                let range =
+                    ui.add(egui::Slider::new(&mut properties.range, 0.1..=50.0).text("range"));
+                (shadows.changed() || range.changed(), range.dragged())
+            })
+            .inner;
+        (
+            set_light_properties::LightProperties::Point(properties),
+            changed,
+            ongoing,
+        )
+    } else if let Some(directional_light) = directional_light {
+        let mut properties = directional_light.clone();
+        let (changed, ongoing) = ui
+            .group(|ui| {
+                ui.label("directional light");
+                let shadows = ui.checkbox(&mut properties.shadows_enabled, "shadows");
+                let half_size = ui.add(
+                    egui::Slider::new(&mut properties.half_size, 1.0..=100.0).text("half size"),
+                );
+                (
+                    shadows.changed() || half_size.changed(),
+                    half_size.dragged(),
+                )
+            })
+            .inner;
+        (
+            set_light_properties::LightProperties::Directional(properties),
+            changed,
+            ongoing,
+        )
+    } else {
+        return None;
+    };
+
+    changed.then_some((
+        set_light_properties::Command { entity, properties },
+        ongoing,
+    ))
+}
+
+// list of undo history entries. Entries above the current position (i.e. undone ones) are grayed out.
+// Returns the history position the user clicked on (the number of entries that should be applied).
+fn undo_history_ui(ui: &mut egui::Ui, undo_stack: &undo::UndoStack) -> Option<usize> {
+    let pos = undo_stack.jump_target.unwrap_or(undo_stack.stack.len());
+    let mut clicked = None;
+    if ui.selectable_label(pos == 0, "<initial state>").clicked() {
+        clicked = Some(0);
+    }
+    for (i, entry) in undo_stack.history().enumerate() {
+        let text = egui::RichText::new(entry.description());
+        let text = if i < pos { text } else { text.weak() };
+        if ui.selectable_label(i + 1 == pos, text).clicked() {
+            clicked = Some(i + 1);
+        }
+    }
+    clicked
 }
 
 fn show_2d_view(
@@ -253,33 +570,27 @@ fn send_wm_events_for_egui_response(
             modifiers,
         };
 
-        let drag_allowed = button == WmMouseButton::Middle
-            || button == WmMouseButton::Right
-            || response.ctx.input(|i| i.modifiers.ctrl)
-            || slot.drag_active;
-
-        if drag_allowed {
-            if response.drag_started() {
-                slot.drag_active = true;
-                event_writer.send(WmEvent::DragStart {
-                    window: name,
-                    button,
-                    pointer_state,
-                });
-            } else if response.dragged() && slot.drag_active {
-                event_writer.send(WmEvent::DragUpdate {
-                    window: name,
-                    button,
-                    pointer_state,
-                });
-            } else if response.drag_stopped() && slot.drag_active {
-                slot.drag_active = false;
-                event_writer.send(WmEvent::DragEnd {
-                    window: name,
-                    button,
-                    pointer_state,
-                });
-            }
+        // NOTE: egui only reports a drag once the pointer moved a bit, so plain left clicks still end up as Clicked
+        if response.drag_started() {
+            slot.drag_active = true;
+            event_writer.send(WmEvent::DragStart {
+                window: name,
+                button,
+                pointer_state,
+            });
+        } else if response.dragged() && slot.drag_active {
+            event_writer.send(WmEvent::DragUpdate {
+                window: name,
+                button,
+                pointer_state,
+            });
+        } else if response.drag_stopped() && slot.drag_active {
+            slot.drag_active = false;
+            event_writer.send(WmEvent::DragEnd {
+                window: name,
+                button,
+                pointer_state,
+            });
         } else if response.clicked() {
             event_writer.send(WmEvent::Clicked {
                 window: name,
@@ -298,12 +609,17 @@ fn send_wm_events_for_egui_response(
 
 pub fn write_view_settings(
     wm_state: Res<resources::WmState>,
+    grid_settings: Res<resources::GridSettings>,
     mut last_written_settings: Local<WmSettings>,
 ) {
-    if wm_state.settings != *last_written_settings {
+    let settings = WmSettings {
+        grid: grid_settings.clone(),
+        ..wm_state.settings.clone()
+    };
+    if settings != *last_written_settings {
         if let Ok(file) = std::fs::File::create("wm_settings.yaml") {
-            let _ = serde_yaml::to_writer(file, &wm_state.settings);
-            *last_written_settings = wm_state.settings;
+            let _ = serde_yaml::to_writer(file, &settings);
+            *last_written_settings = settings;
             info!("window settings written");
         }
     }
@@ -133,7 +133,9 @@ pub fn load_brushes<F: AsRef<Path>>(filename: F) -> (Vec<csg::Brush>, HashMap<i3
 
         let origin = parse_vec3(&node.Properties.origin).unwrap();
 
-        let Some(brushes) = &node.Components.Brush else {continue};
+        let Some(brushes) = &node.Components.Brush else {
+            continue;
+        };
         for brush in brushes {
             let (mut csg_brush, plane_appearances) = brush.to_csg_brush_with_offset(&origin);
 
@@ -0,0 +1,94 @@
+use std::time::{Duration, Instant};
+
+use bevy::prelude::Vec3;
+use rand::Rng;
+use sstree::{Entry, SsTree};
+
+// compares bulk loading with incremental insertion: build time and the speed of queries on the resulting trees.
+// Run with `cargo run --release --example bench_bulk_load`
+
+const M: usize = 8;
+const NUM_QUERIES: usize = 10000;
+
+type Tree = SsTree<usize, Vec3, M>;
+
+fn random_entries(n: usize) -> Vec<Entry<usize, Vec3>> {
+    // brush-like: a level of small to medium sized objects
+    let mut rng = rand::thread_rng();
+    (0..n)
+        .map(|i| {
+            let center = Vec3::new(
+                rng.gen_range(-500.0..500.0),
+                rng.gen_range(-20.0..20.0),
+                rng.gen_range(-500.0..500.0),
+            );
+            Entry::new(center, rng.gen_range(0.5..8.0), i)
+        })
+        .collect()
+}
+
+fn time<R>(f: impl FnOnce() -> R) -> (R, Duration) {
+    let start = Instant::now();
+    let res = f();
+    (res, start.elapsed())
+}
+
+fn bench_queries(name: &str, tree: &Tree, targets: &[Vec3]) {
+    let (found, radius_time) = time(|| {
+        let mut found = 0;
+        for target in targets {
+            let mut out = Vec::new();
+            tree.find_entries_within_radius(target, 10.0, &mut out);
+            found += out.len();
+        }
+        found
+    });
+    let (_, nearest_time) = time(|| {
+        for target in targets {
+            std::hint::black_box(tree.find_k_nearest(target, 8));
+        }
+    });
+    let (_, ray_time) = time(|| {
+        for target in targets {
+            std::hint::black_box(tree.find_entries_intersecting_ray(target, &Vec3::X, 100.0));
+        }
+    });
+    println!(
+        "  {name:12} height: {} fill: {:.2} radius: {:?} ({found} found) k-nearest: {:?} ray: {:?}",
+        tree.get_height(),
+        tree.get_fill_factor(),
+        radius_time,
+        nearest_time,
+        ray_time,
+    );
+}
+
+fn main() {
+    let mut rng = rand::thread_rng();
+    let targets = (0..NUM_QUERIES)
+        .map(|_| {
+            Vec3::new(
+                rng.gen_range(-500.0..500.0),
+                rng.gen_range(-20.0..20.0),
+                rng.gen_range(-500.0..500.0),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    for n in [1000, 10000, 100000] {
+        println!("{n} entries, {NUM_QUERIES} queries each:");
+
+        let (incremental, insert_time) = time(|| {
+            let mut tree = Tree::default();
+            for entry in random_entries(n) {
+                tree.insert_entry(entry);
+            }
+            tree
+        });
+        let (bulk, bulk_time) = time(|| Tree::bulk_load(random_entries(n)));
+        println!("  build: incremental {insert_time:?} bulk {bulk_time:?}");
+
+        bench_queries("incremental", &incremental, &targets);
+        bench_queries("bulk", &bulk, &targets);
+    }
+}
@@ -0,0 +1,146 @@
+use std::time::{Duration, Instant};
+
+use bevy::prelude::Vec3;
+use rand::Rng;
+use sstree::indirect_handle::{self, NodePool, NodePoolBaseline, NodePoolSlab, SsTreeI};
+
+// compares the node pools of indirect_handle::SsTree with each other and with the boxed sstree::SsTree on a
+// SpatialIndex-like workload. Run with `cargo run --release --example bench_node_pool`
+
+const M: usize = 8;
+const NUM_QUERIES: usize = 10000;
+
+fn random_bounds(n: usize) -> Vec<(Vec3, f32)> {
+    let mut rng = rand::thread_rng();
+    (0..n)
+        .map(|_| {
+            let center = Vec3::new(
+                rng.gen_range(-500.0..500.0),
+                rng.gen_range(-20.0..20.0),
+                rng.gen_range(-500.0..500.0),
+            );
+            (center, rng.gen_range(0.5..8.0))
+        })
+        .collect()
+}
+
+fn time(f: impl FnOnce()) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+struct Timings {
+    insert: Duration,
+    query: Duration,
+    nearest: Duration,
+    remove: Duration,
+}
+
+impl std::fmt::Display for Timings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "insert: {:?} radius query: {:?} k-nearest: {:?} remove: {:?}",
+            self.insert, self.query, self.nearest, self.remove
+        )
+    }
+}
+
+fn bench_indirect_handle<N: NodePool<usize, Vec3, M> + Default>(
+    entries: &[(Vec3, f32)],
+    targets: &[Vec3],
+) -> Timings {
+    let mut tree = indirect_handle::SsTree::<usize, Vec3, M, N>::new(M / 2);
+    let insert = time(|| {
+        for (i, (center, radius)) in entries.iter().enumerate() {
+            tree.insert(i, *center, *radius);
+        }
+    });
+    let query = time(|| {
+        for target in targets {
+            let mut out = Vec::new();
+            tree.find_entries_within_radius(
+                &indirect_handle::Bounds {
+                    center: *target,
+                    radius: 10.0,
+                },
+                &mut out,
+            );
+            std::hint::black_box(out);
+        }
+    });
+    let nearest = time(|| {
+        for target in targets {
+            std::hint::black_box(tree.find_k_nearest(target, 8));
+        }
+    });
+    let remove = time(|| {
+        for (i, (center, radius)) in entries.iter().enumerate() {
+            let center_radius = indirect_handle::Bounds {
+                center: *center,
+                radius: *radius,
+            };
+            tree.remove_if(&center_radius, &|p| *p == i);
+        }
+    });
+    Timings {
+        insert,
+        query,
+        nearest,
+        remove,
+    }
+}
+
+fn bench_boxed(entries: &[(Vec3, f32)], targets: &[Vec3]) -> Timings {
+    let mut tree = sstree::SsTree::<usize, Vec3, M>::default();
+    let insert = time(|| {
+        for (i, (center, radius)) in entries.iter().enumerate() {
+            tree.insert(i, *center, *radius);
+        }
+    });
+    let query = time(|| {
+        for target in targets {
+            let mut out = Vec::new();
+            tree.find_entries_within_radius(target, 10.0, &mut out);
+            std::hint::black_box(out);
+        }
+    });
+    let nearest = time(|| {
+        for target in targets {
+            std::hint::black_box(tree.find_k_nearest(target, 8));
+        }
+    });
+    let remove = time(|| {
+        for (i, (center, radius)) in entries.iter().enumerate() {
+            tree.remove_if(center, *radius, |p| *p == i);
+        }
+    });
+    Timings {
+        insert,
+        query,
+        nearest,
+        remove,
+    }
+}
+
+fn main() {
+    let targets = random_bounds(NUM_QUERIES)
+        .into_iter()
+        .map(|(center, _)| center)
+        .collect::<Vec<_>>();
+
+    for n in [1000, 10000, 100000] {
+        let entries = random_bounds(n);
+        println!("{n} entries, {NUM_QUERIES} queries:");
+        println!(
+            "  baseline pool  {}",
+            bench_indirect_handle::<NodePoolBaseline<usize, Vec3, M>>(&entries, &targets)
+        );
+        println!(
+            "  slab pool      {}",
+            bench_indirect_handle::<NodePoolSlab<usize, Vec3, M>>(&entries, &targets)
+        );
+        println!("  boxed          {}", bench_boxed(&entries, &targets));
+    }
+}
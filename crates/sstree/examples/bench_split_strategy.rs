@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant};
+
+use bevy::prelude::Vec3;
+use rand::Rng;
+use sstree::{Entry, SplitStrategy, SsTree, SsTreeConfig};
+
+// compares the split strategies with and without forced reinsertion: build time, node radius / overlap and query
+// cost, for random and spatially coherent insertion order. Run with `cargo run --release --example bench_split_strategy`
+
+const M: usize = 8;
+const NUM_QUERIES: usize = 10000;
+
+type Tree = SsTree<usize, Vec3, M>;
+
+fn random_entries(n: usize) -> Vec<Entry<usize, Vec3>> {
+    let mut rng = rand::thread_rng();
+    (0..n)
+        .map(|i| {
+            let center = Vec3::new(
+                rng.gen_range(-500.0..500.0),
+                rng.gen_range(-20.0..20.0),
+                rng.gen_range(-500.0..500.0),
+            );
+            Entry::new(center, rng.gen_range(0.5..8.0), i)
+        })
+        .collect()
+}
+
+fn time<R>(f: impl FnOnce() -> R) -> (R, Duration) {
+    let start = Instant::now();
+    let res = f();
+    (res, start.elapsed())
+}
+
+fn bench(config: SsTreeConfig, entries: &[Entry<usize, Vec3>], targets: &[Vec3]) {
+    let (tree, build_time) = time(|| {
+        let mut tree = Tree::with_config(M / 2, config);
+        for entry in entries {
+            tree.insert(entry.payload, entry.center, entry.radius);
+        }
+        tree
+    });
+    let visits = targets
+        .iter()
+        .map(|target| tree.count_node_visits(target, 10.0))
+        .sum::<usize>();
+    let (_, query_time) = time(|| {
+        for target in targets {
+            let mut out = Vec::new();
+            tree.find_entries_within_radius(target, 10.0, &mut out);
+            std::hint::black_box(out);
+        }
+    });
+    let metrics = tree.metrics();
+    println!(
+        "    {:16} reinsert: {:5} build: {build_time:?} nodes: {} radius: {:.2} overlap: {:.2} visits/query: {:.1} query: {query_time:?}",
+        format!("{:?}", config.split),
+        config.forced_reinsert,
+        metrics.num_nodes,
+        metrics.average_radius,
+        metrics.average_overlap,
+        visits as f32 / targets.len() as f32,
+    );
+}
+
+fn main() {
+    let targets = random_entries(NUM_QUERIES)
+        .into_iter()
+        .map(|entry| entry.center)
+        .collect::<Vec<_>>();
+
+    for n in [1000, 10000, 100000] {
+        let random = random_entries(n);
+        // e.g. a level loaded or edited brush by brush
+        let mut coherent = random_entries(n);
+        coherent.sort_by(|e1, e2| e1.center.x.total_cmp(&e2.center.x));
+
+        println!("{n} entries, {NUM_QUERIES} queries each:");
+        for (order, entries) in [("random", &random), ("coherent", &coherent)] {
+            println!("  {order} order:");
+            for split in [SplitStrategy::MinVariance, SplitStrategy::MinRadiusOverlap] {
+                for forced_reinsert in [false, true] {
+                    let config = SsTreeConfig {
+                        split,
+                        forced_reinsert,
+                    };
+                    bench(config, entries, &targets);
+                }
+            }
+        }
+    }
+}
@@ -330,16 +330,20 @@ fn draw_tree<const M: usize>(
     draw_points: bool,
     pool: &dyn sstree::indirect_handle::NodePool<u64, [f32; 2], M>,
 ) {
+    // nodes are only borrowed from the pool while they are drawn, so the stack holds their bounds and ids
     let mut stack = Vec::new();
-    stack.push((node, 0));
+    stack.push((
+        node.center_radius.center,
+        node.center_radius.radius,
+        node.links,
+        0,
+    ));
     let mut element_color = 0;
-    while let Some((node, level)) = stack.pop() {
+    while let Some((center, radius, links, level)) = stack.pop() {
         if level > max_level {
             continue;
         }
 
-        let Bounds { center, radius } = node.center_radius;
-
         if !overlaps(bounds, center, radius) {
             continue;
         }
@@ -357,12 +361,12 @@ fn draw_tree<const M: usize>(
             Stroke::new(1.0, COLORS[level]),
         ));
 
-        let links = pool.get(node.links);
-        match links {
+        match &*pool.get(links) {
             sstree::indirect_handle::Node::Inner(nodes) => {
                 // level_color.inc();
                 for node in nodes.iter() {
-                    stack.push((node, level + 1))
+                    let Bounds { center, radius } = node.center_radius;
+                    stack.push((center, radius, node.links, level + 1))
                 }
                 // level_color.dec();
             }
@@ -1,5 +1,12 @@
 use arrayvec::ArrayVec;
 use bevy::prelude::{Resource, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    check_fill,
+    nearest::{sphere_distance, NearestQueue},
+    sphere_contains, ValidationError,
+};
 
 pub const MAX_TREE_HEIGHT: usize = 16;
 
@@ -19,7 +26,7 @@ impl<K: Distance + Default + DimIndex + PartialEq> Center for K {}
 pub trait Radius: PartialEq {}
 impl<R: PartialEq> Radius for R {}
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Bounds<K: Center> {
     pub center: K,
     pub radius: f32,
@@ -41,21 +48,24 @@ impl<K: Center> Bounds<K> {
     fn intersects(&self, target: &Self) -> bool {
         self.center.distance(&target.center) < (self.radius + target.radius)
     }
+    fn sphere_distance(&self, target: &K) -> f32 {
+        sphere_distance(self.center.distance(target), self.radius)
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct InnerLink<P, K: Center, const M: usize> {
     pub center_radius: Bounds<K>,
     pub links: Box<Node<P, K, M>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct LeafLink<P, K: Center> {
     pub center_radius: Bounds<K>,
     pub payload: P,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum Node<P, K: Center, const M: usize> {
     Inner(ArrayVec<InnerLink<P, K, M>, M>),
     Leaf(ArrayVec<LeafLink<P, K>, M>),
@@ -181,6 +191,7 @@ impl<P, K: Center, const M: usize> InnerLink<P, K, M> {
                     if children.len() < M - 1 {
                         children.push(new_child_1);
                         children.push(new_child_2);
+                        self.update_bounding_envelope();
                     } else {
                         // TODO: use ArrayVec<_, M+1> when generic_const_exprs are suppported
                         let mut nodes_to_split: Vec<_> = children
@@ -281,6 +292,38 @@ impl<P, K: Center, const M: usize> InnerLink<P, K, M> {
         }
     }
 
+    // structural checks of the subtree, see SsTree::validate
+    pub fn validate(&self, depth: usize, height: usize, m: usize) -> Result<(), ValidationError> {
+        let contains = |child: &Bounds<K>| {
+            sphere_contains(
+                self.center_radius.radius,
+                self.center_radius.distance(child),
+                child.radius,
+            )
+        };
+        match self.links.as_ref() {
+            Node::Leaf(entries) => {
+                check_fill(entries.len(), depth, m, true)?;
+                if depth != height {
+                    return Err(ValidationError::UnbalancedLeaf { depth, height });
+                }
+                if !entries.iter().all(|e| contains(&e.center_radius)) {
+                    return Err(ValidationError::NotContained { depth: depth + 1 });
+                }
+            }
+            Node::Inner(nodes) => {
+                check_fill(nodes.len(), depth, m, false)?;
+                for node in nodes.iter() {
+                    if !contains(&node.center_radius) {
+                        return Err(ValidationError::NotContained { depth: depth + 1 });
+                    }
+                    node.validate(depth + 1, height, m)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn count_nodes(&self) -> (usize, usize) {
         match self.links.as_ref() {
             Node::Inner(nodes) => nodes.iter().fold((0, 1), |(a_points, a_nodes), n| {
@@ -579,7 +622,7 @@ impl<P, K: Center, const M: usize> InnerLink<P, K, M> {
     //   return points
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SsTree<P, K: Center, const M: usize> {
     pub root: InnerLink<P, K, M>,
     height: usize,
@@ -639,6 +682,14 @@ impl<P, K: Center, const M: usize> SsTree<P, K, M> {
         num_points as f32 / num_nodes as f32
     }
 
+    // see sstree::SsTree::validate
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.height > MAX_TREE_HEIGHT {
+            return Err(ValidationError::TooHigh(self.height));
+        }
+        self.root.validate(1, self.height, self.m)
+    }
+
     pub fn find_entries_within_radius<'a>(
         &'a self,
         center_radius: &Bounds<K>,
@@ -654,6 +705,38 @@ impl<P, K: Center, const M: usize> SsTree<P, K, M> {
     ) -> Option<&'a LeafLink<P, K>> {
         self.root.find_if(center_radius, &f)
     }
+
+    // the k entries closest to `target` with their distances, nearest first. Distances are measured to the bounding
+    // sphere of the entries.
+    pub fn find_k_nearest(&self, target: &K, k: usize) -> Vec<(f32, &LeafLink<P, K>)> {
+        self.find_k_nearest_within(target, k, f32::INFINITY)
+    }
+
+    // like find_k_nearest, but only entries up to `max_distance` away
+    pub fn find_k_nearest_within(
+        &self,
+        target: &K,
+        k: usize,
+        max_distance: f32,
+    ) -> Vec<(f32, &LeafLink<P, K>)> {
+        let mut queue = NearestQueue::new(k, max_distance);
+        queue.push_node(self.root.center_radius.sphere_distance(target), &self.root);
+        while let Some(node) = queue.next_node() {
+            match node.links.as_ref() {
+                Node::Inner(nodes) => {
+                    for child in nodes.iter() {
+                        queue.push_node(child.center_radius.sphere_distance(target), child);
+                    }
+                }
+                Node::Leaf(entries) => {
+                    for entry in entries.iter() {
+                        queue.push_entry(entry.center_radius.sphere_distance(target), entry);
+                    }
+                }
+            }
+        }
+        queue.into_nearest()
+    }
     pub fn remove_if<F: Fn(&P) -> bool>(
         &mut self,
         center_radius: &Bounds<K>,
@@ -813,6 +896,7 @@ fn test_bevy_vec3() {
 mod test {
     use crate::indirect::Bounds;
 
+    use super::Distance;
     use super::LeafLink;
     use super::SsTree;
 
@@ -942,6 +1026,73 @@ mod test {
         );
         assert!(out.is_empty());
     }
+
+    #[test]
+    fn test_find_k_nearest() {
+        let mut tree = SsTree::<usize, [f32; 2], 8>::new(4);
+        let mut points = Vec::new();
+        for i in 0..400 {
+            // scrambled, so that the tree does not degenerate from sorted input
+            let (x, y) = ((i * 7) % 20, (i * 13) % 23);
+            let center = [x as f32 * 1.5, y as f32 * 0.5];
+            let radius = (i % 3) as f32 * 0.1;
+            points.push((center, radius));
+            tree.insert(i, center, radius);
+        }
+
+        let target = [10.2, 4.7];
+        let nearest = tree.find_k_nearest(&target, 10);
+        let mut expected = points
+            .iter()
+            .map(|(center, radius)| (center.distance(&target) - radius).max(0.0))
+            .collect::<Vec<_>>();
+        expected.sort_by(f32::total_cmp);
+        let distances = nearest.iter().map(|(d, _)| *d).collect::<Vec<_>>();
+        assert_eq!(distances, expected[..10]);
+        for (distance, entry) in nearest {
+            let (center, radius) = points[entry.payload];
+            assert_eq!(distance, (center.distance(&target) - radius).max(0.0));
+        }
+
+        let within = tree.find_k_nearest_within(&target, 100, 1.0);
+        assert_eq!(within.len(), expected.iter().filter(|d| **d <= 1.0).count());
+        assert!(tree.find_k_nearest(&target, 0).is_empty());
+        assert!(SsTree::<(), [f32; 2], 8>::new(4)
+            .find_k_nearest(&target, 1)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_stress() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        // random inserts and removals, then a round trip through ron
+        let mut rng = StdRng::seed_from_u64(47);
+        let mut tree = SsTree::<usize, [f32; 2], 8>::new(4);
+        let mut entries = Vec::new();
+        for round in 0..30 {
+            for i in 0..rng.gen_range(0..150) {
+                let center = [rng.gen_range(-50.0..50.0), rng.gen_range(-50.0..50.0)];
+                let radius = rng.gen_range(0.1..3.0);
+                tree.insert(round * 1000 + i, center, radius);
+                entries.push((round * 1000 + i, center, radius));
+            }
+            for _ in 0..rng.gen_range(0..120).min(entries.len()) {
+                let (payload, center, radius) =
+                    entries.swap_remove(rng.gen_range(0..entries.len()));
+                let center_radius = Bounds { center, radius };
+                assert!(tree.remove_if(&center_radius, |p| *p == payload).is_some());
+            }
+            assert_eq!(tree.validate(), Ok(()));
+            assert_eq!(tree.root.count_nodes().0, entries.len());
+        }
+
+        let restored: SsTree<usize, [f32; 2], 8> =
+            ron::from_str(&ron::to_string(&tree).unwrap()).unwrap();
+        assert_eq!(restored.validate(), Ok(()));
+        assert_eq!(restored.get_height(), tree.get_height());
+        assert_eq!(restored.root.count_nodes(), tree.root.count_nodes());
+    }
 }
 
 use bevy::prelude::*;
@@ -980,4 +1131,25 @@ impl SpatialIndex {
         self.sstree.find_entries_within_radius(&bounds, &mut out);
         out.into_iter().map(|e| e.payload)
     }
+
+    // the k entities closest to `point` with their distances, nearest first
+    pub fn find_k_nearest(
+        &self,
+        point: Vec3,
+        k: usize,
+    ) -> impl Iterator<Item = (Entity, f32)> + '_ {
+        self.find_k_nearest_within(point, k, f32::INFINITY)
+    }
+
+    pub fn find_k_nearest_within(
+        &self,
+        point: Vec3,
+        k: usize,
+        max_distance: f32,
+    ) -> impl Iterator<Item = (Entity, f32)> + '_ {
+        self.sstree
+            .find_k_nearest_within(&point, k, max_distance)
+            .into_iter()
+            .map(|(distance, e)| (e.payload, distance))
+    }
 }
@@ -1,8 +1,23 @@
+#[cfg(feature = "sled")]
+use std::cell::{Cell, RefCell};
+#[cfg(feature = "sled")]
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::ops::Deref;
+use std::rc::Rc;
 
 use arrayvec::ArrayVec;
 use bevy::prelude::{Resource, Vec3};
+#[cfg(feature = "sled")]
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    check_fill,
+    nearest::{sphere_distance, NearestQueue},
+    sphere_contains, ValidationError,
+};
 
 pub const MAX_TREE_HEIGHT: usize = 16;
 
@@ -22,7 +37,7 @@ impl<K: Distance + Default + DimIndex + PartialEq> Center for K {}
 pub trait Radius: PartialEq {}
 impl<R: PartialEq> Radius for R {}
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Bounds<K: Center> {
     pub center: K,
     pub radius: f32,
@@ -44,9 +59,12 @@ impl<K: Center> Bounds<K> {
     fn intersects(&self, target: &Self) -> bool {
         self.center.distance(&target.center) < (self.radius + target.radius)
     }
+    fn sphere_distance(&self, target: &K) -> f32 {
+        sphere_distance(self.center.distance(target), self.radius)
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct InnerLink<P, K: Center, const M: usize> {
     pub center_radius: Bounds<K>,
     // pub links: Box<Node<P, K, M>>,
@@ -54,26 +72,78 @@ pub struct InnerLink<P, K: Center, const M: usize> {
     pub links: u64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct LeafLink<P, K: Center> {
     pub center_radius: Bounds<K>,
     pub payload: P,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum Node<P, K: Center, const M: usize> {
     Inner(ArrayVec<InnerLink<P, K, M>, M>),
     Leaf(ArrayVec<LeafLink<P, K>, M>),
 }
 
+// node returned by NodePool::get. Pools that keep all nodes in memory lend them, pools that can evict nodes share
+// them with the caller, so the node stays valid after it left the pool.
+pub enum NodeRef<'a, P, K: Center, const M: usize> {
+    Borrowed(&'a Node<P, K, M>),
+    Shared(Rc<Node<P, K, M>>),
+}
+
+impl<P, K: Center, const M: usize> Deref for NodeRef<'_, P, K, M> {
+    type Target = Node<P, K, M>;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            NodeRef::Borrowed(node) => node,
+            NodeRef::Shared(node) => node,
+        }
+    }
+}
+
+impl<P, K: Center, const M: usize> Clone for NodeRef<'_, P, K, M> {
+    fn clone(&self) -> Self {
+        match self {
+            NodeRef::Borrowed(node) => NodeRef::Borrowed(node),
+            NodeRef::Shared(node) => NodeRef::Shared(node.clone()),
+        }
+    }
+}
+
+// entry of a leaf as returned by the queries, keeps the leaf alive
+pub struct EntryRef<'a, P, K: Center, const M: usize> {
+    leaf: NodeRef<'a, P, K, M>,
+    index: usize,
+}
+
+impl<P, K: Center, const M: usize> Deref for EntryRef<'_, P, K, M> {
+    type Target = LeafLink<P, K>;
+
+    fn deref(&self) -> &Self::Target {
+        match &*self.leaf {
+            Node::Leaf(entries) => &entries[self.index],
+            Node::Inner(_) => unreachable!("entry of an inner node"),
+        }
+    }
+}
+
+impl<P: std::fmt::Debug, K: Center + std::fmt::Debug, const M: usize> std::fmt::Debug
+    for EntryRef<'_, P, K, M>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
 pub trait NodePool<P, K: Center, const M: usize> {
     fn alloc(&mut self) -> u64;
-    fn get(&self, id: u64) -> &Node<P, K, M>;
+    fn get(&self, id: u64) -> NodeRef<'_, P, K, M>;
     fn remove(&mut self, id: u64) -> Node<P, K, M>;
     fn put(&mut self, id: u64, n: Node<P, K, M>);
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct NodePoolBaseline<P, K: Center, const M: usize> {
     nodes: HashMap<u64, Node<P, K, M>>,
     next_id: u64,
@@ -101,9 +171,9 @@ impl<P, K: Center, const M: usize> NodePool<P, K, M> for NodePoolBaseline<P, K,
         // println!("alloc: {}", ret);
         ret
     }
-    fn get(&self, id: u64) -> &Node<P, K, M> {
+    fn get(&self, id: u64) -> NodeRef<'_, P, K, M> {
         // println!("get: {}", id);
-        self.nodes.get(&id).expect("unknown node id")
+        NodeRef::Borrowed(self.nodes.get(&id).expect("unknown node id"))
     }
     fn remove(&mut self, id: u64) -> Node<P, K, M> {
         // println!("remove: {}", id);
@@ -115,6 +185,356 @@ impl<P, K: Center, const M: usize> NodePool<P, K, M> for NodePoolBaseline<P, K,
     }
 }
 
+// nodes in a contiguous Vec, freed slots are reused (most recently freed first, so the node that replaces a removed
+// one usually ends up in the same slot). Ids contain the slot index in the lower and a generation counter in the upper
+// 32 bits, so stale ids are detected.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NodePoolSlab<P, K: Center, const M: usize> {
+    slots: Vec<SlabSlot<P, K, M>>,
+    free: Vec<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SlabSlot<P, K: Center, const M: usize> {
+    generation: u32,
+    node: Option<Node<P, K, M>>,
+}
+
+impl<P, K: Center, const M: usize> Default for NodePoolSlab<P, K, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P, K: Center, const M: usize> NodePoolSlab<P, K, M> {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn slot(&self, id: u64) -> &SlabSlot<P, K, M> {
+        let slot = self.slots.get(id as u32 as usize).expect("unknown node id");
+        assert_eq!(slot.generation, (id >> 32) as u32, "stale node id");
+        slot
+    }
+
+    fn slot_mut(&mut self, id: u64) -> &mut SlabSlot<P, K, M> {
+        let slot = self
+            .slots
+            .get_mut(id as u32 as usize)
+            .expect("unknown node id");
+        assert_eq!(slot.generation, (id >> 32) as u32, "stale node id");
+        slot
+    }
+}
+
+impl<P, K: Center, const M: usize> NodePool<P, K, M> for NodePoolSlab<P, K, M> {
+    fn alloc(&mut self) -> u64 {
+        let index = self.free.pop().unwrap_or_else(|| {
+            self.slots.push(SlabSlot {
+                generation: 0,
+                node: None,
+            });
+            (self.slots.len() - 1) as u32
+        });
+        ((self.slots[index as usize].generation as u64) << 32) | index as u64
+    }
+    fn get(&self, id: u64) -> NodeRef<'_, P, K, M> {
+        NodeRef::Borrowed(self.slot(id).node.as_ref().expect("node id not in use"))
+    }
+    fn remove(&mut self, id: u64) -> Node<P, K, M> {
+        let slot = self.slot_mut(id);
+        let node = slot.node.take().expect("node id not in use");
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id as u32);
+        node
+    }
+    fn put(&mut self, id: u64, n: Node<P, K, M>) {
+        self.slot_mut(id).node = Some(n);
+    }
+}
+
+#[cfg(feature = "sled")]
+#[derive(thiserror::Error, Debug)]
+pub enum NodePoolSledError {
+    #[error("db error: {0}")]
+    Db(#[from] sled::Error),
+    #[error("failed to serialize node: {0}")]
+    Serialize(#[from] flexbuffers::SerializationError),
+    #[error("failed to deserialize node: {0}")]
+    Deserialize(#[from] flexbuffers::DeserializationError),
+    #[error("node {0} is missing from the db")]
+    MissingNode(u64),
+    #[error(
+        "a node could not be loaded earlier, the tree in memory is incomplete and cannot be stored"
+    )]
+    Poisoned,
+}
+
+// nodes serialized into a sled tree, so that the index of a large world can persist between runs and be queried
+// without loading it completely. Nodes are loaded on demand into a cache of `cache_size` nodes, the least recently
+// used ones are written back and evicted when nodes are loaded or put. Removed nodes stay in the db until the next
+// flush, which stores the root in the same batch, so the db always contains the tree as of the last flush. Nodes
+// written back between flushes are garbage if the tree is not flushed.
+//
+// The NodePool interface cannot fail, so I/O errors are kept and returned by the next flush. Nodes that fail to be
+// written back stay in the cache. A node that fails to load is replaced by an empty leaf and poisons the pool: the
+// tree in memory is incomplete from then on and flush refuses to store it.
+#[cfg(feature = "sled")]
+pub struct NodePoolSled<P, K: Center, const M: usize> {
+    nodes: sled::Tree,
+    cache: RefCell<SledCache<P, K, M>>,
+    error: RefCell<Option<NodePoolSledError>>,
+    poisoned: Cell<bool>,
+    removed: Vec<u64>,
+    next_id: u64,
+    cache_size: usize,
+}
+
+#[cfg(feature = "sled")]
+struct CachedNode<P, K: Center, const M: usize> {
+    node: Rc<Node<P, K, M>>,
+    dirty: bool,
+    last_used: u64,
+}
+
+#[cfg(feature = "sled")]
+struct SledCache<P, K: Center, const M: usize> {
+    nodes: HashMap<u64, CachedNode<P, K, M>>,
+    // node ids by the time of their last use, the first one is evicted next
+    lru: BTreeMap<u64, u64>,
+    clock: u64,
+}
+
+#[cfg(feature = "sled")]
+impl<P, K: Center, const M: usize> Default for SledCache<P, K, M> {
+    fn default() -> Self {
+        Self {
+            nodes: HashMap::new(),
+            lru: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+}
+
+#[cfg(feature = "sled")]
+impl<P, K, const M: usize> SledCache<P, K, M>
+where
+    P: Serialize,
+    K: Center + Serialize,
+{
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn get(&mut self, id: u64) -> Option<Rc<Node<P, K, M>>> {
+        let now = self.tick();
+        let cached = self.nodes.get_mut(&id)?;
+        self.lru.remove(&cached.last_used);
+        self.lru.insert(now, id);
+        cached.last_used = now;
+        Some(cached.node.clone())
+    }
+
+    fn insert(&mut self, id: u64, node: Rc<Node<P, K, M>>, dirty: bool) {
+        let last_used = self.tick();
+        self.restore(
+            id,
+            CachedNode {
+                node,
+                dirty,
+                last_used,
+            },
+        );
+    }
+
+    // put back a node taken out of the cache, at its old position in the lru order
+    fn restore(&mut self, id: u64, cached: CachedNode<P, K, M>) {
+        self.lru.insert(cached.last_used, id);
+        if let Some(old) = self.nodes.insert(id, cached) {
+            self.lru.remove(&old.last_used);
+        }
+    }
+
+    fn take(&mut self, id: u64) -> Option<CachedNode<P, K, M>> {
+        let cached = self.nodes.remove(&id)?;
+        self.lru.remove(&cached.last_used);
+        Some(cached)
+    }
+
+    // write back and drop the least recently used nodes until at most `cache_size` are left
+    fn evict(&mut self, db: &sled::Tree, cache_size: usize) -> Result<(), NodePoolSledError> {
+        while self.nodes.len() > cache_size {
+            let Some((_, id)) = self.lru.pop_first() else {
+                break;
+            };
+            let cached = self.nodes.remove(&id).expect("lru and cache out of sync");
+            if cached.dirty {
+                let written = flexbuffers::to_vec(&*cached.node)
+                    .map_err(NodePoolSledError::from)
+                    .and_then(|buf| Ok(db.insert(id.to_be_bytes(), buf)?));
+                if let Err(error) = written {
+                    self.restore(id, cached);
+                    return Err(error);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sled")]
+const SLED_ROOT_KEY: &[u8] = b"root";
+#[cfg(feature = "sled")]
+const SLED_NEXT_ID_KEY: &[u8] = b"next_id";
+
+// root and height of the stored tree
+#[cfg(feature = "sled")]
+type StoredRoot<P, K, const M: usize> = (InnerLink<P, K, M>, usize);
+
+#[cfg(feature = "sled")]
+impl<P, K, const M: usize> NodePoolSled<P, K, M>
+where
+    P: Serialize + DeserializeOwned,
+    K: Center + Serialize + DeserializeOwned,
+{
+    pub fn open(db: sled::Db, cache_size: usize) -> Result<Self, NodePoolSledError> {
+        let nodes = db.open_tree("sstree_nodes")?;
+        // ids are only handed out once up to the last flush, later ones belong to garbage and may be reused
+        let next_id = match nodes.get(SLED_NEXT_ID_KEY)? {
+            Some(buf) => flexbuffers::from_slice(&buf)?,
+            None => 0,
+        };
+        Ok(Self {
+            nodes,
+            cache: RefCell::default(),
+            error: RefCell::default(),
+            poisoned: Cell::new(false),
+            removed: Vec::new(),
+            next_id,
+            cache_size,
+        })
+    }
+
+    // nodes in the cache
+    pub fn cached(&self) -> usize {
+        self.cache.borrow().nodes.len()
+    }
+
+    // write back dirty nodes, delete removed ones and store the root. Returns the first error since the last flush.
+    fn flush(&mut self, root: &InnerLink<P, K, M>, height: usize) -> Result<(), NodePoolSledError> {
+        if let Some(error) = self.error.get_mut().take() {
+            return Err(error);
+        }
+        if self.poisoned.get() {
+            return Err(NodePoolSledError::Poisoned);
+        }
+        let cache = self.cache.get_mut();
+        let mut batch = sled::Batch::default();
+        let mut written = Vec::new();
+        for (id, cached) in cache.nodes.iter().filter(|(_, c)| c.dirty) {
+            batch.insert(&id.to_be_bytes(), flexbuffers::to_vec(&*cached.node)?);
+            written.push(*id);
+        }
+        for id in &self.removed {
+            batch.remove(&id.to_be_bytes());
+        }
+        batch.insert(SLED_ROOT_KEY, flexbuffers::to_vec((root, height))?);
+        batch.insert(SLED_NEXT_ID_KEY, flexbuffers::to_vec(self.next_id)?);
+        self.nodes.apply_batch(batch)?;
+        self.nodes.flush()?;
+        // only now, a failed flush can be repeated
+        for id in written {
+            if let Some(cached) = cache.nodes.get_mut(&id) {
+                cached.dirty = false;
+            }
+        }
+        self.removed.clear();
+        Ok(())
+    }
+
+    fn load_root(&self) -> Result<Option<StoredRoot<P, K, M>>, NodePoolSledError> {
+        let Some(buf) = self.nodes.get(SLED_ROOT_KEY)? else {
+            return Ok(None);
+        };
+        Ok(Some(flexbuffers::from_slice(&buf)?))
+    }
+
+    fn load(&self, id: u64) -> Node<P, K, M> {
+        let loaded = self
+            .nodes
+            .get(id.to_be_bytes())
+            .map_err(NodePoolSledError::from)
+            .and_then(|buf| {
+                let buf = buf.ok_or(NodePoolSledError::MissingNode(id))?;
+                Ok(flexbuffers::from_slice(&buf)?)
+            });
+        loaded.unwrap_or_else(|error| {
+            self.record_error(error);
+            self.poisoned.set(true);
+            Node::Leaf(ArrayVec::new())
+        })
+    }
+
+    // the first error is kept for flush
+    fn record_error(&self, error: NodePoolSledError) {
+        self.error.borrow_mut().get_or_insert(error);
+    }
+}
+
+#[cfg(feature = "sled")]
+impl<P, K, const M: usize> NodePool<P, K, M> for NodePoolSled<P, K, M>
+where
+    P: Serialize + DeserializeOwned,
+    K: Center + Serialize + DeserializeOwned,
+{
+    fn alloc(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+    fn get(&self, id: u64) -> NodeRef<'_, P, K, M> {
+        let mut cache = self.cache.borrow_mut();
+        if let Some(node) = cache.get(id) {
+            return NodeRef::Shared(node);
+        }
+        let node = Rc::new(self.load(id));
+        cache.insert(id, node.clone(), false);
+        if let Err(error) = cache.evict(&self.nodes, self.cache_size) {
+            self.record_error(error);
+        }
+        NodeRef::Shared(node)
+    }
+    fn remove(&mut self, id: u64) -> Node<P, K, M> {
+        let node = match self.cache.get_mut().take(id) {
+            // NodeRefs borrow the pool, so there are no other references left
+            Some(cached) => Rc::try_unwrap(cached.node)
+                .unwrap_or_else(|_| unreachable!("removed node is still referenced")),
+            None => self.load(id),
+        };
+        self.removed.push(id);
+        node
+    }
+    fn put(&mut self, id: u64, n: Node<P, K, M>) {
+        let cache = self.cache.get_mut();
+        cache.insert(id, Rc::new(n), true);
+        if let Err(error) = cache.evict(&self.nodes, self.cache_size) {
+            self.error.get_mut().get_or_insert(error);
+        }
+    }
+}
+
 impl<P, K: Center> AsRef<Bounds<K>> for LeafLink<P, K> {
     fn as_ref(&self) -> &Bounds<K> {
         &self.center_radius
@@ -171,28 +591,28 @@ impl<P, K: Center, const M: usize> InnerLink<P, K, M> {
         self.center_radius.intersects_point(target)
     }
 
-    pub fn search<'a>(&'a self, target: &K, pool: &'a dyn NodePool<P, K, M>) -> Option<&Self> {
-        let links = pool.get(self.links);
-        match links {
-            Node::Inner(children) => children.iter().find(|node| node.intersects_point(target)),
-            Node::Leaf(points) => {
-                if points.iter().any(|x| x.intersects_point(target)) {
-                    Some(self)
-                } else {
-                    None
-                }
-            }
+    // node id of the child containing `target`, or of this node if it is a leaf with an entry containing it
+    pub fn search(&self, target: &K, pool: &dyn NodePool<P, K, M>) -> Option<u64> {
+        match &*pool.get(self.links) {
+            Node::Inner(children) => children
+                .iter()
+                .find(|node| node.intersects_point(target))
+                .map(|node| node.links),
+            Node::Leaf(points) => points
+                .iter()
+                .any(|x| x.intersects_point(target))
+                .then_some(self.links),
         }
     }
 
-    pub fn search_parent_leaf<'a>(&'a self, target: &K, pool: &'a dyn NodePool<P, K, M>) -> &Self {
-        let links = pool.get(self.links);
-        match links {
+    // node id of the leaf an entry at `target` would be inserted into
+    pub fn search_parent_leaf(&self, target: &K, pool: &dyn NodePool<P, K, M>) -> u64 {
+        match &*pool.get(self.links) {
             Node::Inner(children) => {
                 let child = Self::find_closest_child(children, target);
                 child.search_parent_leaf(target, pool)
             }
-            Node::Leaf(_) => self,
+            Node::Leaf(_) => self.links,
         }
     }
 
@@ -402,9 +822,46 @@ impl<P, K: Center, const M: usize> InnerLink<P, K, M> {
         }
     }
 
+    // structural checks of the subtree, see SsTree::validate
+    pub fn validate(
+        &self,
+        depth: usize,
+        height: usize,
+        m: usize,
+        pool: &dyn NodePool<P, K, M>,
+    ) -> Result<(), ValidationError> {
+        let contains = |child: &Bounds<K>| {
+            sphere_contains(
+                self.center_radius.radius,
+                self.center_radius.distance(child),
+                child.radius,
+            )
+        };
+        match &*pool.get(self.links) {
+            Node::Leaf(entries) => {
+                check_fill(entries.len(), depth, m, true)?;
+                if depth != height {
+                    return Err(ValidationError::UnbalancedLeaf { depth, height });
+                }
+                if !entries.iter().all(|e| contains(&e.center_radius)) {
+                    return Err(ValidationError::NotContained { depth: depth + 1 });
+                }
+            }
+            Node::Inner(nodes) => {
+                check_fill(nodes.len(), depth, m, false)?;
+                for node in nodes.iter() {
+                    if !contains(&node.center_radius) {
+                        return Err(ValidationError::NotContained { depth: depth + 1 });
+                    }
+                    node.validate(depth + 1, height, m, pool)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn count_nodes(&self, pool: &dyn NodePool<P, K, M>) -> (usize, usize) {
-        let links = pool.get(self.links);
-        match links {
+        match &*pool.get(self.links) {
             Node::Inner(nodes) => nodes.iter().fold((0, 1), |(a_points, a_nodes), n| {
                 let (points, nodes) = n.count_nodes(pool);
                 (a_points + points, a_nodes + nodes)
@@ -413,19 +870,22 @@ impl<P, K: Center, const M: usize> InnerLink<P, K, M> {
         }
     }
     pub fn find_entries_within_radius<'a>(
-        &'a self,
+        &self,
         // center: &K,
         // radius: f32,
         center_radius: &Bounds<K>,
-        out: &mut Vec<&'a LeafLink<P, K>>,
+        out: &mut Vec<EntryRef<'a, P, K, M>>,
         pool: &'a dyn NodePool<P, K, M>,
     ) {
         let links = pool.get(self.links);
-        match links {
+        match &*links {
             Node::Leaf(points) => {
-                for point in points.iter() {
+                for (index, point) in points.iter().enumerate() {
                     if point.center_radius.intersects(center_radius) {
-                        out.push(point);
+                        out.push(EntryRef {
+                            leaf: links.clone(),
+                            index,
+                        });
                     }
                 }
             }
@@ -440,17 +900,20 @@ impl<P, K: Center, const M: usize> InnerLink<P, K, M> {
     }
 
     pub fn find_if<'a, F: Fn(&P) -> bool>(
-        &'a self,
+        &self,
         center_radius: &Bounds<K>,
         f: &F,
         pool: &'a dyn NodePool<P, K, M>,
-    ) -> Option<&LeafLink<P, K>> {
+    ) -> Option<EntryRef<'a, P, K, M>> {
         let links = pool.get(self.links);
-        match links {
+        match &*links {
             Node::Leaf(points) => {
-                for point in points.iter() {
+                for (index, point) in points.iter().enumerate() {
                     if point.center_radius.intersects(center_radius) && f(&point.payload) {
-                        return Some(point);
+                        return Some(EntryRef {
+                            leaf: links.clone(),
+                            index,
+                        });
                     }
                 }
             }
@@ -595,12 +1058,11 @@ impl<P, K: Center, const M: usize> InnerLink<P, K, M> {
         m: usize,
         pool: &dyn NodePool<P, K, M>,
     ) -> Option<usize> {
-        let siblings_to_borrow_from = nodes.iter().enumerate().filter(|(i, sibling)| {
-            let links = pool.get(sibling.links);
-            match links {
-                Node::Inner(nodes) => *i != node_to_fix && nodes.len() > m,
-                Node::Leaf(points) => *i != node_to_fix && points.len() > m,
-            }
+        let siblings_to_borrow_from = nodes.iter().enumerate().filter(|(i, sibling)| match &*pool
+            .get(sibling.links)
+        {
+            Node::Inner(nodes) => *i != node_to_fix && nodes.len() > m,
+            Node::Leaf(points) => *i != node_to_fix && points.len() > m,
         });
 
         let mut closest_sibling = None;
@@ -697,13 +1159,14 @@ impl<P, K: Center, const M: usize> InnerLink<P, K, M> {
         m: usize,
         pool: &dyn NodePool<P, K, M>,
     ) -> Option<usize> {
-        let siblings_to_merge_to = nodes.iter().enumerate().filter(|(i, sibling)| {
-            let links = pool.get(sibling.links);
-            match links {
-                Node::Inner(nodes) => *i != node_to_fix && nodes.len() == m,
-                Node::Leaf(points) => *i != node_to_fix && points.len() == m,
-            }
-        });
+        let siblings_to_merge_to =
+            nodes
+                .iter()
+                .enumerate()
+                .filter(|(i, sibling)| match &*pool.get(sibling.links) {
+                    Node::Inner(nodes) => *i != node_to_fix && nodes.len() == m,
+                    Node::Leaf(points) => *i != node_to_fix && points.len() == m,
+                });
 
         let mut closest_sibling = None;
         let mut closest_sibling_dist = f32::INFINITY;
@@ -864,11 +1327,14 @@ pub trait SsTreeI<P, K: Center, const M: usize> {
     fn find_entries_within_radius<'a>(
         &'a self,
         center_radius: &Bounds<K>,
-        out: &mut Vec<&'a LeafLink<P, K>>,
+        out: &mut Vec<EntryRef<'a, P, K, M>>,
     );
 
-    fn find_if(&self, center_radius: &Bounds<K>, f: &dyn Fn(&P) -> bool)
-        -> Option<&LeafLink<P, K>>;
+    fn find_if(
+        &self,
+        center_radius: &Bounds<K>,
+        f: &dyn Fn(&P) -> bool,
+    ) -> Option<EntryRef<'_, P, K, M>>;
     fn remove_if(
         &mut self,
         center_radius: &Bounds<K>,
@@ -877,9 +1343,49 @@ pub trait SsTreeI<P, K: Center, const M: usize> {
 
     fn get_root(&self) -> &InnerLink<P, K, M>;
     fn get_pool(&self) -> &dyn NodePool<P, K, M>;
+
+    // the k entries closest to `target` with their distances, nearest first. Distances are measured to the bounding
+    // sphere of the entries.
+    fn find_k_nearest(&self, target: &K, k: usize) -> Vec<(f32, EntryRef<'_, P, K, M>)> {
+        self.find_k_nearest_within(target, k, f32::INFINITY)
+    }
+
+    // like find_k_nearest, but only entries up to `max_distance` away
+    fn find_k_nearest_within(
+        &self,
+        target: &K,
+        k: usize,
+        max_distance: f32,
+    ) -> Vec<(f32, EntryRef<'_, P, K, M>)> {
+        let pool = self.get_pool();
+        let root = self.get_root();
+        // nodes are queued by id, so that only the nodes on the way to the results are fetched from the pool
+        let mut queue = NearestQueue::new(k, max_distance);
+        queue.push_node(root.center_radius.sphere_distance(target), root.links);
+        while let Some(id) = queue.next_node() {
+            let node = pool.get(id);
+            match &*node {
+                Node::Inner(nodes) => {
+                    for child in nodes.iter() {
+                        queue.push_node(child.center_radius.sphere_distance(target), child.links);
+                    }
+                }
+                Node::Leaf(entries) => {
+                    for (index, entry) in entries.iter().enumerate() {
+                        let entry_ref = EntryRef {
+                            leaf: node.clone(),
+                            index,
+                        };
+                        queue.push_entry(entry.center_radius.sphere_distance(target), entry_ref);
+                    }
+                }
+            }
+        }
+        queue.into_nearest()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SsTree<P, K: Center, const M: usize, N: NodePool<P, K, M> = NodePoolBaseline<P, K, M>> {
     pub root: InnerLink<P, K, M>,
     pub pool: N,
@@ -909,6 +1415,39 @@ impl<P, K: Center, const M: usize, N: NodePool<P, K, M>> SsTree<P, K, M, N> {
             m,
         }
     }
+
+    // see sstree::SsTree::validate
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.height > MAX_TREE_HEIGHT {
+            return Err(ValidationError::TooHigh(self.height));
+        }
+        self.root.validate(1, self.height, self.m, &self.pool)
+    }
+}
+
+#[cfg(feature = "sled")]
+impl<P, K, const M: usize> SsTree<P, K, M, NodePoolSled<P, K, M>>
+where
+    P: Serialize + DeserializeOwned,
+    K: Center + Serialize + DeserializeOwned,
+{
+    // continue with the tree stored in the pool, or start a new one if it is empty
+    pub fn open(pool: NodePoolSled<P, K, M>, m: usize) -> Result<Self, NodePoolSledError> {
+        let Some((root, height)) = pool.load_root()? else {
+            return Ok(Self::with_pool(pool, m));
+        };
+        Ok(Self {
+            root,
+            pool,
+            height,
+            m,
+        })
+    }
+
+    // make the current state of the tree persistent
+    pub fn flush(&mut self) -> Result<(), NodePoolSledError> {
+        self.pool.flush(&self.root, self.height)
+    }
 }
 
 impl<P, K: Center, const M: usize, N: NodePool<P, K, M>> SsTreeI<P, K, M> for SsTree<P, K, M, N> {
@@ -939,18 +1478,15 @@ impl<P, K: Center, const M: usize, N: NodePool<P, K, M>> SsTreeI<P, K, M> for Ss
     fn remove(&mut self, point: &K) {
         let (_deleted, _violiates_invariant) = self.root.remove(point, self.m, &mut self.pool);
 
-        let links = self.pool.get(self.root.links);
-
-        match links {
-            Node::Inner(nodes) if nodes.len() == 1 => {
-                let links = self.pool.remove(self.root.links);
-                let Node::Inner(mut nodes) = links else {
-                    panic!("expecting Node::Inner here")
-                };
-                self.root = nodes.pop().unwrap();
-                self.height -= 1;
-            }
-            _ => (),
+        let single_child =
+            matches!(&*self.pool.get(self.root.links), Node::Inner(nodes) if nodes.len() == 1);
+        if single_child {
+            let links = self.pool.remove(self.root.links);
+            let Node::Inner(mut nodes) = links else {
+                panic!("expecting Node::Inner here")
+            };
+            self.root = nodes.pop().unwrap();
+            self.height -= 1;
         }
     }
 
@@ -965,7 +1501,7 @@ impl<P, K: Center, const M: usize, N: NodePool<P, K, M>> SsTreeI<P, K, M> for Ss
     fn find_entries_within_radius<'a>(
         &'a self,
         center_radius: &Bounds<K>,
-        out: &mut Vec<&'a LeafLink<P, K>>,
+        out: &mut Vec<EntryRef<'a, P, K, M>>,
     ) {
         self.root
             .find_entries_within_radius(center_radius, out, &self.pool);
@@ -975,7 +1511,7 @@ impl<P, K: Center, const M: usize, N: NodePool<P, K, M>> SsTreeI<P, K, M> for Ss
         &self,
         center_radius: &Bounds<K>,
         f: &dyn Fn(&P) -> bool,
-    ) -> Option<&LeafLink<P, K>> {
+    ) -> Option<EntryRef<'_, P, K, M>> {
         self.root.find_if(center_radius, &f, &self.pool)
     }
     fn remove_if(
@@ -987,17 +1523,15 @@ impl<P, K: Center, const M: usize, N: NodePool<P, K, M>> SsTreeI<P, K, M> for Ss
             .root
             .remove_if(center_radius, self.m, &f, &mut self.pool)
             .2;
-        let links = self.pool.get(self.root.links);
-        match links {
-            Node::Inner(nodes) if nodes.len() == 1 => {
-                let links = self.pool.remove(self.root.links);
-                let Node::Inner(mut nodes) = links else {
-                    panic!("expecting Node::Inner here")
-                };
-                self.root = nodes.pop().unwrap();
-                self.height -= 1;
-            }
-            _ => (),
+        let single_child =
+            matches!(&*self.pool.get(self.root.links), Node::Inner(nodes) if nodes.len() == 1);
+        if single_child {
+            let links = self.pool.remove(self.root.links);
+            let Node::Inner(mut nodes) = links else {
+                panic!("expecting Node::Inner here")
+            };
+            self.root = nodes.pop().unwrap();
+            self.height -= 1;
         }
         deleted_entry
     }
@@ -1068,6 +1602,8 @@ mod test {
 
     use super::Bounds;
 
+    use super::Distance;
+    use super::EntryRef;
     use super::LeafLink;
     use super::SsTree;
 
@@ -1100,6 +1636,12 @@ mod test {
         }
     }
 
+    impl<P, const M: usize> PartialEq<&LeafLink<P, [f32; 2]>> for EntryRef<'_, P, [f32; 2], M> {
+        fn eq(&self, other: &&LeafLink<P, [f32; 2]>) -> bool {
+            **self == **other
+        }
+    }
+
     #[test]
     fn test_search() {
         const UPPER_M: usize = 8;
@@ -1170,20 +1712,22 @@ mod test {
             &mut out,
         );
         assert_eq!(out.len(), 2);
-        assert!(out.contains(&&LeafLink::<(), _>::new(
-            Bounds {
-                center: [5.0, 5.0],
-                radius: 1.0,
-            },
-            ()
-        )));
-        assert!(out.contains(&&LeafLink::<(), _>::new(
-            Bounds {
-                center: [0.0, 0.0],
-                radius: 1.0,
-            },
-            ()
-        )));
+        assert!(out.iter().any(|e| *e
+            == &LeafLink::<(), _>::new(
+                Bounds {
+                    center: [5.0, 5.0],
+                    radius: 1.0,
+                },
+                ()
+            )));
+        assert!(out.iter().any(|e| *e
+            == &LeafLink::<(), _>::new(
+                Bounds {
+                    center: [0.0, 0.0],
+                    radius: 1.0,
+                },
+                ()
+            )));
 
         let mut out = Vec::new();
 
@@ -1197,6 +1741,184 @@ mod test {
         );
         assert!(out.is_empty());
     }
+
+    #[test]
+    fn test_node_pool_slab() {
+        use super::{Node, NodePool, NodePoolSlab};
+        use arrayvec::ArrayVec;
+
+        // freed slots are reused with a new generation
+        let mut pool = NodePoolSlab::<(), [f32; 2], 8>::new();
+        let a = pool.alloc();
+        pool.put(a, Node::Leaf(ArrayVec::new()));
+        let b = pool.alloc();
+        pool.put(b, Node::Leaf(ArrayVec::new()));
+        pool.remove(a);
+        let c = pool.alloc();
+        assert_eq!(c as u32, a as u32);
+        assert_ne!(c, a);
+        assert_eq!(pool.len(), 2);
+
+        let mut tree = SsTree::<usize, [f32; 2], 8, NodePoolSlab<usize, [f32; 2], 8>>::new(4);
+        for i in 0..500 {
+            let j = (i * 7) % 500;
+            tree.insert(i, [(j % 25) as f32, (j / 25) as f32], 0.25);
+        }
+        for i in (0..500).step_by(2) {
+            let j = (i * 7) % 500;
+            let center_radius = Bounds {
+                center: [(j % 25) as f32, (j / 25) as f32],
+                radius: 0.25,
+            };
+            assert!(tree.remove_if(&center_radius, &|p| *p == i).is_some());
+        }
+        let (num_entries, num_nodes) = tree.root.count_nodes(&tree.pool);
+        assert_eq!(num_entries, 250);
+        // removed nodes went back to the pool
+        assert_eq!(tree.pool.len(), num_nodes);
+        let nearest = tree.find_k_nearest(&[0.0, 0.0], usize::MAX);
+        assert_eq!(nearest.len(), 250);
+        assert!(nearest.iter().all(|(_, entry)| entry.payload % 2 == 1));
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn test_node_pool_sled() {
+        use super::{NodePoolSled, NodePoolSledError};
+
+        type Tree = SsTree<usize, [f32; 2], 8, NodePoolSled<usize, [f32; 2], 8>>;
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let center = |i: usize| {
+            let j = (i * 7) % 500;
+            [(j % 25) as f32, (j / 25) as f32]
+        };
+        let remove_odd = |tree: &mut Tree| {
+            for i in (1..500).step_by(2) {
+                let center_radius = Bounds {
+                    center: center(i),
+                    radius: 0.25,
+                };
+                assert!(tree.remove_if(&center_radius, &|p| *p == i).is_some());
+            }
+        };
+
+        // small cache, nodes are written back while the tree is built
+        let mut tree = Tree::open(NodePoolSled::open(db.clone(), 16).unwrap(), 4).unwrap();
+        for i in 0..500 {
+            tree.insert(i, center(i), 0.25);
+        }
+        assert!(tree.pool.cached() <= 16);
+        tree.flush().unwrap();
+        // not flushed, the stored tree stays as it was
+        remove_odd(&mut tree);
+        drop(tree);
+
+        let mut tree = Tree::open(NodePoolSled::open(db.clone(), 16).unwrap(), 4).unwrap();
+        let (num_entries, _) = tree.root.count_nodes(&tree.pool);
+        assert_eq!(num_entries, 500);
+        remove_odd(&mut tree);
+        tree.flush().unwrap();
+        drop(tree);
+
+        let tree = Tree::open(NodePoolSled::open(db.clone(), 16).unwrap(), 4).unwrap();
+        let nearest = tree.find_k_nearest(&[0.0, 0.0], usize::MAX);
+        assert_eq!(nearest.len(), 250);
+        assert!(nearest.iter().all(|(_, entry)| entry.payload % 2 == 0));
+        // reads evict as well, the results keep their leaves alive
+        assert!(tree.pool.cached() <= 16);
+        drop(nearest);
+        drop(tree);
+
+        // a node missing from the db is reported by flush, which does not store the incomplete tree
+        let mut tree = Tree::open(NodePoolSled::open(db.clone(), 16).unwrap(), 4).unwrap();
+        let nodes = db.open_tree("sstree_nodes").unwrap();
+        nodes.remove(tree.root.links.to_be_bytes()).unwrap();
+        assert_eq!(tree.root.count_nodes(&tree.pool), (0, 1));
+        assert!(matches!(
+            tree.flush(),
+            Err(NodePoolSledError::MissingNode(id)) if id == tree.root.links
+        ));
+        assert!(matches!(tree.flush(), Err(NodePoolSledError::Poisoned)));
+    }
+
+    #[test]
+    fn test_find_k_nearest() {
+        let mut tree = SsTree::<usize, [f32; 2], 8>::new(4);
+        let mut points = Vec::new();
+        for i in 0..400 {
+            // scrambled, so that the tree does not degenerate from sorted input
+            let (x, y) = ((i * 7) % 20, (i * 13) % 23);
+            let center = [x as f32 * 1.5, y as f32 * 0.5];
+            let radius = (i % 3) as f32 * 0.1;
+            points.push((center, radius));
+            tree.insert(i, center, radius);
+        }
+
+        let target = [10.2, 4.7];
+        let nearest = tree.find_k_nearest(&target, 10);
+        let mut expected = points
+            .iter()
+            .map(|(center, radius)| (center.distance(&target) - radius).max(0.0))
+            .collect::<Vec<_>>();
+        expected.sort_by(f32::total_cmp);
+        let distances = nearest.iter().map(|(d, _)| *d).collect::<Vec<_>>();
+        assert_eq!(distances, expected[..10]);
+        for (distance, entry) in nearest {
+            let (center, radius) = points[entry.payload];
+            assert_eq!(distance, (center.distance(&target) - radius).max(0.0));
+        }
+
+        let within = tree.find_k_nearest_within(&target, 100, 1.0);
+        assert_eq!(within.len(), expected.iter().filter(|d| **d <= 1.0).count());
+        assert!(tree.find_k_nearest(&target, 0).is_empty());
+        assert!(SsTree::<(), [f32; 2], 8>::new(4)
+            .find_k_nearest(&target, 1)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_stress() {
+        use super::{NodePool, NodePoolBaseline, NodePoolSlab};
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        use serde::{de::DeserializeOwned, Serialize};
+
+        // random inserts and removals, then a round trip through ron
+        fn stress<N>()
+        where
+            N: NodePool<usize, [f32; 2], 8> + Default + Serialize + DeserializeOwned,
+        {
+            let mut rng = StdRng::seed_from_u64(47);
+            let mut tree = SsTree::<usize, [f32; 2], 8, N>::new(4);
+            let mut entries = Vec::new();
+            for round in 0..30 {
+                for i in 0..rng.gen_range(0..150) {
+                    let center = [rng.gen_range(-50.0..50.0), rng.gen_range(-50.0..50.0)];
+                    let radius = rng.gen_range(0.1..3.0);
+                    tree.insert(round * 1000 + i, center, radius);
+                    entries.push((round * 1000 + i, center, radius));
+                }
+                for _ in 0..rng.gen_range(0..120).min(entries.len()) {
+                    let (payload, center, radius) =
+                        entries.swap_remove(rng.gen_range(0..entries.len()));
+                    let center_radius = Bounds { center, radius };
+                    assert!(tree.remove_if(&center_radius, &|p| *p == payload).is_some());
+                }
+                assert_eq!(tree.validate(), Ok(()));
+                assert_eq!(tree.root.count_nodes(&tree.pool).0, entries.len());
+            }
+
+            let restored: SsTree<usize, [f32; 2], 8, N> =
+                ron::from_str(&ron::to_string(&tree).unwrap()).unwrap();
+            assert_eq!(restored.validate(), Ok(()));
+            assert_eq!(restored.get_height(), tree.get_height());
+            assert_eq!(
+                restored.root.count_nodes(&restored.pool),
+                tree.root.count_nodes(&tree.pool)
+            );
+        }
+        stress::<NodePoolBaseline<usize, [f32; 2], 8>>();
+        stress::<NodePoolSlab<usize, [f32; 2], 8>>();
+    }
 }
 
 use bevy::prelude::*;
@@ -1235,4 +1957,25 @@ impl SpatialIndex {
         self.sstree.find_entries_within_radius(&bounds, &mut out);
         out.into_iter().map(|e| e.payload)
     }
+
+    // the k entities closest to `point` with their distances, nearest first
+    pub fn find_k_nearest(
+        &self,
+        point: Vec3,
+        k: usize,
+    ) -> impl Iterator<Item = (Entity, f32)> + '_ {
+        self.find_k_nearest_within(point, k, f32::INFINITY)
+    }
+
+    pub fn find_k_nearest_within(
+        &self,
+        point: Vec3,
+        k: usize,
+        max_distance: f32,
+    ) -> impl Iterator<Item = (Entity, f32)> + '_ {
+        self.sstree
+            .find_k_nearest_within(&point, k, max_distance)
+            .into_iter()
+            .map(|(distance, e)| (e.payload, distance))
+    }
 }
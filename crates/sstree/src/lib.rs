@@ -1,8 +1,12 @@
 use arrayvec::ArrayVec;
 use bevy::prelude::{Resource, Vec3};
+use serde::{Deserialize, Serialize};
 
 pub mod indirect;
 pub mod indirect_handle;
+mod nearest;
+
+use nearest::{sphere_distance, NearestQueue};
 
 pub const MAX_TREE_HEIGHT: usize = 16;
 
@@ -16,7 +20,7 @@ pub trait DimIndex:
     const NUM_DIMENSIONS: usize;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Entry<P, K> {
     pub center: K,
     pub radius: f32,
@@ -36,6 +40,9 @@ impl<P, K: Distance> Entry<P, K> {
     }
 }
 
+// result of the overlapping pairs queries
+pub type EntryPair<'a, P, Q, K> = (&'a Entry<P, K>, &'a Entry<Q, K>);
+
 impl<const K: usize> Distance for [f32; K] {
     fn distance(&self, p2: &[f32; K]) -> f32 {
         self.iter()
@@ -67,7 +74,7 @@ fn test_distance() {
     assert_eq!([1000.0, -1000.0].distance(&[1000.0, 2000.0]), 3000.0);
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum SsNodeLinks<P, K: Distance + DimIndex + PartialEq, const M: usize> {
     Inner(Box<ArrayVec<SsNode<P, K, M>, M>>),
     Leaf(Box<ArrayVec<Entry<P, K>, M>>),
@@ -85,11 +92,111 @@ fn test_bevy_vec3() {
     });
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum UpdateResult {
+    Updated,
+    // found, but the new bounds would extend the leaf
+    DoesNotFit,
+    NotFound,
+}
+
+// how overflowing nodes are split
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SplitStrategy {
+    // along the direction of maximum variance, at the index minimising the variance of both halves
+    #[default]
+    MinVariance,
+    // along any direction, at the index minimising the sum of the radii and the overlap of the two new spheres
+    MinRadiusOverlap,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SsTreeConfig {
+    pub split: SplitStrategy,
+    // R*-tree style: the first leaf that overflows during an insert is not split, the entries farthest from its
+    // center are inserted again instead. Helps against fat, overlapping nodes from spatially coherent insertion order.
+    pub forced_reinsert: bool,
+}
+
+// share of the entries of an overflowing leaf that is reinserted
+const REINSERT_PERCENT: usize = 30;
+
+// node statistics to compare tree configurations, see SsTree::metrics
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TreeMetrics {
+    pub num_nodes: usize,
+    // mean radius of all nodes except the root
+    pub average_radius: f32,
+    // mean overlap (r1 + r2 - distance, if positive) of all pairs of sibling nodes
+    pub average_overlap: f32,
+}
+
+// structural problem found by validate(). Depth 1 is the root.
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum ValidationError {
+    #[error("tree height {0} exceeds MAX_TREE_HEIGHT")]
+    TooHigh(usize),
+    #[error("node at depth {depth} is not contained in the sphere of its parent")]
+    NotContained { depth: usize },
+    #[error("node at depth {depth} has {len} children, m is {m}")]
+    Underfull { depth: usize, len: usize, m: usize },
+    #[error("leaf at depth {depth}, tree height is {height}")]
+    UnbalancedLeaf { depth: usize, height: usize },
+}
+
+// sphere (distance of the centers, radius) within a sphere of radius `outer`, with some slack for the rounding of the
+// bounding sphere computation
+pub(crate) fn sphere_contains(outer: f32, distance: f32, radius: f32) -> bool {
+    distance + radius <= outer + 1e-4 * (1.0 + outer)
+}
+
+// fill factor of a node with `len` children. The root has no lower bound, but an inner root must branch.
+pub(crate) fn check_fill(
+    len: usize,
+    depth: usize,
+    m: usize,
+    is_leaf: bool,
+) -> Result<(), ValidationError> {
+    let min = match (depth, is_leaf) {
+        (1, true) => 0,
+        (1, false) => 2,
+        _ => m,
+    };
+    if len < min {
+        return Err(ValidationError::Underfull { depth, len, m });
+    }
+    Ok(())
+}
+
+// id of a node within its tree, only assigned for trees that are tracked (see Tracker)
+pub type NodeId = u64;
+
+// told where entries and nodes end up while a tree is restructured, so a map from entries to their leaf and from
+// nodes to their parent can be kept up to date. The `()` tracker does nothing and hands out 0 as id to every node.
+pub trait Tracker<P> {
+    fn new_node_id(&mut self) -> NodeId;
+    fn entry_moved(&mut self, payload: &P, leaf: NodeId);
+    // parent None: `node` is the root now
+    fn node_moved(&mut self, node: NodeId, parent: Option<NodeId>);
+    fn node_dropped(&mut self, node: NodeId);
+}
+
+impl<P> Tracker<P> for () {
+    fn new_node_id(&mut self) -> NodeId {
+        0
+    }
+    fn entry_moved(&mut self, _payload: &P, _leaf: NodeId) {}
+    fn node_moved(&mut self, _node: NodeId, _parent: Option<NodeId>) {}
+    fn node_dropped(&mut self, _node: NodeId) {}
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SsNode<P, K: Distance + DimIndex + PartialEq, const M: usize> {
     pub centroid: K,
     pub radius: f32,
     pub links: SsNodeLinks<P, K, M>,
+    #[serde(skip)]
+    pub id: NodeId,
 }
 
 impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P, K, M> {
@@ -99,6 +206,7 @@ impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P,
             centroid,
             radius,
             links: SsNodeLinks::Leaf(Box::new(entries)),
+            id: 0,
         }
     }
 
@@ -108,6 +216,7 @@ impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P,
             centroid,
             radius,
             links: SsNodeLinks::Inner(Box::new(nodes)),
+            id: 0,
         }
     }
 
@@ -149,9 +258,34 @@ impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P,
         self.radius = radius;
     }
     pub fn insert(&mut self, entry: Entry<P, K>, m: usize) -> Option<(Self, Self)> {
+        self.insert_with(entry, m, &SsTreeConfig::default(), &mut None)
+    }
+
+    // `reinsert` is Some(empty) if forced reinsertion is still possible during this insert. The entries to reinsert
+    // are returned in it.
+    pub fn insert_with(
+        &mut self,
+        entry: Entry<P, K>,
+        m: usize,
+        config: &SsTreeConfig,
+        reinsert: &mut Option<Vec<Entry<P, K>>>,
+    ) -> Option<(Self, Self)> {
+        self.insert_tracked(entry, m, config, reinsert, &mut ())
+    }
+
+    // on a split the first new node keeps the id of this node
+    pub fn insert_tracked<T: Tracker<P>>(
+        &mut self,
+        entry: Entry<P, K>,
+        m: usize,
+        config: &SsTreeConfig,
+        reinsert: &mut Option<Vec<Entry<P, K>>>,
+        tracker: &mut T,
+    ) -> Option<(Self, Self)> {
         match &mut self.links {
             SsNodeLinks::Leaf(points) => {
                 if points.len() < M {
+                    tracker.entry_moved(&entry.payload, self.id);
                     points.push(entry);
                     self.update_bounding_envelope();
                     return None;
@@ -161,7 +295,32 @@ impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P,
                         .chain(std::iter::once(entry))
                         .collect::<Vec<_>>();
 
-                    let split_index = leaf::find_split_index::<P, K, M>(&mut nodes_to_split, m);
+                    if let Some(reinsert) = reinsert.as_mut().filter(|r| r.is_empty()) {
+                        // farthest from the center first
+                        let centroid = util::centroid::<K, _>(&nodes_to_split);
+                        let outer_distance =
+                            |e: &Entry<P, K>| centroid.distance(&e.center) + e.radius;
+                        nodes_to_split
+                            .sort_by(|e1, e2| outer_distance(e2).total_cmp(&outer_distance(e1)));
+                        let num_reinsert = (M * REINSERT_PERCENT / 100).clamp(1, M + 1 - m);
+                        // closest ones are reinserted first
+                        reinsert.extend(nodes_to_split.drain(..num_reinsert).rev());
+                        for entry in &nodes_to_split {
+                            tracker.entry_moved(&entry.payload, self.id);
+                        }
+                        points.extend(nodes_to_split);
+                        self.update_bounding_envelope();
+                        return None;
+                    }
+
+                    let split_index = match config.split {
+                        SplitStrategy::MinVariance => {
+                            leaf::find_split_index::<P, K, M>(&mut nodes_to_split, m)
+                        }
+                        SplitStrategy::MinRadiusOverlap => {
+                            util::find_min_radius_overlap_split_index(&mut nodes_to_split, m)
+                        }
+                    };
                     let points2: ArrayVec<_, M> = nodes_to_split.drain(split_index..).collect();
                     let (centroid2, radius2) = leaf::centroid_and_radius::<P, K, M>(&points2);
 
@@ -172,12 +331,16 @@ impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P,
                         centroid: centroid1,
                         radius: radius1,
                         links: SsNodeLinks::Leaf(Box::new(points1)),
+                        id: self.id,
                     };
                     let new_node2 = Self {
                         centroid: centroid2,
                         radius: radius2,
                         links: SsNodeLinks::Leaf(Box::new(points2)),
+                        id: tracker.new_node_id(),
                     };
+                    new_node1.report_children(tracker);
+                    new_node2.report_children(tracker);
 
                     return Some((new_node1, new_node2));
                 }
@@ -185,14 +348,17 @@ impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P,
 
             SsNodeLinks::Inner(children) => {
                 let closest_child_index = find_closest_child_index(children, &entry.center);
-                if let Some((new_child_1, new_child_2)) =
-                    children[closest_child_index].insert(entry, m)
+                if let Some((new_child_1, new_child_2)) = children[closest_child_index]
+                    .insert_tracked(entry, m, config, reinsert, tracker)
                 {
                     children.remove(closest_child_index);
 
                     if children.len() < M - 1 {
+                        tracker.node_moved(new_child_1.id, Some(self.id));
+                        tracker.node_moved(new_child_2.id, Some(self.id));
                         children.push(new_child_1);
                         children.push(new_child_2);
+                        self.update_bounding_envelope();
                     } else {
                         let mut nodes_to_split: Vec<_> = children
                             .drain(..)
@@ -200,7 +366,14 @@ impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P,
                             .chain(std::iter::once(new_child_2))
                             .collect();
 
-                        let split_index = inner::find_split_index(&mut nodes_to_split, m);
+                        let split_index = match config.split {
+                            SplitStrategy::MinVariance => {
+                                inner::find_split_index(&mut nodes_to_split, m)
+                            }
+                            SplitStrategy::MinRadiusOverlap => {
+                                util::find_min_radius_overlap_split_index(&mut nodes_to_split, m)
+                            }
+                        };
 
                         let points2: ArrayVec<_, M> = nodes_to_split.drain(split_index..).collect();
                         let (centroid2, radius2) = inner::centroid_and_radius(&points2);
@@ -212,12 +385,16 @@ impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P,
                             centroid: centroid1,
                             radius: radius1,
                             links: SsNodeLinks::Inner(Box::new(points1)),
+                            id: self.id,
                         };
                         let new_node2 = Self {
                             centroid: centroid2,
                             radius: radius2,
                             links: SsNodeLinks::Inner(Box::new(points2)),
+                            id: tracker.new_node_id(),
                         };
+                        new_node1.report_children(tracker);
+                        new_node2.report_children(tracker);
                         return Some((new_node1, new_node2));
                     }
                 } else {
@@ -228,6 +405,31 @@ impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P,
         None
     }
 
+    // tell the tracker that all entries or child nodes are here now
+    fn report_children<T: Tracker<P>>(&self, tracker: &mut T) {
+        match &self.links {
+            SsNodeLinks::Inner(nodes) => {
+                for node in nodes.iter() {
+                    tracker.node_moved(node.id, Some(self.id));
+                }
+            }
+            SsNodeLinks::Leaf(entries) => {
+                for entry in entries.iter() {
+                    tracker.entry_moved(&entry.payload, self.id);
+                }
+            }
+        }
+    }
+
+    // give every node of the subtree a new id and report where everything is
+    fn assign_ids<T: Tracker<P>>(&mut self, tracker: &mut T) {
+        self.id = tracker.new_node_id();
+        if let SsNodeLinks::Inner(nodes) = &mut self.links {
+            nodes.iter_mut().for_each(|node| node.assign_ids(tracker));
+        }
+        self.report_children(tracker);
+    }
+
     pub fn remove(&mut self, target: &K, m: usize) -> (bool, bool) {
         match &mut self.links {
             SsNodeLinks::Leaf(entries) => {
@@ -272,16 +474,7 @@ impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P,
                     }
 
                     Some(node_to_fix) => {
-                        if let Some(sibling_to_borrow_from) =
-                            inner::find_sibling_to_borrow_from(nodes, node_to_fix, m)
-                        {
-                            inner::borrow_from_sibling(nodes, node_to_fix, sibling_to_borrow_from);
-                        } else if let Some(sibling_to_merge_to) =
-                            inner::find_sibling_to_merge_to(nodes, node_to_fix, m)
-                        {
-                            // no sibling to borrow from -> merge
-                            inner::merge_siblings(nodes, node_to_fix, sibling_to_merge_to);
-                        }
+                        inner::fix_underfull_child(nodes, node_to_fix, m, &mut ());
                         let num_nodes = nodes.len();
                         if num_nodes != 0 {
                             self.update_bounding_envelope();
@@ -294,6 +487,71 @@ impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P,
         }
     }
 
+    // structural checks of the subtree, see SsTree::validate
+    pub fn validate(&self, depth: usize, height: usize, m: usize) -> Result<(), ValidationError> {
+        let contains = |center: &K, radius: f32| {
+            sphere_contains(self.radius, self.centroid.distance(center), radius)
+        };
+        match &self.links {
+            SsNodeLinks::Leaf(entries) => {
+                check_fill(entries.len(), depth, m, true)?;
+                if depth != height {
+                    return Err(ValidationError::UnbalancedLeaf { depth, height });
+                }
+                if !entries.iter().all(|e| contains(&e.center, e.radius)) {
+                    return Err(ValidationError::NotContained { depth: depth + 1 });
+                }
+            }
+            SsNodeLinks::Inner(nodes) => {
+                check_fill(nodes.len(), depth, m, false)?;
+                for node in nodes.iter() {
+                    if !contains(&node.centroid, node.radius) {
+                        return Err(ValidationError::NotContained { depth: depth + 1 });
+                    }
+                    node.validate(depth + 1, height, m)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // sums for TreeMetrics: (number of nodes, sum of radii, number of sibling pairs, sum of overlaps) below this node
+    fn sum_metrics(&self) -> (usize, f32, usize, f32) {
+        let SsNodeLinks::Inner(nodes) = &self.links else {
+            return (0, 0.0, 0, 0.0);
+        };
+        let mut sums = (nodes.len(), 0.0, 0, 0.0);
+        for (i, n1) in nodes.iter().enumerate() {
+            sums.1 += n1.radius;
+            for n2 in &nodes[i + 1..] {
+                sums.2 += 1;
+                sums.3 += (n1.radius + n2.radius - n1.centroid.distance(&n2.centroid)).max(0.0);
+            }
+            let child = n1.sum_metrics();
+            sums = (
+                sums.0 + child.0,
+                sums.1 + child.1,
+                sums.2 + child.2,
+                sums.3 + child.3,
+            );
+        }
+        sums
+    }
+
+    // number of nodes find_entries_within_radius visits
+    pub fn count_node_visits(&self, center: &K, radius: f32) -> usize {
+        match &self.links {
+            SsNodeLinks::Leaf(_) => 1,
+            SsNodeLinks::Inner(nodes) => {
+                1 + nodes
+                    .iter()
+                    .filter(|child| child.centroid.distance(center) <= radius + child.radius)
+                    .map(|child| child.count_node_visits(center, radius))
+                    .sum::<usize>()
+            }
+        }
+    }
+
     pub fn count_nodes(&self) -> (usize, usize) {
         match &self.links {
             SsNodeLinks::Inner(nodes) => nodes.iter().fold((0, 1), |(a_points, a_nodes), n| {
@@ -327,6 +585,31 @@ impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P,
         }
     }
 
+    // entries whose bounding sphere passes `test(center, radius)`. Nodes are pruned with the same test, so it has to
+    // pass for every sphere that contains a passing one.
+    pub fn find_entries_matching<'a, F: Fn(&K, f32) -> bool>(
+        &'a self,
+        test: &F,
+        out: &mut Vec<&'a Entry<P, K>>,
+    ) {
+        match &self.links {
+            SsNodeLinks::Leaf(points) => {
+                out.extend(
+                    points
+                        .iter()
+                        .filter(|point| test(&point.center, point.radius)),
+                );
+            }
+            SsNodeLinks::Inner(nodes) => {
+                for child in nodes.iter() {
+                    if test(&child.centroid, child.radius) {
+                        child.find_entries_matching(test, out);
+                    }
+                }
+            }
+        }
+    }
+
     pub fn find_if<F: Fn(&P) -> bool>(
         &self,
         center: &K,
@@ -356,6 +639,50 @@ impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P,
         None
     }
 
+    // move the entry matching `f` (searched like in remove_if) to the bounds in `to`, if they still fit into its
+    // leaf. `to` is taken when the entry was moved, the envelopes of the leaf and its ancestors are updated then.
+    pub fn update_if<F: Fn(&P) -> bool>(
+        &mut self,
+        center: &K,
+        radius: f32,
+        f: &F,
+        to: &mut Option<(K, f32)>,
+    ) -> UpdateResult {
+        let res = match &mut self.links {
+            SsNodeLinks::Leaf(entries) => {
+                let Some(entry) = entries
+                    .iter_mut()
+                    .find(|p| p.center.distance(center) < (radius + p.radius) && f(&p.payload))
+                else {
+                    return UpdateResult::NotFound;
+                };
+                match to.take() {
+                    Some((new_center, new_radius))
+                        if self.centroid.distance(&new_center) + new_radius <= self.radius =>
+                    {
+                        entry.center = new_center;
+                        entry.radius = new_radius;
+                        UpdateResult::Updated
+                    }
+                    new_bounds => {
+                        *to = new_bounds;
+                        UpdateResult::DoesNotFit
+                    }
+                }
+            }
+            SsNodeLinks::Inner(nodes) => nodes
+                .iter_mut()
+                .filter(|child| child.centroid.distance(center) <= radius + child.radius)
+                .map(|child| child.update_if(center, radius, f, to))
+                .find(|res| *res != UpdateResult::NotFound)
+                .unwrap_or(UpdateResult::NotFound),
+        };
+        if res == UpdateResult::Updated {
+            self.update_bounding_envelope();
+        }
+        res
+    }
+
     pub fn remove_if<F: Fn(&P) -> bool>(
         &mut self,
         center: &K,
@@ -408,16 +735,7 @@ impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P,
                     }
 
                     Some(node_to_fix) => {
-                        if let Some(sibling_to_borrow_from) =
-                            inner::find_sibling_to_borrow_from(nodes, node_to_fix, m)
-                        {
-                            inner::borrow_from_sibling(nodes, node_to_fix, sibling_to_borrow_from);
-                        } else if let Some(sibling_to_merge_to) =
-                            inner::find_sibling_to_merge_to(nodes, node_to_fix, m)
-                        {
-                            // no sibling to borrow from -> merge
-                            inner::merge_siblings(nodes, node_to_fix, sibling_to_merge_to);
-                        }
+                        inner::fix_underfull_child(nodes, node_to_fix, m, &mut ());
                         let num_nodes = nodes.len();
                         if num_nodes != 0 {
                             self.update_bounding_envelope();
@@ -430,6 +748,95 @@ impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P,
         }
     }
 
+    // like update_if, but the entry is in the leaf at the end of `path` (the ids of the nodes below this one), so
+    // there is no search. It stays in the leaf if it still fits into the sphere of the leaf's `parent`, the leaf may
+    // grow then.
+    fn update_at<F: Fn(&P) -> bool>(
+        &mut self,
+        path: &[NodeId],
+        f: &F,
+        to: &mut Option<(K, f32)>,
+        parent: Option<(&K, f32)>,
+    ) -> UpdateResult {
+        let res = match (&mut self.links, path) {
+            (SsNodeLinks::Leaf(entries), []) => {
+                let Some(entry) = entries.iter_mut().find(|e| f(&e.payload)) else {
+                    return UpdateResult::NotFound;
+                };
+                match to.take() {
+                    Some((new_center, new_radius))
+                        if parent.is_none_or(|(centroid, radius)| {
+                            centroid.distance(&new_center) + new_radius <= radius
+                        }) =>
+                    {
+                        entry.center = new_center;
+                        entry.radius = new_radius;
+                        UpdateResult::Updated
+                    }
+                    new_bounds => {
+                        *to = new_bounds;
+                        UpdateResult::DoesNotFit
+                    }
+                }
+            }
+            (SsNodeLinks::Inner(nodes), [id, path @ ..]) => {
+                match nodes.iter_mut().find(|node| node.id == *id) {
+                    Some(child) => {
+                        child.update_at(path, f, to, Some((&self.centroid, self.radius)))
+                    }
+                    None => UpdateResult::NotFound,
+                }
+            }
+            _ => UpdateResult::NotFound,
+        };
+        if res == UpdateResult::Updated {
+            self.update_bounding_envelope();
+        }
+        res
+    }
+
+    // like remove_if, but the entry is in the leaf at the end of `path`. Returns whether this node is underfull now
+    // and the removed entry.
+    fn remove_at<F: Fn(&P) -> bool, T: Tracker<P>>(
+        &mut self,
+        path: &[NodeId],
+        m: usize,
+        f: &F,
+        tracker: &mut T,
+    ) -> (bool, Option<Entry<P, K>>) {
+        let (num_nodes, entry) = match (&mut self.links, path) {
+            (SsNodeLinks::Leaf(entries), []) => {
+                let Some(i) = entries.iter().position(|e| f(&e.payload)) else {
+                    return (false, None);
+                };
+                let entry = entries.remove(i);
+                let num_entries = entries.len();
+                if num_entries != 0 {
+                    self.update_bounding_envelope();
+                }
+                return (num_entries < m, Some(entry));
+            }
+            (SsNodeLinks::Inner(nodes), [id, path @ ..]) => {
+                let Some(i) = nodes.iter().position(|node| node.id == *id) else {
+                    return (false, None);
+                };
+                let (violates_invariants, entry) = nodes[i].remove_at(path, m, f, tracker);
+                if entry.is_none() {
+                    return (false, None);
+                }
+                if violates_invariants {
+                    inner::fix_underfull_child(nodes, i, m, tracker);
+                }
+                (nodes.len(), entry)
+            }
+            _ => return (false, None),
+        };
+        if num_nodes != 0 {
+            self.update_bounding_envelope();
+        }
+        (num_nodes < m, entry)
+    }
+
     // function pointsWithinRegion(node, region)
     //   points ← []
     //   if node.leaf then
@@ -443,6 +850,49 @@ impl<P, K: Default + DimIndex + Distance + PartialEq, const M: usize> SsNode<P,
     //   return points
 }
 
+// parameter t where the ray `origin + t * dir` enters the sphere, 0 if the origin is inside of it. None if the ray
+// misses the sphere or points away from it.
+fn ray_sphere_intersection<K: DimIndex>(
+    origin: &K,
+    dir: &K,
+    center: &K,
+    radius: f32,
+) -> Option<f32> {
+    // |origin + t * dir - center|^2 = radius^2  <=>  a * t^2 - 2 * b * t + c = 0
+    let (mut a, mut b, mut c) = (0.0, 0.0, -radius * radius);
+    for i in 0..K::NUM_DIMENSIONS {
+        let to_center = center[i] - origin[i];
+        a += dir[i] * dir[i];
+        b += to_center * dir[i];
+        c += to_center * to_center;
+    }
+    if c <= 0.0 {
+        return Some(0.0);
+    }
+    let discriminant = b * b - a * c;
+    if a <= 0.0 || b < 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    Some((b - discriminant.sqrt()) / a)
+}
+
+fn sphere_intersects_aabb<K: DimIndex>(center: &K, radius: f32, min: &K, max: &K) -> bool {
+    let mut distance_squared = 0.0;
+    for i in 0..K::NUM_DIMENSIONS {
+        let d = (min[i] - center[i]).max(center[i] - max[i]).max(0.0);
+        distance_squared += d * d;
+    }
+    distance_squared <= radius * radius
+}
+
+// signed distance of `point` to the plane `dot(normal, x) = w`
+fn plane_distance<K: DimIndex>(point: &K, normal: &K, w: f32) -> f32 {
+    (0..K::NUM_DIMENSIONS)
+        .map(|i| point[i] * normal[i])
+        .sum::<f32>()
+        - w
+}
+
 fn find_closest_child<'a, P, K: Distance + DimIndex + PartialEq, const M: usize>(
     children: &'a [SsNode<P, K, M>],
     target: &K,
@@ -474,26 +924,68 @@ fn find_closest_child_index<P, K: Distance + DimIndex + PartialEq, const M: usiz
     cur_min.unwrap()
 }
 
-#[derive(Debug)]
+// deserialized trees are not checked, use validate() for data that might be broken
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SsTree<P, K: Distance + DimIndex + PartialEq, const M: usize> {
     pub root: SsNode<P, K, M>,
     height: usize,
     m: usize,
+    #[serde(default)]
+    config: SsTreeConfig,
 }
 
 impl<P, K: Default + Distance + DimIndex + PartialEq, const M: usize> SsTree<P, K, M> {
     pub fn new(m: usize) -> Self {
+        Self::with_config(m, SsTreeConfig::default())
+    }
+
+    pub fn with_config(m: usize, config: SsTreeConfig) -> Self {
         Self {
             root: SsNode {
                 centroid: K::default(),
                 radius: 0f32,
                 links: SsNodeLinks::Leaf(Box::new(ArrayVec::new())),
+                id: 0,
             },
             height: 1,
             m,
+            config,
         }
     }
 
+    // balanced tree from all entries at once. The entries are split top-down along the direction of maximum variance
+    // into one group per child, which gives tighter nodes than inserting them one by one.
+    pub fn bulk_load(entries: Vec<Entry<P, K>>) -> Self {
+        let m = M / 2;
+        if entries.is_empty() {
+            return Self::new(m);
+        }
+        let mut height = 1;
+        let mut capacity = M;
+        while capacity < entries.len() {
+            capacity *= M;
+            height += 1;
+        }
+        Self {
+            root: bulk::build(entries, height),
+            height,
+            m,
+            config: SsTreeConfig::default(),
+        }
+    }
+
+    // like bulk_load, every node gets an id from `tracker`
+    pub fn bulk_load_tracked<T: Tracker<P>>(entries: Vec<Entry<P, K>>, tracker: &mut T) -> Self {
+        let mut tree = Self::bulk_load(entries);
+        tree.root.assign_ids(tracker);
+        tracker.node_moved(tree.root.id, None);
+        tree
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(&self.root.links, SsNodeLinks::Leaf(entries) if entries.is_empty())
+    }
+
     pub fn insert(&mut self, payload: P, center: K, radius: f32) {
         self.insert_entry(Entry {
             center,
@@ -502,7 +994,29 @@ impl<P, K: Default + Distance + DimIndex + PartialEq, const M: usize> SsTree<P,
         })
     }
     pub fn insert_entry(&mut self, entry: Entry<P, K>) {
-        if let Some((new_child_1, new_child_2)) = self.root.insert(entry, self.m) {
+        self.insert_entry_tracked(entry, &mut ());
+    }
+
+    // insert and tell `tracker` where entries and nodes moved to
+    pub fn insert_entry_tracked<T: Tracker<P>>(&mut self, entry: Entry<P, K>, tracker: &mut T) {
+        let mut reinsert = self.config.forced_reinsert.then(Vec::new);
+        self.insert_into_root(entry, &mut reinsert, tracker);
+        // entries pushed out of an overflowing leaf. They are not reinserted again, further overflows are split.
+        for entry in reinsert.into_iter().flatten() {
+            self.insert_into_root(entry, &mut None, tracker);
+        }
+    }
+
+    fn insert_into_root<T: Tracker<P>>(
+        &mut self,
+        entry: Entry<P, K>,
+        reinsert: &mut Option<Vec<Entry<P, K>>>,
+        tracker: &mut T,
+    ) {
+        if let Some((new_child_1, new_child_2)) =
+            self.root
+                .insert_tracked(entry, self.m, &self.config, reinsert, tracker)
+        {
             let mut nodes = ArrayVec::<_, M>::new();
             nodes.push(new_child_1);
             nodes.push(new_child_2);
@@ -511,7 +1025,10 @@ impl<P, K: Default + Distance + DimIndex + PartialEq, const M: usize> SsTree<P,
                 centroid,
                 radius,
                 links: SsNodeLinks::Inner(Box::new(nodes)),
+                id: tracker.new_node_id(),
             };
+            tracker.node_moved(self.root.id, None);
+            self.root.report_children(tracker);
             self.height += 1;
         }
     }
@@ -537,6 +1054,29 @@ impl<P, K: Default + Distance + DimIndex + PartialEq, const M: usize> SsTree<P,
         num_points as f32 / num_nodes as f32
     }
 
+    pub fn metrics(&self) -> TreeMetrics {
+        let (num_nodes, radius_sum, num_pairs, overlap_sum) = self.root.sum_metrics();
+        TreeMetrics {
+            num_nodes: num_nodes + 1,
+            average_radius: radius_sum / num_nodes.max(1) as f32,
+            average_overlap: overlap_sum / num_pairs.max(1) as f32,
+        }
+    }
+
+    // cost of a radius query, in nodes visited
+    pub fn count_node_visits(&self, center: &K, radius: f32) -> usize {
+        self.root.count_node_visits(center, radius)
+    }
+
+    // checks the structural invariants: child spheres within their parents, at least m children per node (M is
+    // enforced by the ArrayVecs), all leaves at the same depth and the height limit
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.height > MAX_TREE_HEIGHT {
+            return Err(ValidationError::TooHigh(self.height));
+        }
+        self.root.validate(1, self.height, self.m)
+    }
+
     pub fn find_entries_within_radius<'a>(
         &'a self,
         center: &K,
@@ -546,6 +1086,57 @@ impl<P, K: Default + Distance + DimIndex + PartialEq, const M: usize> SsTree<P,
         self.root.find_entries_within_radius(center, radius, out);
     }
 
+    pub fn find_entries_matching<'a, F: Fn(&K, f32) -> bool>(
+        &'a self,
+        test: F,
+        out: &mut Vec<&'a Entry<P, K>>,
+    ) {
+        self.root.find_entries_matching(&test, out);
+    }
+
+    // entries whose bounding sphere intersects the axis aligned box from `min` to `max`
+    pub fn find_entries_intersecting_aabb<'a>(
+        &'a self,
+        min: &K,
+        max: &K,
+        out: &mut Vec<&'a Entry<P, K>>,
+    ) {
+        self.find_entries_matching(
+            |center, radius| sphere_intersects_aabb(center, radius, min, max),
+            out,
+        );
+    }
+
+    // entries whose bounding sphere reaches into the half space `dot(normal, x) >= w`. `normal` must be normalized.
+    pub fn find_entries_intersecting_half_space<'a>(
+        &'a self,
+        normal: &K,
+        w: f32,
+        out: &mut Vec<&'a Entry<P, K>>,
+    ) {
+        self.find_entries_matching(
+            |center, radius| plane_distance(center, normal, w) >= -radius,
+            out,
+        );
+    }
+
+    // entries whose bounding sphere is not completely outside of one of the half spaces. Conservative for frustums:
+    // spheres near the corners may be included although they are outside.
+    pub fn find_entries_intersecting_frustum<'a>(
+        &'a self,
+        half_spaces: &[(K, f32)],
+        out: &mut Vec<&'a Entry<P, K>>,
+    ) {
+        self.find_entries_matching(
+            |center, radius| {
+                half_spaces
+                    .iter()
+                    .all(|(normal, w)| plane_distance(center, normal, *w) >= -radius)
+            },
+            out,
+        );
+    }
+
     pub fn find_if<'a, F: Fn(&P) -> bool>(
         &'a self,
         center: &K,
@@ -554,51 +1145,260 @@ impl<P, K: Default + Distance + DimIndex + PartialEq, const M: usize> SsTree<P,
     ) -> Option<&'a Entry<P, K>> {
         self.root.find_if(center, radius, &f)
     }
-    pub fn remove_if<F: Fn(&P) -> bool>(
-        &mut self,
-        center: &K,
-        radius: f32,
-        f: F,
-    ) -> Option<Entry<P, K>> {
-        let deleted_entry = self.root.remove_if(center, radius, self.m, &f).2;
-        match &mut self.root.links {
-            SsNodeLinks::Inner(nodes) if nodes.len() == 1 => {
-                self.root = nodes.pop().unwrap();
-                self.height -= 1;
-            }
-            _ => (),
-        }
-        deleted_entry
-    }
-}
 
-impl<P, K: Distance + DimIndex + Default + PartialEq, const M: usize> Default for SsTree<P, K, M> {
-    fn default() -> Self {
-        Self::new(M / 2)
+    // the k entries closest to `target` with their distances, nearest first. Distances are measured to the bounding
+    // sphere of the entries.
+    pub fn find_k_nearest(&self, target: &K, k: usize) -> Vec<(f32, &Entry<P, K>)> {
+        self.find_k_nearest_within(target, k, f32::INFINITY)
     }
-}
 
-mod util {
-    use super::{DimIndex, Distance, Entry, SsNode};
-
-    pub trait GetCenter<K> {
-        fn get_center(&self) -> &K;
+    // like find_k_nearest, but only entries up to `max_distance` away
+    pub fn find_k_nearest_within(
+        &self,
+        target: &K,
+        k: usize,
+        max_distance: f32,
+    ) -> Vec<(f32, &Entry<P, K>)> {
+        self.find_k_nearest_by(k, max_distance, |center| center.distance(target))
     }
 
-    impl<P, K> GetCenter<K> for Entry<P, K> {
-        fn get_center(&self) -> &K {
-            &self.center
+    // like find_k_nearest_within, for targets other than a point. `distance` must not change faster than the point
+    // moves (true for the distance to any set of points, e.g. a segment), otherwise nodes are pruned too early.
+    pub fn find_k_nearest_by<F: Fn(&K) -> f32>(
+        &self,
+        k: usize,
+        max_distance: f32,
+        distance: F,
+    ) -> Vec<(f32, &Entry<P, K>)> {
+        let mut queue = NearestQueue::new(k, max_distance);
+        queue.push_node(
+            sphere_distance(distance(&self.root.centroid), self.root.radius),
+            &self.root,
+        );
+        while let Some(node) = queue.next_node() {
+            match &node.links {
+                SsNodeLinks::Inner(nodes) => {
+                    for child in nodes.iter() {
+                        queue.push_node(
+                            sphere_distance(distance(&child.centroid), child.radius),
+                            child,
+                        );
+                    }
+                }
+                SsNodeLinks::Leaf(entries) => {
+                    for entry in entries.iter() {
+                        queue.push_entry(
+                            sphere_distance(distance(&entry.center), entry.radius),
+                            entry,
+                        );
+                    }
+                }
+            }
         }
+        queue.into_nearest()
     }
 
-    impl<P, K: Distance + DimIndex + PartialEq, const M: usize> GetCenter<K> for SsNode<P, K, M> {
-        fn get_center(&self) -> &K {
-            &self.centroid
+    // entries whose bounding sphere is hit by the ray `origin + t * dir` with t <= max_t, ordered by the t where the
+    // ray enters the sphere (0 for spheres containing the origin)
+    pub fn find_entries_intersecting_ray(
+        &self,
+        origin: &K,
+        dir: &K,
+        max_t: f32,
+    ) -> Vec<(f32, &Entry<P, K>)> {
+        let mut queue = NearestQueue::new(usize::MAX, max_t);
+        if let Some(t) = ray_sphere_intersection(origin, dir, &self.root.centroid, self.root.radius)
+        {
+            queue.push_node(t, &self.root);
+        }
+        while let Some(node) = queue.next_node() {
+            match &node.links {
+                SsNodeLinks::Inner(nodes) => {
+                    for child in nodes.iter() {
+                        if let Some(t) =
+                            ray_sphere_intersection(origin, dir, &child.centroid, child.radius)
+                        {
+                            queue.push_node(t, child);
+                        }
+                    }
+                }
+                SsNodeLinks::Leaf(entries) => {
+                    for entry in entries.iter() {
+                        if let Some(t) =
+                            ray_sphere_intersection(origin, dir, &entry.center, entry.radius)
+                        {
+                            queue.push_entry(t, entry);
+                        }
+                    }
+                }
+            }
         }
+        queue.into_nearest()
     }
 
-    pub fn mean_along_direction<K: DimIndex, E: GetCenter<K>>(
-        entry: &[E],
+    // entries intersecting the segment from `start` to `end`, ordered like find_entries_intersecting_ray with t in
+    // 0..=1
+    pub fn find_entries_intersecting_segment(
+        &self,
+        start: &K,
+        end: &K,
+    ) -> Vec<(f32, &Entry<P, K>)> {
+        let mut dir = K::default();
+        for i in 0..K::NUM_DIMENSIONS {
+            dir[i] = end[i] - start[i];
+        }
+        self.find_entries_intersecting_ray(start, &dir, 1.0)
+    }
+
+    // every pair of entries whose spheres intersect (same test as find_entries_within_radius), each pair once. The
+    // tree is traversed simultaneously with itself, so subtrees that do not touch are skipped as a whole.
+    pub fn overlapping_pairs(&self) -> Vec<EntryPair<'_, P, P, K>> {
+        let mut out = Vec::new();
+        pairs::self_join(&self.root, &mut out);
+        out
+    }
+
+    // pairs of intersecting entries with the first one from this and the second one from the other tree
+    pub fn overlapping_pairs_with<'a, Q>(
+        &'a self,
+        other: &'a SsTree<Q, K, M>,
+    ) -> Vec<EntryPair<'a, P, Q, K>> {
+        let mut out = Vec::new();
+        pairs::join(&self.root, &other.root, &mut out);
+        out
+    }
+
+    // move the entry matching `f` at `center`/`radius` to the new bounds. It is updated in place if it still fits
+    // into its leaf, otherwise it is re-inserted. Returns false if there is no such entry.
+    pub fn update_if<F: Fn(&P) -> bool>(
+        &mut self,
+        center: &K,
+        radius: f32,
+        f: F,
+        new_center: K,
+        new_radius: f32,
+    ) -> bool {
+        let mut to = Some((new_center, new_radius));
+        match self.root.update_if(center, radius, &f, &mut to) {
+            UpdateResult::Updated => true,
+            UpdateResult::NotFound => false,
+            UpdateResult::DoesNotFit => {
+                let (Some(entry), Some((new_center, new_radius))) =
+                    (self.remove_if(center, radius, f), to)
+                else {
+                    return false;
+                };
+                self.insert(entry.payload, new_center, new_radius);
+                true
+            }
+        }
+    }
+
+    // like update_if for tracked trees: `path` holds the ids from the root down to the leaf of the entry, as
+    // recorded by the tracker. Entries that leave the sphere of their leaf's parent are re-inserted.
+    pub fn update_at<F: Fn(&P) -> bool, T: Tracker<P>>(
+        &mut self,
+        path: &[NodeId],
+        f: F,
+        new_center: K,
+        new_radius: f32,
+        tracker: &mut T,
+    ) -> bool {
+        let [root_id, below_root @ ..] = path else {
+            return false;
+        };
+        if *root_id != self.root.id {
+            return false;
+        }
+        let mut to = Some((new_center, new_radius));
+        match self.root.update_at(below_root, &f, &mut to, None) {
+            UpdateResult::Updated => true,
+            UpdateResult::NotFound => false,
+            UpdateResult::DoesNotFit => {
+                let (Some(mut entry), Some((new_center, new_radius))) =
+                    (self.remove_at(path, f, tracker), to)
+                else {
+                    return false;
+                };
+                entry.center = new_center;
+                entry.radius = new_radius;
+                self.insert_entry_tracked(entry, tracker);
+                true
+            }
+        }
+    }
+
+    // like remove_if for tracked trees, see update_at for `path`
+    pub fn remove_at<F: Fn(&P) -> bool, T: Tracker<P>>(
+        &mut self,
+        path: &[NodeId],
+        f: F,
+        tracker: &mut T,
+    ) -> Option<Entry<P, K>> {
+        let [root_id, below_root @ ..] = path else {
+            return None;
+        };
+        if *root_id != self.root.id {
+            return None;
+        }
+        let deleted_entry = self.root.remove_at(below_root, self.m, &f, tracker).1;
+        match &mut self.root.links {
+            SsNodeLinks::Inner(nodes) if nodes.len() == 1 => {
+                tracker.node_dropped(self.root.id);
+                self.root = nodes.pop().unwrap();
+                tracker.node_moved(self.root.id, None);
+                self.height -= 1;
+            }
+            _ => (),
+        }
+        deleted_entry
+    }
+
+    pub fn remove_if<F: Fn(&P) -> bool>(
+        &mut self,
+        center: &K,
+        radius: f32,
+        f: F,
+    ) -> Option<Entry<P, K>> {
+        let deleted_entry = self.root.remove_if(center, radius, self.m, &f).2;
+        match &mut self.root.links {
+            SsNodeLinks::Inner(nodes) if nodes.len() == 1 => {
+                self.root = nodes.pop().unwrap();
+                self.height -= 1;
+            }
+            _ => (),
+        }
+        deleted_entry
+    }
+}
+
+impl<P, K: Distance + DimIndex + Default + PartialEq, const M: usize> Default for SsTree<P, K, M> {
+    fn default() -> Self {
+        Self::new(M / 2)
+    }
+}
+
+mod util {
+    use super::{DimIndex, Distance, Entry, SsNode};
+
+    pub trait GetCenter<K> {
+        fn get_center(&self) -> &K;
+    }
+
+    impl<P, K> GetCenter<K> for Entry<P, K> {
+        fn get_center(&self) -> &K {
+            &self.center
+        }
+    }
+
+    impl<P, K: Distance + DimIndex + PartialEq, const M: usize> GetCenter<K> for SsNode<P, K, M> {
+        fn get_center(&self) -> &K {
+            &self.centroid
+        }
+    }
+
+    pub fn mean_along_direction<K: DimIndex, E: GetCenter<K>>(
+        entry: &[E],
         direction_index: usize,
     ) -> f32 {
         assert!(!entry.is_empty());
@@ -640,6 +1440,62 @@ mod util {
         direction_index
     }
 
+    pub trait GetRadius {
+        fn get_radius(&self) -> f32;
+    }
+
+    impl<P, K> GetRadius for Entry<P, K> {
+        fn get_radius(&self) -> f32 {
+            self.radius
+        }
+    }
+
+    impl<P, K: Distance + DimIndex + PartialEq, const M: usize> GetRadius for SsNode<P, K, M> {
+        fn get_radius(&self) -> f32 {
+            self.radius
+        }
+    }
+
+    // split index for SplitStrategy::MinRadiusOverlap. Tries all directions, `entries` are left sorted along the best
+    // one.
+    pub fn find_min_radius_overlap_split_index<
+        K: DimIndex + Distance + Default,
+        E: GetCenter<K> + GetRadius,
+    >(
+        entries: &mut [E],
+        m: usize,
+    ) -> usize {
+        let sphere = |entries: &[E]| {
+            let centroid = centroid::<K, E>(entries);
+            let radius = entries
+                .iter()
+                .map(|e| centroid.distance(e.get_center()) + e.get_radius())
+                .fold(0.0, f32::max);
+            (centroid, radius)
+        };
+        let sort = |entries: &mut [E], direction: usize| {
+            entries
+                .sort_by(|e1, e2| e1.get_center()[direction].total_cmp(&e2.get_center()[direction]))
+        };
+
+        let mut best = (f32::INFINITY, 0, m);
+        for direction in 0..K::NUM_DIMENSIONS {
+            sort(entries, direction);
+            for i in m..=(entries.len() - m) {
+                let (centroid1, radius1) = sphere(&entries[..i]);
+                let (centroid2, radius2) = sphere(&entries[i..]);
+                let overlap = (radius1 + radius2 - centroid1.distance(&centroid2)).max(0.0);
+                let cost = radius1 + radius2 + overlap;
+                if cost < best.0 {
+                    best = (cost, direction, i);
+                }
+            }
+        }
+        let (_, direction, split_index) = best;
+        sort(entries, direction);
+        split_index
+    }
+
     pub fn centroid<K: DimIndex + Default, E: GetCenter<K>>(entries: &[E]) -> K {
         let mut centroid = K::default();
         for i in 0..K::NUM_DIMENSIONS {
@@ -649,6 +1505,127 @@ mod util {
     }
 }
 
+mod bulk {
+    use super::{util::direction_of_max_variance, DimIndex, Distance, Entry, SsNode};
+
+    // node of the given height (1 is a leaf) holding all entries, which must fit into it
+    pub fn build<P, K: Default + DimIndex + Distance + PartialEq, const M: usize>(
+        entries: Vec<Entry<P, K>>,
+        height: usize,
+    ) -> SsNode<P, K, M> {
+        if height == 1 {
+            return SsNode::from_entries(entries.into_iter().collect());
+        }
+        // as few children as possible, so that the nodes below are at least half full
+        let child_capacity = M.pow(height as u32 - 1);
+        let num_children = entries.len().div_ceil(child_capacity);
+        let mut groups = Vec::with_capacity(num_children);
+        split(entries, num_children, &mut groups);
+        SsNode::from_nodes(
+            groups
+                .into_iter()
+                .map(|group| build(group, height - 1))
+                .collect(),
+        )
+    }
+
+    // split into `n` groups of (almost) the same size by halving along the direction of maximum variance
+    fn split<P, K: DimIndex>(
+        mut entries: Vec<Entry<P, K>>,
+        n: usize,
+        out: &mut Vec<Vec<Entry<P, K>>>,
+    ) {
+        if n == 1 {
+            out.push(entries);
+            return;
+        }
+        let direction = direction_of_max_variance(&entries);
+        entries.sort_by(|e1, e2| e1.center[direction].total_cmp(&e2.center[direction]));
+        let n1 = n / 2;
+        let entries2 = entries.split_off(entries.len() * n1 / n);
+        split(entries, n1, out);
+        split(entries2, n - n1, out);
+    }
+}
+
+mod pairs {
+    use super::{DimIndex, Distance, Entry, EntryPair, SsNode, SsNodeLinks};
+
+    // same tests as find_entries_within_radius
+    fn entries_intersect<P, Q, K: Distance>(e1: &Entry<P, K>, e2: &Entry<Q, K>) -> bool {
+        e1.center.distance(&e2.center) < e1.radius + e2.radius
+    }
+
+    fn nodes_intersect<P, Q, K: Distance + DimIndex + PartialEq, const M: usize>(
+        n1: &SsNode<P, K, M>,
+        n2: &SsNode<Q, K, M>,
+    ) -> bool {
+        n1.centroid.distance(&n2.centroid) <= n1.radius + n2.radius
+    }
+
+    // pairs within the subtree of `node`
+    pub fn self_join<'a, P, K: Distance + DimIndex + PartialEq, const M: usize>(
+        node: &'a SsNode<P, K, M>,
+        out: &mut Vec<EntryPair<'a, P, P, K>>,
+    ) {
+        match &node.links {
+            SsNodeLinks::Leaf(entries) => {
+                for (i, e1) in entries.iter().enumerate() {
+                    for e2 in &entries[i + 1..] {
+                        if entries_intersect(e1, e2) {
+                            out.push((e1, e2));
+                        }
+                    }
+                }
+            }
+            SsNodeLinks::Inner(nodes) => {
+                for (i, n1) in nodes.iter().enumerate() {
+                    self_join(n1, out);
+                    for n2 in &nodes[i + 1..] {
+                        join(n1, n2, out);
+                    }
+                }
+            }
+        }
+    }
+
+    // pairs with one entry from each subtree. The larger node is expanded first, the subtrees may have different
+    // heights.
+    pub fn join<'a, P, Q, K: Distance + DimIndex + PartialEq, const M: usize>(
+        n1: &'a SsNode<P, K, M>,
+        n2: &'a SsNode<Q, K, M>,
+        out: &mut Vec<EntryPair<'a, P, Q, K>>,
+    ) {
+        if !nodes_intersect(n1, n2) {
+            return;
+        }
+        match (&n1.links, &n2.links) {
+            (SsNodeLinks::Leaf(entries1), SsNodeLinks::Leaf(entries2)) => {
+                for e1 in entries1.iter() {
+                    for e2 in entries2.iter() {
+                        if entries_intersect(e1, e2) {
+                            out.push((e1, e2));
+                        }
+                    }
+                }
+            }
+            (SsNodeLinks::Inner(children), SsNodeLinks::Leaf(_)) => {
+                children.iter().for_each(|child| join(child, n2, out));
+            }
+            (SsNodeLinks::Leaf(_), SsNodeLinks::Inner(children)) => {
+                children.iter().for_each(|child| join(n1, child, out));
+            }
+            (SsNodeLinks::Inner(children1), SsNodeLinks::Inner(children2)) => {
+                if n1.radius >= n2.radius {
+                    children1.iter().for_each(|child| join(child, n2, out));
+                } else {
+                    children2.iter().for_each(|child| join(n1, child, out));
+                }
+            }
+        }
+    }
+}
+
 mod leaf {
     use super::{
         util::{centroid, direction_of_max_variance, variance_along_direction},
@@ -698,7 +1675,7 @@ mod inner {
 
     use super::{
         util::{centroid, direction_of_max_variance, variance_along_direction},
-        DimIndex, Distance, SsNode, SsNodeLinks,
+        DimIndex, Distance, SsNode, SsNodeLinks, Tracker,
     };
 
     pub fn centroid_and_radius<P, K: DimIndex + Distance + PartialEq + Default, const M: usize>(
@@ -765,11 +1742,37 @@ mod inner {
         closest_sibling
     }
 
-    pub fn borrow_from_sibling<P, K: Distance + Default + DimIndex + PartialEq, const M: usize>(
+    // borrow from or merge with a sibling after a removal left `node_to_fix` underfull
+    pub fn fix_underfull_child<
+        P,
+        K: Distance + Default + DimIndex + PartialEq,
+        T: Tracker<P>,
+        const M: usize,
+    >(
+        nodes: &mut ArrayVec<SsNode<P, K, M>, M>,
+        node_to_fix: usize,
+        m: usize,
+        tracker: &mut T,
+    ) {
+        if let Some(sibling_to_borrow_from) = find_sibling_to_borrow_from(nodes, node_to_fix, m) {
+            borrow_from_sibling(nodes, node_to_fix, sibling_to_borrow_from, tracker);
+        } else if let Some(sibling_to_merge_to) = find_sibling_to_merge_to(nodes, node_to_fix, m) {
+            // no sibling to borrow from -> merge
+            merge_siblings(nodes, node_to_fix, sibling_to_merge_to, tracker);
+        }
+    }
+
+    pub fn borrow_from_sibling<
+        P,
+        K: Distance + Default + DimIndex + PartialEq,
+        T: Tracker<P>,
+        const M: usize,
+    >(
         nodes: &mut [SsNode<P, K, M>],
         node_to_fix: usize,
 
         sibling_to_borrow_from: usize,
+        tracker: &mut T,
     ) {
         // found sibling to borrow from
         let to_fix_centroid = &nodes[node_to_fix].centroid;
@@ -786,6 +1789,7 @@ mod inner {
                 }
                 let node = nodes2.remove(closest_node.unwrap());
                 nodes[sibling_to_borrow_from].update_bounding_envelope();
+                tracker.node_moved(node.id, Some(nodes[node_to_fix].id));
 
                 match &mut nodes[node_to_fix].links {
                     SsNodeLinks::Inner(fix_nodes) => fix_nodes.push(node),
@@ -809,6 +1813,7 @@ mod inner {
                 // );
                 let point = points.remove(closest_point.unwrap());
                 nodes[sibling_to_borrow_from].update_bounding_envelope();
+                tracker.entry_moved(&point.payload, nodes[node_to_fix].id);
                 match &mut nodes[node_to_fix].links {
                     SsNodeLinks::Inner(_) => panic!("unbalanced tree"),
                     SsNodeLinks::Leaf(fix_points) => fix_points.push(point),
@@ -845,10 +1850,17 @@ mod inner {
         closest_sibling
     }
 
-    pub fn merge_siblings<P, K: Default + Distance + DimIndex + PartialEq, const M: usize>(
+    // the merged node keeps the id of the first one
+    pub fn merge_siblings<
+        P,
+        K: Default + Distance + DimIndex + PartialEq,
+        T: Tracker<P>,
+        const M: usize,
+    >(
         nodes: &mut ArrayVec<SsNode<P, K, M>, M>,
         mut node_index_1: usize,
         mut node_index_2: usize,
+        tracker: &mut T,
     ) {
         if node_index_1 > node_index_2 {
             // remove node with larger index first
@@ -856,7 +1868,11 @@ mod inner {
         }
         let node_2 = nodes.remove(node_index_2);
         let node_1 = nodes.remove(node_index_1);
-        let node = merge(node_1, node_2);
+        let (id, dropped_id) = (node_1.id, node_2.id);
+        let mut node = merge(node_1, node_2);
+        node.id = id;
+        tracker.node_dropped(dropped_id);
+        node.report_children(tracker);
         nodes.push(node);
     }
 
@@ -880,8 +1896,9 @@ mod inner {
 
 #[cfg(test)]
 mod test {
+    use super::Distance;
     use super::Entry;
-    use super::SsTree;
+    use super::{SsNode, SsNodeLinks, SsTree, ValidationError};
 
     impl<P, K: PartialEq> PartialEq for Entry<P, K> {
         fn eq(&self, other: &Self) -> bool {
@@ -928,9 +1945,647 @@ mod test {
         );
         assert!(out.is_empty());
     }
+
+    #[test]
+    fn test_find_k_nearest() {
+        let mut tree = SsTree::<usize, [f32; 2], 8>::new(4);
+        let mut points = Vec::new();
+        for i in 0..400 {
+            // scrambled, so that the tree does not degenerate from sorted input
+            let (x, y) = ((i * 7) % 20, (i * 13) % 23);
+            let center = [x as f32 * 1.5, y as f32 * 0.5];
+            let radius = (i % 3) as f32 * 0.1;
+            points.push((center, radius));
+            tree.insert(i, center, radius);
+        }
+
+        let target = [10.2, 4.7];
+        let nearest = tree.find_k_nearest(&target, 10);
+        let mut expected = points
+            .iter()
+            .map(|(center, radius)| (center.distance(&target) - radius).max(0.0))
+            .collect::<Vec<_>>();
+        expected.sort_by(f32::total_cmp);
+        let distances = nearest.iter().map(|(d, _)| *d).collect::<Vec<_>>();
+        assert_eq!(distances, expected[..10]);
+        for (distance, entry) in nearest {
+            let (center, radius) = points[entry.payload];
+            assert_eq!(distance, (center.distance(&target) - radius).max(0.0));
+        }
+
+        let within = tree.find_k_nearest_within(&target, 100, 1.0);
+        assert_eq!(within.len(), expected.iter().filter(|d| **d <= 1.0).count());
+        assert!(tree.find_k_nearest(&target, 0).is_empty());
+        assert!(SsTree::<(), [f32; 2], 8>::new(4)
+            .find_k_nearest(&target, 1)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_find_entries_intersecting_ray() {
+        let mut tree = SsTree::<usize, [f32; 2], 8>::new(4);
+        for i in 0..400 {
+            // scrambled 20x20 grid
+            let j = (i * 7) % 400;
+            let (x, y) = (j % 20, j / 20);
+            tree.insert(i, [x as f32, y as f32], 0.25);
+        }
+
+        // along the row y = 3, starting between x = 4 and x = 5
+        let hits = tree.find_entries_intersecting_ray(&[4.5, 3.0], &[1.0, 0.0], 100.0);
+        let ts = hits.iter().map(|(t, _)| *t).collect::<Vec<_>>();
+        let expected = (5..20).map(|x| x as f32 - 4.75).collect::<Vec<_>>();
+        assert_eq!(ts, expected);
+        assert!(hits.iter().all(|(_, entry)| entry.center[1] == 3.0));
+
+        // limited length, starting inside of an entry
+        let hits = tree.find_entries_intersecting_ray(&[5.1, 3.0], &[-2.0, 0.0], 1.0);
+        let centers = hits.iter().map(|(_, e)| e.center).collect::<Vec<_>>();
+        assert_eq!(centers, vec![[5.0, 3.0], [4.0, 3.0], [3.0, 3.0]]);
+        assert_eq!(hits[0].0, 0.0);
+
+        // diagonal segment passing between the entries
+        let hits = tree.find_entries_intersecting_segment(&[0.5, 0.0], &[3.5, 3.0]);
+        assert!(hits.is_empty());
+        let hits = tree.find_entries_intersecting_segment(&[0.0, 0.0], &[3.0, 3.0]);
+        assert_eq!(hits.len(), 4);
+        assert!(hits.windows(2).all(|w| w[0].0 <= w[1].0));
+    }
+
+    #[test]
+    fn test_bulk_load() {
+        // leaves all at the same depth, all nodes except the root at least half full, children inside their parent
+        fn check<P>(node: &SsNode<P, [f32; 2], 8>, is_root: bool) -> usize {
+            let contains = |center: &[f32; 2], radius: f32| {
+                node.centroid.distance(center) + radius <= node.radius + 1e-3
+            };
+            match &node.links {
+                SsNodeLinks::Leaf(entries) => {
+                    assert!(is_root || entries.len() >= 4);
+                    assert!(entries.iter().all(|e| contains(&e.center, e.radius)));
+                    1
+                }
+                SsNodeLinks::Inner(nodes) => {
+                    assert!(nodes.len() >= if is_root { 2 } else { 4 });
+                    assert!(nodes.iter().all(|n| contains(&n.centroid, n.radius)));
+                    let depths = nodes.iter().map(|n| check(n, false)).collect::<Vec<_>>();
+                    assert!(depths.iter().all(|d| *d == depths[0]));
+                    depths[0] + 1
+                }
+            }
+        }
+
+        for n in [0, 1, 8, 9, 64, 65, 500, 1000] {
+            let entries = (0..n)
+                .map(|i| {
+                    let j = (i * 7) % n.max(1);
+                    Entry::new([(j % 31) as f32, (j / 31) as f32 * 0.7], 0.1, i)
+                })
+                .collect::<Vec<_>>();
+            let tree = SsTree::<usize, [f32; 2], 8>::bulk_load(entries);
+            assert_eq!(tree.is_empty(), n == 0);
+            assert_eq!(check(&tree.root, true), tree.get_height());
+            assert_eq!(tree.validate(), Ok(()));
+            assert_eq!(tree.root.count_nodes().0, n);
+            assert_eq!(
+                tree.find_k_nearest(&[0.0, 0.0], usize::MAX).len(),
+                n,
+                "all entries reachable"
+            );
+        }
+
+        // the bulk loaded tree can be modified like any other
+        let mut tree = SsTree::<usize, [f32; 2], 8>::bulk_load(
+            (0..100)
+                .map(|i| Entry::new([i as f32, 0.0], 0.1, i))
+                .collect(),
+        );
+        tree.insert(100, [-1.0, 0.0], 0.1);
+        assert!(tree.remove_if(&[50.0, 0.0], 0.1, |p| *p == 50).is_some());
+        let nearest = tree.find_k_nearest(&[49.8, 0.0], 1);
+        assert_eq!(nearest[0].1.payload, 49);
+        assert_eq!(tree.root.count_nodes().0, 100);
+    }
+
+    #[test]
+    fn test_update_if() {
+        let mut tree = SsTree::<usize, [f32; 2], 8>::new(4);
+        for i in 0..100 {
+            tree.insert(i, [(i % 10) as f32, (i / 10) as f32], 0.25);
+        }
+        let find = |tree: &SsTree<usize, [f32; 2], 8>, center: &[f32; 2], radius: f32| {
+            let mut out = Vec::new();
+            tree.find_entries_within_radius(center, radius, &mut out);
+            out.iter().map(|e| e.payload).collect::<Vec<_>>()
+        };
+
+        // a small move stays in the leaf
+        assert!(tree.update_if(&[5.0, 5.0], 0.25, |p| *p == 55, [5.1, 5.0], 0.2));
+        assert_eq!(find(&tree, &[5.1, 5.0], 0.01), vec![55]);
+        // moving across the tree re-inserts
+        assert!(tree.update_if(&[5.1, 5.0], 0.2, |p| *p == 55, [20.0, 20.0], 0.5));
+        assert_eq!(find(&tree, &[20.0, 20.0], 0.01), vec![55]);
+        assert!(find(&tree, &[5.0, 5.0], 0.01).is_empty());
+        // unknown payload or wrong position
+        assert!(!tree.update_if(&[1.0, 1.0], 0.25, |p| *p == 1000, [0.0, 0.0], 0.1));
+        assert!(!tree.update_if(&[1.0, 1.0], 0.25, |p| *p == 55, [0.0, 0.0], 0.1));
+        assert_eq!(tree.root.count_nodes().0, 100);
+
+        // the envelopes still contain all entries
+        for i in (0..100).filter(|i| *i != 55) {
+            assert_eq!(
+                find(&tree, &[(i % 10) as f32, (i / 10) as f32], 0.01),
+                vec![i]
+            );
+        }
+    }
+
+    #[test]
+    fn test_spatial_index() {
+        use super::{SpatialBounds, SpatialIndex, SpatialIndexError};
+        use bevy::prelude::{Entity, Vec3};
+
+        let bounds = |x: f32| SpatialBounds {
+            center: Vec3::new(x, 0.0, 0.0),
+            radius: 0.5,
+        };
+        let mut index = SpatialIndex::default();
+        for i in 0..50 {
+            index.update(Entity::from_raw(i), bounds(i as f32)).unwrap();
+        }
+        let e = Entity::from_raw(10);
+        // no need to know the old bounds for updates and removal
+        index.update(e, bounds(100.0)).unwrap();
+        assert_eq!(index.get_bounds(e).unwrap().center.x, 100.0);
+        assert_eq!(index.query(bounds(100.0)).collect::<Vec<_>>(), vec![e]);
+        assert_eq!(index.remove(e).unwrap().center.x, 100.0);
+        assert!(index.query(bounds(100.0)).next().is_none());
+        assert!(matches!(
+            index.remove(e),
+            Err(SpatialIndexError::UnknownEntity(_))
+        ));
+        // a failed remove keeps the bounds, like a failed update
+        let e = Entity::from_raw(20);
+        let leaf = index.locations.leaves.remove(&e).unwrap();
+        assert!(matches!(
+            index.remove(e),
+            Err(SpatialIndexError::Inconsistent(_))
+        ));
+        assert_eq!(index.get_bounds(e).unwrap().center.x, 20.0);
+        index.locations.leaves.insert(e, leaf);
+        assert_eq!(index.remove(e).unwrap().center.x, 20.0);
+    }
+
+    #[test]
+    fn test_spatial_index_nearest() {
+        use super::{LineSegment, NearestTarget, SpatialBounds, SpatialIndex};
+        use bevy::prelude::{Entity, Vec3};
+        use std::collections::BTreeSet;
+
+        // a 10x10x10 grid, seen from above (-y) like in an ortho view
+        let mut index = SpatialIndex::default();
+        let position =
+            |i: u32| Vec3::new((i % 10) as f32, (i / 100) as f32, ((i / 10) % 10) as f32);
+        for i in 0..1000 {
+            let bounds = SpatialBounds {
+                center: position(i),
+                radius: 0.1,
+            };
+            index.update(Entity::from_raw(i), bounds).unwrap();
+        }
+        fn nearest(
+            index: &SpatialIndex,
+            target: impl NearestTarget,
+            k: usize,
+            max_distance: f32,
+        ) -> Vec<(u32, f32)> {
+            index
+                .find_k_nearest_within(target, k, max_distance)
+                .map(|(entity, distance)| (entity.index(), distance))
+                .collect()
+        }
+        let found = nearest(&index, Vec3::new(3.1, 5.0, 4.2), 1, f32::INFINITY);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, 543);
+        assert!((found[0].1 - 0.12360679).abs() < 1e-5);
+        assert!(nearest(&index, Vec3::new(3.5, 5.0, 4.5), 10, 0.5).is_empty());
+
+        // the column below the cursor, within the visible depth 2..=4: in-plane distance, depth is ignored
+        let column = LineSegment {
+            start: Vec3::new(3.1, 2.0, 4.2),
+            end: Vec3::new(3.1, 4.0, 4.2),
+        };
+        let found = nearest(&index, column, 3, 0.5);
+        assert_eq!(
+            found.iter().map(|(i, _)| *i).collect::<BTreeSet<_>>(),
+            BTreeSet::from([243, 343, 443])
+        );
+        assert!(found.iter().all(|(_, d)| (d - 0.12360679).abs() < 1e-5));
+        // nothing outside of the visible depth, even with room left in k
+        assert_eq!(nearest(&index, column, 10, 0.5).len(), 3);
+    }
+
+    #[test]
+    fn test_spatial_index_frustum() {
+        use super::{SpatialBounds, SpatialIndex};
+        use bevy::{
+            prelude::*,
+            render::{camera::CameraProjection, primitives::Frustum},
+        };
+
+        // the same frustum bevy computes for a camera
+        let frustum = |projection: Projection, transform: Transform| {
+            let clip_from_world =
+                projection.get_clip_from_view() * transform.compute_matrix().inverse();
+            Frustum::from_clip_from_world_custom_far(
+                &clip_from_world,
+                &transform.translation,
+                &transform.back(),
+                projection.far(),
+            )
+        };
+        let mut index = SpatialIndex::default();
+        let positions = [
+            Vec3::new(10.0, 0.0, -5.0),
+            Vec3::new(11.05, 0.5, -5.0),
+            Vec3::new(10.0, -1.2, -5.0),
+            Vec3::new(10.0, 0.0, 5.0),
+            Vec3::new(0.0, 0.0, -5.0),
+            Vec3::new(13.5, 0.0, -10.0),
+            Vec3::new(10.0, 0.0, -2000.0),
+        ];
+        for (i, center) in positions.into_iter().enumerate() {
+            let bounds = SpatialBounds {
+                center,
+                radius: 0.1,
+            };
+            index.update(Entity::from_raw(i as u32), bounds).unwrap();
+        }
+        let query = |frustum: Frustum| {
+            let mut found = index
+                .query_frustum(&frustum)
+                .map(|entity| entity.index())
+                .collect::<Vec<_>>();
+            found.sort();
+            found
+        };
+
+        // looking down -z from (10, 0, 0), x and y in -1..=1 around the camera, up to 1000 deep
+        let camera = Transform::from_xyz(10.0, 0.0, 0.0);
+        let ortho = Projection::Orthographic(OrthographicProjection::default());
+        assert_eq!(query(frustum(ortho, camera)), vec![0, 1]);
+
+        // 45 degrees vertical fov: 4.14 wide at 10 deep
+        let perspective = Projection::Perspective(PerspectiveProjection::default());
+        assert_eq!(query(frustum(perspective, camera)), vec![0, 1, 2, 5]);
+    }
+
+    #[test]
+    fn test_spatial_index_moves() {
+        use super::{SpatialBounds, SpatialIndex};
+        use bevy::prelude::{Entity, Vec3};
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        // entities wander around while others come and go, so their leaves are split, merged and replaced by a
+        // rebuild in between. The recorded leaves have to keep up with that.
+        let mut rng = StdRng::seed_from_u64(44);
+        let mut index = SpatialIndex::default();
+        let mut entities = Vec::new();
+        let mut next_entity = 0;
+        let check = |index: &SpatialIndex, entities: &[Entity]| {
+            index.sstree.validate().unwrap();
+            let (num_entries, num_nodes) = index.sstree.root.count_nodes();
+            assert_eq!(num_entries, entities.len());
+            assert_eq!(index.locations.leaves.len(), entities.len());
+            // no parents of dropped nodes are left behind
+            assert_eq!(index.locations.parents.len(), num_nodes - 1);
+            for entity in entities {
+                let bounds = index.get_bounds(*entity).unwrap();
+                let found = index.query(SpatialBounds {
+                    center: bounds.center,
+                    radius: 0.01,
+                });
+                assert!(found.into_iter().any(|e| e == *entity));
+            }
+        };
+        for round in 0..40 {
+            for _ in 0..rng.gen_range(0..40) {
+                let entity = Entity::from_raw(next_entity);
+                next_entity += 1;
+                let center = Vec3::new(rng.gen_range(-50.0..50.0), rng.gen_range(-50.0..50.0), 0.0);
+                index
+                    .update(
+                        entity,
+                        SpatialBounds {
+                            center,
+                            radius: 0.5,
+                        },
+                    )
+                    .unwrap();
+                entities.push(entity);
+            }
+            for _ in 0..rng.gen_range(0..20).min(entities.len()) {
+                let entity = entities.swap_remove(rng.gen_range(0..entities.len()));
+                index.remove(entity).unwrap();
+            }
+            for entity in &entities {
+                let mut bounds = index.get_bounds(*entity).unwrap();
+                // mostly small steps that stay in the leaf, now and then a jump to somewhere else
+                bounds.center += if rng.gen_bool(0.9) {
+                    Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0)
+                } else {
+                    Vec3::new(rng.gen_range(-30.0..30.0), rng.gen_range(-30.0..30.0), 0.0)
+                };
+                index.update(*entity, bounds).unwrap();
+            }
+            if round == 20 {
+                let all = entities.iter().map(|e| (*e, index.get_bounds(*e).unwrap()));
+                index.rebuild(all.collect::<Vec<_>>());
+            }
+            check(&index, &entities);
+        }
+        assert!(index.sstree.get_height() > 2);
+    }
+
+    #[test]
+    fn test_find_entries_intersecting_region() {
+        let mut tree = SsTree::<usize, [f32; 2], 8>::new(4);
+        for i in 0..400 {
+            // scrambled 20x20 grid
+            let j = (i * 7) % 400;
+            tree.insert(j, [(j % 20) as f32, (j / 20) as f32], 0.25);
+        }
+        let sorted = |out: Vec<&Entry<usize, [f32; 2]>>| {
+            let mut payloads = out.iter().map(|e| e.payload).collect::<Vec<_>>();
+            payloads.sort();
+            payloads
+        };
+        let grid = |f: &dyn Fn(usize, usize) -> bool| {
+            (0..400).filter(|j| f(j % 20, j / 20)).collect::<Vec<_>>()
+        };
+
+        // box from (2.3, 5.1) to (6.1, 7.0): columns 3..=6 (2 is 0.05 too far away), rows 5..=7
+        let mut out = Vec::new();
+        tree.find_entries_intersecting_aabb(&[2.3, 5.1], &[6.1, 7.0], &mut out);
+        assert_eq!(
+            sorted(out),
+            grid(&|x, y| (3..=6).contains(&x) && (5..=7).contains(&y))
+        );
+
+        // x >= 10.2 reaches the spheres at x = 10
+        let mut out = Vec::new();
+        tree.find_entries_intersecting_half_space(&[1.0, 0.0], 10.2, &mut out);
+        assert_eq!(sorted(out), grid(&|x, _| x >= 10));
+
+        // diagonal half space x + y >= 30 (normalized), the spheres on x + y = 29 are 0.707 away
+        let n = std::f32::consts::FRAC_1_SQRT_2;
+        let mut out = Vec::new();
+        tree.find_entries_intersecting_half_space(&[n, n], 30.0 * n, &mut out);
+        assert_eq!(sorted(out), grid(&|x, y| x + y >= 30));
+
+        // the triangle x >= 1, y >= 1, x + y <= 4
+        let half_spaces = [([1.0, 0.0], 1.0), ([0.0, 1.0], 1.0), ([-n, -n], -4.0 * n)];
+        let mut out = Vec::new();
+        tree.find_entries_intersecting_frustum(&half_spaces, &mut out);
+        assert_eq!(sorted(out), grid(&|x, y| x >= 1 && y >= 1 && x + y <= 4));
+    }
+
+    #[test]
+    fn test_validate_stress() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        // random inserts, updates and removals. The tree stays valid and no entry gets lost.
+        let mut rng = StdRng::seed_from_u64(47);
+        let mut tree = SsTree::<usize, [f32; 2], 8>::new(4);
+        let mut entries = Vec::new();
+        let mut next_payload = 0;
+        for _ in 0..30 {
+            for _ in 0..rng.gen_range(0..150) {
+                let center = [rng.gen_range(-50.0..50.0), rng.gen_range(-50.0..50.0)];
+                let radius = rng.gen_range(0.1..3.0);
+                tree.insert(next_payload, center, radius);
+                entries.push((next_payload, center, radius));
+                next_payload += 1;
+            }
+            for _ in 0..rng.gen_range(0..20).min(entries.len()) {
+                let i = rng.gen_range(0..entries.len());
+                let (payload, center, radius) = &mut entries[i];
+                let new_center = [center[0] + rng.gen_range(-2.0..2.0), center[1]];
+                let new_radius = rng.gen_range(0.1..3.0);
+                assert!(tree.update_if(
+                    center,
+                    *radius,
+                    |p| *p == *payload,
+                    new_center,
+                    new_radius
+                ));
+                (*center, *radius) = (new_center, new_radius);
+            }
+            for _ in 0..rng.gen_range(0..120).min(entries.len()) {
+                let (payload, center, radius) =
+                    entries.swap_remove(rng.gen_range(0..entries.len()));
+                assert!(tree.remove_if(&center, radius, |p| *p == payload).is_some());
+            }
+            assert_eq!(tree.validate(), Ok(()));
+            assert_eq!(tree.root.count_nodes().0, entries.len());
+        }
+        for (payload, center, radius) in entries {
+            assert!(tree.remove_if(&center, radius, |p| *p == payload).is_some());
+        }
+        assert_eq!(tree.validate(), Ok(()));
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_configs() {
+        use super::{SplitStrategy, SsTreeConfig};
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(49);
+        let mut entries = (0..1000)
+            .map(|i| {
+                let center: [f32; 2] = [rng.gen_range(-50.0..50.0), rng.gen_range(-50.0..50.0)];
+                (i, center, rng.gen_range(0.1..2.0))
+            })
+            .collect::<Vec<_>>();
+        // coherent order, the case forced reinsertion is for
+        entries.sort_by(|(_, c1, _), (_, c2, _)| c1[0].total_cmp(&c2[0]));
+        let targets = (0..50)
+            .map(|_| [rng.gen_range(-50.0..50.0), rng.gen_range(-50.0..50.0)])
+            .collect::<Vec<_>>();
+
+        for split in [SplitStrategy::MinVariance, SplitStrategy::MinRadiusOverlap] {
+            for forced_reinsert in [false, true] {
+                let config = SsTreeConfig {
+                    split,
+                    forced_reinsert,
+                };
+                let mut tree = SsTree::<usize, [f32; 2], 8>::with_config(4, config);
+                for (payload, center, radius) in &entries {
+                    tree.insert(*payload, *center, *radius);
+                }
+                assert_eq!(tree.validate(), Ok(()), "{config:?}");
+                assert_eq!(tree.root.count_nodes().0, entries.len());
+                assert!(tree.metrics().num_nodes > entries.len() / 8);
+
+                for target in &targets {
+                    let mut out = Vec::new();
+                    tree.find_entries_within_radius(target, 5.0, &mut out);
+                    let mut found = out.iter().map(|e| e.payload).collect::<Vec<_>>();
+                    found.sort();
+                    let mut expected = entries
+                        .iter()
+                        .filter(|(_, center, radius)| center.distance(target) < 5.0 + radius)
+                        .map(|(payload, _, _)| *payload)
+                        .collect::<Vec<_>>();
+                    expected.sort();
+                    assert_eq!(found, expected, "{config:?}");
+                    assert!(tree.count_node_visits(target, 5.0) >= 1);
+                }
+                for (payload, center, radius) in &entries {
+                    assert!(tree.remove_if(center, *radius, |p| p == payload).is_some());
+                }
+                assert!(tree.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        fn first_child(tree: &mut SsTree<usize, [f32; 2], 8>) -> &mut SsNode<usize, [f32; 2], 8> {
+            let SsNodeLinks::Inner(nodes) = &mut tree.root.links else {
+                panic!("expecting inner root");
+            };
+            &mut nodes[0]
+        }
+
+        let mut tree = SsTree::<usize, [f32; 2], 8>::new(4);
+        for i in 0..100 {
+            tree.insert(i, [(i % 10) as f32, (i / 10) as f32], 0.5);
+        }
+        assert_eq!(tree.validate(), Ok(()));
+
+        // the children of the shrunk node stick out
+        first_child(&mut tree).radius *= 0.5;
+        assert_eq!(
+            tree.validate(),
+            Err(ValidationError::NotContained { depth: 3 })
+        );
+        first_child(&mut tree).update_bounding_envelope();
+        assert_eq!(tree.validate(), Ok(()));
+
+        let SsNodeLinks::Inner(children) = &mut first_child(&mut tree).links else {
+            panic!("expecting inner node");
+        };
+        children.truncate(1);
+        assert_eq!(
+            tree.validate(),
+            Err(ValidationError::Underfull {
+                depth: 2,
+                len: 1,
+                m: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_serde() {
+        let mut tree = SsTree::<usize, [f32; 2], 8>::new(4);
+        for i in 0..100 {
+            tree.insert(i, [(i % 10) as f32, (i / 10) as f32], 0.5);
+        }
+        let restored: SsTree<usize, [f32; 2], 8> =
+            ron::from_str(&ron::to_string(&tree).unwrap()).unwrap();
+        assert_eq!(restored.validate(), Ok(()));
+        assert_eq!(restored.get_height(), tree.get_height());
+        let payloads = |tree: &SsTree<usize, [f32; 2], 8>| {
+            tree.find_k_nearest(&[3.3, 4.4], 10)
+                .iter()
+                .map(|(_, e)| e.payload)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(payloads(&restored), payloads(&tree));
+    }
+
+    #[test]
+    fn test_overlapping_pairs() {
+        use super::{SpatialBounds, SpatialIndex};
+        use bevy::prelude::{Entity, Vec3};
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        use std::collections::BTreeSet;
+
+        let mut rng = StdRng::seed_from_u64(48);
+        let mut random_entries = |n: usize| {
+            (0..n)
+                .map(|i| {
+                    let center = [rng.gen_range(-30.0..30.0), rng.gen_range(-30.0..30.0)];
+                    Entry::new(center, rng.gen_range(0.1..2.0), i)
+                })
+                .collect::<Vec<_>>()
+        };
+        let intersect = |e1: &Entry<usize, [f32; 2]>, e2: &Entry<usize, [f32; 2]>| {
+            e1.center.distance(&e2.center) < e1.radius + e2.radius
+        };
+        let points = random_entries(500);
+        let others = random_entries(100);
+
+        let mut tree = SsTree::<usize, [f32; 2], 8>::new(4);
+        for e in &points {
+            tree.insert(e.payload, e.center, e.radius);
+        }
+        let mut expected = BTreeSet::new();
+        for (i, e1) in points.iter().enumerate() {
+            for e2 in &points[i + 1..] {
+                if intersect(e1, e2) {
+                    expected.insert((e1.payload, e2.payload));
+                }
+            }
+        }
+        let pairs = tree.overlapping_pairs();
+        assert_eq!(pairs.len(), expected.len(), "each pair once");
+        let pairs = pairs
+            .iter()
+            .map(|(e1, e2)| (e1.payload.min(e2.payload), e1.payload.max(e2.payload)))
+            .collect::<BTreeSet<_>>();
+        assert_eq!(pairs, expected);
+
+        // with another tree of a different height
+        let expected = others
+            .iter()
+            .flat_map(|e1| {
+                points
+                    .iter()
+                    .filter(|e2| intersect(e1, e2))
+                    .map(|e2| (e1.payload, e2.payload))
+            })
+            .collect::<BTreeSet<_>>();
+        let other_tree = SsTree::<usize, [f32; 2], 8>::bulk_load(others);
+        let pairs = other_tree.overlapping_pairs_with(&tree);
+        assert_eq!(pairs.len(), expected.len());
+        let pairs = pairs
+            .iter()
+            .map(|(e1, e2)| (e1.payload, e2.payload))
+            .collect::<BTreeSet<_>>();
+        assert_eq!(pairs, expected);
+
+        let bounds = |x: f32| SpatialBounds {
+            center: Vec3::new(x, 0.0, 0.0),
+            radius: 0.6,
+        };
+        let mut index = SpatialIndex::default();
+        for i in 0..20 {
+            index.update(Entity::from_raw(i), bounds(i as f32)).unwrap();
+        }
+        assert_eq!(index.overlapping_pairs().count(), 19);
+        let pairs = index
+            .overlapping_pairs_of([Entity::from_raw(5), Entity::from_raw(6)])
+            .map(|(e1, e2)| (e1.index(), e2.index()))
+            .collect::<BTreeSet<_>>();
+        assert_eq!(pairs, BTreeSet::from([(5, 4), (5, 6), (6, 5), (6, 7)]));
+    }
 }
 
-use bevy::prelude::*;
+use bevy::{prelude::*, render::primitives::Frustum, utils::HashMap};
 
 #[derive(Debug, Clone, Copy, bevy::reflect::Reflect)]
 pub struct SpatialBounds {
@@ -938,32 +2593,198 @@ pub struct SpatialBounds {
     pub radius: f32,
 }
 
+// what SpatialIndex::find_k_nearest_within measures the distance to
+pub trait NearestTarget {
+    fn distance(&self, point: Vec3) -> f32;
+}
+
+impl NearestTarget for Vec3 {
+    fn distance(&self, point: Vec3) -> f32 {
+        Vec3::distance(*self, point)
+    }
+}
+
+// e.g. the line under the cursor in an ortho view, limited to the visible depth. The distance to it is the distance
+// in the view plane for everything in view.
+#[derive(Debug, Clone, Copy)]
+pub struct LineSegment {
+    pub start: Vec3,
+    pub end: Vec3,
+}
+
+impl NearestTarget for LineSegment {
+    fn distance(&self, point: Vec3) -> f32 {
+        let dir = self.end - self.start;
+        let t = (point - self.start).dot(dir) / dir.length_squared();
+        let closest = self.start
+            + dir
+                * if t.is_finite() {
+                    t.clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+        Vec3::distance(closest, point)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SpatialIndexError {
+    #[error("entity {0:?} is not in the spatial index")]
+    UnknownEntity(Entity),
+
+    #[error("entity {0:?} is missing from the tree at its recorded location")]
+    Inconsistent(Entity),
+}
+
+// where the entities are in the tree: the leaf of every entity and the parent of every node but the root. Kept up to
+// date by the tree as a Tracker, so updates and removals go straight to the leaf.
+#[derive(Default)]
+struct TreeLocations {
+    // 0 is the id of the root of a new tree
+    last_id: NodeId,
+    leaves: HashMap<Entity, NodeId>,
+    parents: HashMap<NodeId, NodeId>,
+}
+
+impl TreeLocations {
+    // ids from the root down to the leaf of `entity`
+    fn path(&self, entity: Entity) -> Option<Vec<NodeId>> {
+        let mut path = vec![*self.leaves.get(&entity)?];
+        while let Some(parent) = self.parents.get(path.last().unwrap()) {
+            path.push(*parent);
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+impl Tracker<Entity> for TreeLocations {
+    fn new_node_id(&mut self) -> NodeId {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    fn entry_moved(&mut self, payload: &Entity, leaf: NodeId) {
+        self.leaves.insert(*payload, leaf);
+    }
+
+    fn node_moved(&mut self, node: NodeId, parent: Option<NodeId>) {
+        match parent {
+            Some(parent) => self.parents.insert(node, parent),
+            None => self.parents.remove(&node),
+        };
+    }
+
+    fn node_dropped(&mut self, node: NodeId) {
+        self.parents.remove(&node);
+    }
+}
+
 #[derive(Resource, Default)]
 pub struct SpatialIndex {
     sstree: SsTree<Entity, Vec3, 8>,
+    // current bounds of every entity, so the entries can be found without the caller knowing the old bounds
+    bounds: HashMap<Entity, SpatialBounds>,
+    locations: TreeLocations,
 }
 
 impl SpatialIndex {
     pub fn clear(&mut self) {
         self.sstree = SsTree::default();
+        self.bounds.clear();
+        self.locations = TreeLocations::default();
     }
-    pub fn update(&mut self, entity: Entity, from: Option<SpatialBounds>, to: SpatialBounds) {
-        if let Some(SpatialBounds { center, radius }) = from {
-            if self
-                .sstree
-                .remove_if(&center, radius, |e| *e == entity)
-                .is_none()
-            {
-                error!("failed to remove brush from spatial index for update");
-                panic!("aborting");
-            }
+
+    // insert or move an entity. Moves stay in the entity's leaf unless they leave the sphere of its parent node.
+    pub fn update(
+        &mut self,
+        entity: Entity,
+        bounds: SpatialBounds,
+    ) -> Result<(), SpatialIndexError> {
+        let Some(old) = self.bounds.insert(entity, bounds) else {
+            self.sstree.insert_entry_tracked(
+                Entry::new(bounds.center, bounds.radius, entity),
+                &mut self.locations,
+            );
+            return Ok(());
+        };
+        let updated = self.locations.path(entity).is_some_and(|path| {
+            self.sstree.update_at(
+                &path,
+                |e| *e == entity,
+                bounds.center,
+                bounds.radius,
+                &mut self.locations,
+            )
+        });
+        if !updated {
+            self.bounds.insert(entity, old);
+            return Err(SpatialIndexError::Inconsistent(entity));
         }
-        self.sstree.insert(entity, to.center, to.radius);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Result<SpatialBounds, SpatialIndexError> {
+        let bounds = self
+            .get_bounds(entity)
+            .ok_or(SpatialIndexError::UnknownEntity(entity))?;
+        // only forget the bounds once the tree entry is gone, a failed remove leaves the index as it was
+        let path = self
+            .locations
+            .path(entity)
+            .ok_or(SpatialIndexError::Inconsistent(entity))?;
+        self.sstree
+            .remove_at(&path, |e| *e == entity, &mut self.locations)
+            .ok_or(SpatialIndexError::Inconsistent(entity))?;
+        self.locations.leaves.remove(&entity);
+        self.bounds.remove(&entity);
+        Ok(bounds)
+    }
+
+    pub fn get_bounds(&self, entity: Entity) -> Option<SpatialBounds> {
+        self.bounds.get(&entity).copied()
+    }
+
+    // replace the content with `entries` in one go, much better than one update per entity after large loads
+    pub fn rebuild(&mut self, entries: impl IntoIterator<Item = (Entity, SpatialBounds)>) {
+        self.bounds = entries.into_iter().collect();
+        let entries = self
+            .bounds
+            .iter()
+            .map(|(entity, bounds)| Entry::new(bounds.center, bounds.radius, *entity))
+            .collect();
+        self.locations = TreeLocations::default();
+        self.sstree = SsTree::bulk_load_tracked(entries, &mut self.locations);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bounds.is_empty()
+    }
+
+    pub fn query_aabb(&self, min: Vec3, max: Vec3) -> impl Iterator<Item = Entity> + '_ {
+        let mut out = Vec::new();
+        self.sstree
+            .find_entries_intersecting_aabb(&min, &max, &mut out);
+        out.into_iter().map(|e| e.payload)
+    }
+
+    // entities reaching into the half space `dot(normal, x) >= w`
+    pub fn query_half_space(&self, normal: Vec3, w: f32) -> impl Iterator<Item = Entity> + '_ {
+        let mut out = Vec::new();
+        self.sstree
+            .find_entries_intersecting_half_space(&normal, w, &mut out);
+        out.into_iter().map(|e| e.payload)
     }
 
-    pub fn remove(&mut self, entity: Entity, bounds: SpatialBounds) {
+    pub fn query_frustum(&self, frustum: &Frustum) -> impl Iterator<Item = Entity> + '_ {
+        // bevy half spaces contain the points with `dot(normal, x) + d >= 0`
+        let half_spaces = frustum
+            .half_spaces
+            .map(|half_space| (Vec3::from(half_space.normal()), -half_space.d()));
+        let mut out = Vec::new();
         self.sstree
-            .remove_if(&bounds.center, bounds.radius, |e| *e == entity);
+            .find_entries_intersecting_frustum(&half_spaces, &mut out);
+        out.into_iter().map(|e| e.payload)
     }
 
     pub fn query(&self, bounds: SpatialBounds) -> impl Iterator<Item = Entity> + '_ {
@@ -972,4 +2793,62 @@ impl SpatialIndex {
             .find_entries_within_radius(&bounds.center, bounds.radius, &mut out);
         out.into_iter().map(|e| e.payload)
     }
+
+    // every pair of entities with intersecting bounds, each pair once
+    pub fn overlapping_pairs(&self) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+        self.sstree
+            .overlapping_pairs()
+            .into_iter()
+            .map(|(e1, e2)| (e1.payload, e2.payload))
+    }
+
+    // pairs of entities with intersecting bounds, the first one from `entities`. Pairs within `entities` are reported
+    // in both orders, unknown entities are ignored. The subset is joined with the index as a tree of its own, so this
+    // is one traversal instead of a query per entity.
+    pub fn overlapping_pairs_of(
+        &self,
+        entities: impl IntoIterator<Item = Entity>,
+    ) -> impl Iterator<Item = (Entity, Entity)> {
+        let subset = entities
+            .into_iter()
+            .filter_map(|entity| Some((entity, *self.bounds.get(&entity)?)))
+            .collect::<HashMap<_, _>>()
+            .into_iter()
+            .map(|(entity, bounds)| Entry::new(bounds.center, bounds.radius, entity))
+            .collect();
+        SsTree::<Entity, Vec3, 8>::bulk_load(subset)
+            .overlapping_pairs_with(&self.sstree)
+            .into_iter()
+            .map(|(e1, e2)| (e1.payload, e2.payload))
+            .filter(|(entity, other)| entity != other)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    // up to k entities closest to `target` with their distances, nearest first. Only entities up to `max_distance`
+    // away are found.
+    pub fn find_k_nearest_within(
+        &self,
+        target: impl NearestTarget,
+        k: usize,
+        max_distance: f32,
+    ) -> impl Iterator<Item = (Entity, f32)> + '_ {
+        self.sstree
+            .find_k_nearest_by(k, max_distance, |center| target.distance(*center))
+            .into_iter()
+            .map(|(distance, e)| (e.payload, distance))
+    }
+
+    // candidates for a raycast: the entities whose bounds are hit by `ray` within `max_distance`, with the distance
+    // where the ray enters the bounds, nearest first
+    pub fn raycast(
+        &self,
+        ray: Ray3d,
+        max_distance: f32,
+    ) -> impl Iterator<Item = (Entity, f32)> + '_ {
+        self.sstree
+            .find_entries_intersecting_ray(&ray.origin, &*ray.direction, max_distance)
+            .into_iter()
+            .map(|(distance, e)| (e.payload, distance))
+    }
 }
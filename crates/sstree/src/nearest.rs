@@ -0,0 +1,96 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+// best-first traversal for the k-nearest-neighbour and ray queries, shared by the tree variants. Nodes and entries
+// are queued by a lower bound of their distance to the target (or along the ray), so entries come out of the queue in
+// order of their distance.
+
+enum Candidate<N, E> {
+    Node(N),
+    Entry(E),
+}
+
+struct Queued<N, E> {
+    distance: f32,
+    candidate: Candidate<N, E>,
+}
+
+impl<N, E> PartialEq for Queued<N, E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<N, E> Eq for Queued<N, E> {}
+
+impl<N, E> PartialOrd for Queued<N, E> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N, E> Ord for Queued<N, E> {
+    // reversed, BinaryHeap is a max-heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance)
+    }
+}
+
+pub(crate) struct NearestQueue<N, E> {
+    queue: BinaryHeap<Queued<N, E>>,
+    k: usize,
+    max_distance: f32,
+    nearest: Vec<(f32, E)>,
+}
+
+impl<N, E> NearestQueue<N, E> {
+    pub fn new(k: usize, max_distance: f32) -> Self {
+        Self {
+            queue: BinaryHeap::new(),
+            k,
+            max_distance,
+            nearest: Vec::new(),
+        }
+    }
+
+    pub fn push_node(&mut self, distance: f32, node: N) {
+        self.push(distance, Candidate::Node(node));
+    }
+
+    pub fn push_entry(&mut self, distance: f32, entry: E) {
+        self.push(distance, Candidate::Entry(entry));
+    }
+
+    fn push(&mut self, distance: f32, candidate: Candidate<N, E>) {
+        if distance <= self.max_distance {
+            self.queue.push(Queued {
+                distance,
+                candidate,
+            });
+        }
+    }
+
+    // next node to expand. Entries that reach the front of the queue are final results. None once k entries are
+    // found or nothing within the max distance is left.
+    pub fn next_node(&mut self) -> Option<N> {
+        while self.nearest.len() < self.k {
+            let Queued {
+                distance,
+                candidate,
+            } = self.queue.pop()?;
+            match candidate {
+                Candidate::Node(node) => return Some(node),
+                Candidate::Entry(entry) => self.nearest.push((distance, entry)),
+            }
+        }
+        None
+    }
+
+    pub fn into_nearest(self) -> Vec<(f32, E)> {
+        self.nearest
+    }
+}
+
+// distance of a point to a sphere, given its distance to the center of the sphere. 0 inside of the sphere.
+pub(crate) fn sphere_distance(distance_to_center: f32, radius: f32) -> f32 {
+    (distance_to_center - radius).max(0.0)
+}
@@ -477,7 +477,7 @@ pub struct GamePluginGroup;
 impl PluginGroup for GamePluginGroup {
     fn build(self) -> bevy::app::PluginGroupBuilder {
         PluginGroupBuilder::start::<Self>()
-            .add(player_controller::PlayerControllerPlugin)
+            .add(player_controller::PlayerControllerPlugin::default())
             .add(GameplayPlugin)
     }
 }
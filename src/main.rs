@@ -1,8 +1,23 @@
+use std::path::PathBuf;
+
 use bevy::{prelude::*, render::texture::ImageSamplerDescriptor};
-use editor::EditorPluginGroup;
+use clap::Parser;
+use editor::{
+    resources::{SceneFile, SceneFileRequest},
+    EditorPluginGroup,
+};
 use physics::{exit_on_esc_system, ExternalPluginGroup, GamePluginGroup};
 
+#[derive(clap::Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+pub struct CmdlineArgs {
+    // scene file (.ron) loaded into the editor at startup
+    #[clap(long)]
+    scene: Option<PathBuf>,
+}
+
 fn main() {
+    let args = CmdlineArgs::parse();
     let mut app = App::new();
 
     // app.add_plugins(DefaultPlugins);
@@ -38,6 +53,11 @@ fn main() {
     app.add_plugins(GamePluginGroup);
     app.add_plugins(EditorPluginGroup);
     app.add_plugins(ExternalPluginGroup);
+    if let Some(scene) = args.scene {
+        app.world_mut()
+            .resource_mut::<SceneFile>()
+            .request(SceneFileRequest::Open(scene));
+    }
     app.run();
 
     info!("after app.run");
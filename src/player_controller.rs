@@ -1,6 +1,9 @@
 use std::collections::VecDeque;
 
-use bevy::{input::mouse::MouseMotion, prelude::*};
+use bevy::{
+    input::{mouse::MouseMotion, InputSystem},
+    prelude::*,
+};
 use bevy_rapier3d::{parry::simba::scalar::SupersetOf, prelude::*};
 
 use shared::AppState;
@@ -19,6 +22,13 @@ pub struct PlayerState {
     pub rotation: Quat,
 
     pub gravity: Option<f32>,
+
+    // latest applied input. Movement and jump are held until the next input arrives.
+    pub input: PlayerInput,
+    pub grounded: bool,
+
+    // translation and rotation before the last tick, for interpolating the camera
+    pub previous: Option<(Vec3, Quat)>,
 }
 
 impl PlayerState {
@@ -99,12 +109,23 @@ pub fn player_controller_input_system(
     mut mouse_motion: EventReader<MouseMotion>,
     mut query: Query<(&mut PlayerInputSource, &mut PlayerInputQueue)>,
     app_state: Res<State<AppState>>,
+    egui_keyboard: Option<Res<editor::resources::EguiKeyboard>>,
 ) {
+    // keys typed into a text field are not for the player
+    let keyboard = !egui_keyboard.is_some_and(|egui_keyboard| egui_keyboard.0);
     for (mut input_source, mut queue) in &mut query {
-        let input_enabled =
-            *app_state.get() != AppState::Editor || key_codes.pressed(input_source.walk);
+        let input_enabled = keyboard
+            && (*app_state.get() != AppState::Editor || key_codes.pressed(input_source.walk));
 
         if !input_enabled {
+            // a neutral input, otherwise the apply system keeps moving with the last one that was queued
+            mouse_motion.clear();
+            let serial = input_source.next_serial;
+            input_source.next_serial += 1;
+            queue.queue.push_back(PlayerInput {
+                serial,
+                ..default()
+            });
             continue;
         }
 
@@ -195,158 +216,151 @@ pub fn hack_toggle_gravity_system(
         }
     }
 }
-pub fn player_controller_apply_system(
-    time: Res<Time>,
-    mut query: Query<(
-        &mut Transform,
-        &mut KinematicCharacterController,
-        &mut PlayerState,
-        &mut PlayerInputQueue,
-        Option<&KinematicCharacterControllerOutput>,
-    )>,
-) {
-    for (mut _transform, mut character_controller, mut player_state, mut input_queue, output) in
-        &mut query
-    {
-        // let (discard, apply) = if let Some(last_applied) = player_state.last_applied_serial {
-        //     if let Some((first_newer, _)) = input_queue
-        //         .queue
-        //         .iter()
-        //         .enumerate()
-        //         .find(|(i, input)| input.serial > last_applied)
-        //     {
-        //         (0..first_newer, first_newer..input_queue.queue.len())
-        //     } else {
-        //         // no newer input in the queue -> do nothing
-        //         (0..0, 0..0)
-        //     }
-        // } else {
-        //     (0..0, 0..input_queue.queue.len())
-        // };
-
-        // for input in input_queue.queue.drain(apply) {}
-
-        // input_queue.queue.drain(discard);
-
-        // FIXME: we can only apply one user input per frame due to KinematicCharacterController design
-        while input_queue.queue.len() > 1 {
-            info!("skipping user input: {:?}", input_queue.queue.front());
-            input_queue.queue.pop_front().unwrap();
-        }
-
-        if let Some(input) = input_queue.queue.pop_front() {
-            // FIXME: this wants to be a let chain...
-            match player_state.last_applied_serial {
-                Some(serial) if serial >= input.serial => {
-                    info!("input already applied!? {}", input.serial);
-                    continue;
-                }
-                _ => (),
-            }
 
-            player_state.last_applied_serial = Some(input.serial);
-            // if input.lon.abs() > 0.0 || input.lat.abs() > 0.0 {
-            //     info!("apply: {} {}", input.lon, input.lat);
-            // }
-            player_state.lon += input.lon;
-            player_state.lat += input.lat;
-
-            player_state.lat = player_state.lat.clamp(-85.0, 85.0);
-            while player_state.lon < 0.0 {
-                player_state.lon += 360.0;
-            }
-            while player_state.lon >= 360.0 {
-                player_state.lon -= 360.0;
-            }
-
-            let y_rot = player_state.get_y_rotation();
-            let dt = time.delta_seconds();
-            // const DT: f32 = 1.0 / 60.0; // fixed timestep
-
-            let forward = y_rot * (-Vec3::Z * input.forward);
-            let right = y_rot * (Vec3::X * input.right);
+// velocity of one tick from the latest input. `elapsed` is the time on the fixed clock, so the jump timing does not
+// depend on the frame rate either.
+pub fn integrate_player_velocity(player_state: &mut PlayerState, elapsed: f32, dt: f32) {
+    let PlayerInput {
+        forward,
+        right,
+        up,
+        jump,
+        ..
+    } = player_state.input;
+
+    let y_rot = player_state.get_y_rotation();
+
+    let forward = y_rot * (-Vec3::Z * forward);
+    let right = y_rot * (Vec3::X * right);
+
+    if forward.length() != 0.0 || right.length() != 0.0 {
+        player_state.velocity.x = forward.x + right.x;
+        player_state.velocity.z = forward.z + right.z;
+    } else {
+        const DECEL: f32 = 30.0;
+        let Vec2 { x, y: z } = apply_ground_friction(player_state.velocity.xz(), DECEL * dt);
+        player_state.velocity.x = x;
+        player_state.velocity.z = z;
+    }
 
-            if forward.length() != 0.0 || right.length() != 0.0 {
-                player_state.velocity.x = forward.x + right.x;
-                player_state.velocity.z = forward.z + right.z;
-            } else {
-                const DECEL: f32 = 30.0;
-                let Vec2 { x, y: z } =
-                    apply_ground_friction(player_state.velocity.xz(), DECEL * dt);
-                player_state.velocity.x = x;
-                player_state.velocity.z = z;
-            }
-            // * character_controller.custom_mass.unwrap_or(1.0);
-
-            // info!("{:?} {:?}", forward, right);
-            // transform.translation += forward;
-            // transform.translation += right;
-            let up = Vec3::ZERO;
-            // character_controller.max_slope_climb_angle = std::f32::consts::PI / 2.0;
-            let mut up = Vec3::ZERO;
-            if let Some(gravity) = player_state.gravity {
-                if let Some(output) = output {
-                    if output.grounded {
-                        if input.jump && time.elapsed_seconds() - player_state.last_jump >= 0.5 {
-                            player_state.velocity.y = 4.0;
-                            player_state.last_jump = time.elapsed_seconds();
-                            info!("jump");
-                        } else if time.elapsed_seconds() - player_state.last_jump >= 0.1 {
-                            player_state.velocity.y = 0.0;
-                        }
-                    } else {
-                        player_state.velocity.y += gravity * 2.0 * dt;
-                    }
-                }
-            } else {
-                player_state.velocity.y = input.up * dt;
+    if let Some(gravity) = player_state.gravity {
+        if player_state.grounded {
+            if jump && elapsed - player_state.last_jump >= 0.5 {
+                player_state.velocity.y = 4.0;
+                player_state.last_jump = elapsed;
+                info!("jump");
+            } else if elapsed - player_state.last_jump >= 0.1 {
+                player_state.velocity.y = 0.0;
             }
+        } else {
+            player_state.velocity.y += gravity * 2.0 * dt;
+        }
+    } else {
+        player_state.velocity.y = up * dt;
+    }
+}
 
-            if let Some(output) = output {
-                info!(
-                    "grounded: {}\tsliding: {}",
-                    output.grounded, output.is_sliding_down_slope
-                );
+// all inputs queued since the last tick, in serial order: look deltas and jumps accumulate, movement is taken from the
+// latest one.
+pub fn apply_queued_inputs(player_state: &mut PlayerState, input_queue: &mut PlayerInputQueue) {
+    // a jump pressed in any of the queued inputs counts
+    let mut jump = false;
+    for input in input_queue.queue.drain(..) {
+        // FIXME: this wants to be a let chain...
+        match player_state.last_applied_serial {
+            Some(serial) if serial >= input.serial => {
+                info!("input already applied!? {}", input.serial);
+                continue;
             }
-            player_state.velocity.y += up.y;
-            // player_state.velocity.y += Vec3::Y * player_state.z_velocity * dt;
-            // let up = if let Some(gravity) = player_state.gravity {
+            _ => (),
+        }
 
-            // }
+        player_state.last_applied_serial = Some(input.serial);
+        player_state.lon += input.lon;
+        player_state.lat += input.lat;
 
-            character_controller.translation = Some(player_state.velocity * dt);
-            debug!("want: {:?}", character_controller.translation);
-            character_controller.autostep = Some(CharacterAutostep::default());
+        player_state.lat = player_state.lat.clamp(-85.0, 85.0);
+        while player_state.lon < 0.0 {
+            player_state.lon += 360.0;
         }
-
-        assert!(input_queue.queue.is_empty());
+        while player_state.lon >= 360.0 {
+            player_state.lon -= 360.0;
+        }
+        jump |= input.jump;
+        player_state.input = PlayerInput { jump, ..input };
     }
 }
 
-fn player_controller_apply_output_system(
-    mut query: Query<
-        (
-            &mut Transform,
-            &KinematicCharacterController,
-            &KinematicCharacterControllerOutput,
-        ),
-        With<PlayerState>,
-    >,
+// one simulation step per FixedUpdate tick, with the inputs of apply_queued_inputs. The character is moved with
+// RapierContext::move_shape directly, KinematicCharacterController only holds the settings (it would only apply a
+// single translation per physics step). move_shape also pushes the dynamic bodies it hits, that is why it needs the
+// context mutably.
+pub fn player_controller_apply_system(
+    time: Res<Time>,
+    mut rapier_context: ResMut<RapierContext>,
+    mut query: Query<(
+        Entity,
+        &mut Transform,
+        &KinematicCharacterController,
+        &Collider,
+        &mut PlayerState,
+        &mut PlayerInputQueue,
+    )>,
 ) {
-    for (mut transform, _ck, controller_output) in &mut query {
-        // info!("{cko:?}");
-        transform.translation += controller_output.effective_translation;
-        debug!("got: {:?}", controller_output.effective_translation);
-
-        debug!("collisions: {:?}", controller_output.collisions);
-
-        // for c in &cko.collisions {
-        //     info!("{:?}", c.toi.normal2);
-        // }
+    for (
+        entity,
+        mut transform,
+        character_controller,
+        collider,
+        mut player_state,
+        mut input_queue,
+    ) in &mut query
+    {
+        player_state.previous = Some((transform.translation, player_state.get_rotation()));
+        apply_queued_inputs(&mut player_state, &mut input_queue);
+
+        let dt = time.delta_seconds();
+        integrate_player_velocity(&mut player_state, time.elapsed_seconds(), dt);
+
+        let output = rapier_context.move_shape(
+            player_state.velocity * dt,
+            collider,
+            transform.translation,
+            transform.rotation,
+            character_controller.custom_mass.unwrap_or(1.0),
+            &MoveShapeOptions {
+                up: character_controller.up,
+                offset: character_controller.offset,
+                slide: character_controller.slide,
+                autostep: character_controller.autostep,
+                max_slope_climb_angle: character_controller.max_slope_climb_angle,
+                min_slope_slide_angle: character_controller.min_slope_slide_angle,
+                // impulses use `custom_mass` as the mass of the character
+                apply_impulse_to_dynamic_bodies: character_controller
+                    .apply_impulse_to_dynamic_bodies,
+                snap_to_ground: character_controller.snap_to_ground,
+                ..default()
+            },
+            QueryFilter::default().exclude_collider(entity),
+            |_| (),
+        );
+        debug!(
+            "want: {:?} got: {:?}",
+            player_state.velocity * dt,
+            output.effective_translation
+        );
+        debug!(
+            "grounded: {}\tsliding: {}",
+            output.grounded, output.is_sliding_down_slope
+        );
+
+        transform.translation += output.effective_translation;
+        player_state.grounded = output.grounded;
     }
 }
 
 fn sync_player_camera_system(
+    fixed_time: Res<Time<Fixed>>,
     player_query: Query<(&Transform, &PlayerState), Without<PlayerCamera>>,
     mut camera_query: Query<&mut Transform, With<PlayerCamera>>,
 ) {
@@ -357,8 +371,14 @@ fn sync_player_camera_system(
         return;
     };
 
-    camera.translation = player.translation + Vec3::Y * 0.85;
-    camera.rotation = player_state.get_rotation();
+    // the player is only moved on ticks, interpolate between the last two for rendering
+    let rotation = player_state.get_rotation();
+    let (previous_translation, previous_rotation) = player_state
+        .previous
+        .unwrap_or((player.translation, rotation));
+    let s = fixed_time.overstep_fraction();
+    camera.translation = previous_translation.lerp(player.translation, s) + Vec3::Y * 0.85;
+    camera.rotation = previous_rotation.slerp(rotation, s);
 }
 
 #[derive(Bundle)]
@@ -399,26 +419,177 @@ impl Default for PlayerControllerBundle {
     }
 }
 
-pub struct PlayerControllerPlugin;
+pub struct PlayerControllerPlugin {
+    // player simulation ticks per second. The ticks are the FixedUpdate ticks, so this sets the fixed timestep of the
+    // whole app. None keeps the timestep the app is configured with.
+    pub tick_rate: Option<f64>,
+}
+
+impl Default for PlayerControllerPlugin {
+    fn default() -> Self {
+        Self {
+            tick_rate: Some(60.0),
+        }
+    }
+}
 
 impl Plugin for PlayerControllerPlugin {
     fn build(&self, app: &mut App) {
         // app.add_system_set(
         //     SystemSet::on_update(AppState::InGame).with_system(player_controller_input_system),
         // );
-        app.add_systems(Update, player_controller_input_system);
-        app.add_systems(
-            Update,
-            player_controller_apply_system.after(player_controller_input_system),
-        )
-        .add_systems(
-            Update,
-            player_controller_apply_output_system.before(player_controller_apply_system),
-        )
-        .add_systems(
-            Update,
-            sync_player_camera_system.after(player_controller_apply_output_system),
-        )
-        .add_systems(Update, hack_toggle_gravity_system);
+        // replaces Time<Fixed> for everything that runs in FixedUpdate, not only for the player
+        if let Some(tick_rate) = self.tick_rate {
+            app.insert_resource(Time::<Fixed>::from_hz(tick_rate));
+        }
+        // inputs are queued before the FixedUpdate ticks of the same frame run
+        app.add_systems(PreUpdate, player_controller_input_system.after(InputSystem))
+            .add_systems(FixedUpdate, player_controller_apply_system)
+            .add_systems(Update, sync_player_camera_system)
+            .add_systems(Update, hack_toggle_gravity_system);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use bevy::{
+        prelude::*,
+        time::{TimePlugin, TimeUpdateStrategy},
+    };
+
+    use super::{
+        apply_queued_inputs, integrate_player_velocity, PlayerInput, PlayerInputQueue, PlayerState,
+    };
+
+    // frames left that queue an input, and the serial of the next one
+    #[derive(Resource)]
+    struct Frames {
+        fps: f32,
+        left: usize,
+        serial: u32,
+    }
+
+    // horizontal speed and height after every tick
+    #[derive(Resource, Default)]
+    struct Ticks(Vec<Vec2>);
+
+    // one input per frame like player_controller_input_system: running forward, jumping and turning at a constant
+    // speed, so the look deltas of one frame depend on the frame rate
+    fn input_system(mut frames: ResMut<Frames>, mut query: Query<&mut PlayerInputQueue>) {
+        if frames.left == 0 {
+            return;
+        }
+        frames.left -= 1;
+        for mut queue in &mut query {
+            queue.queue.push_back(PlayerInput {
+                serial: frames.serial,
+                forward: 6.0,
+                jump: true,
+                lon: 90.0 / frames.fps,
+                lat: -30.0 / frames.fps,
+                ..default()
+            });
+        }
+        frames.serial += 1;
+    }
+
+    // flat floor at y = 0 instead of the collision query of move_shape
+    fn tick_system(
+        time: Res<Time>,
+        mut query: Query<(&mut Transform, &mut PlayerState, &mut PlayerInputQueue)>,
+        mut ticks: ResMut<Ticks>,
+    ) {
+        for (mut transform, mut player_state, mut input_queue) in &mut query {
+            apply_queued_inputs(&mut player_state, &mut input_queue);
+            let dt = time.delta_seconds();
+            integrate_player_velocity(&mut player_state, time.elapsed_seconds(), dt);
+            transform.translation += player_state.velocity * dt;
+            player_state.grounded = transform.translation.y <= 0.0;
+            transform.translation.y = transform.translation.y.max(0.0);
+            ticks.0.push(Vec2::new(
+                player_state.velocity.xz().length(),
+                transform.translation.y,
+            ));
+        }
+    }
+
+    // ticks and the final player state, queueing inputs for two seconds of frames at `fps`
+    fn simulate(fps: f64) -> (Vec<Vec2>, PlayerState, usize) {
+        let mut app = App::new();
+        app.add_plugins(TimePlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+                1.0 / fps,
+            )))
+            .insert_resource(Time::<Fixed>::from_hz(60.0))
+            .insert_resource(Frames {
+                fps: fps as f32,
+                left: (2.0 * fps) as usize,
+                serial: 0,
+            })
+            .init_resource::<Ticks>()
+            .add_systems(PreUpdate, input_system)
+            .add_systems(FixedUpdate, tick_system);
+        let player = app
+            .world_mut()
+            .spawn((
+                Transform::default(),
+                PlayerState {
+                    gravity: Some(-9.81),
+                    grounded: true,
+                    ..default()
+                },
+                PlayerInputQueue::default(),
+            ))
+            .id();
+        // some frames more for the inputs still waiting for a tick
+        for _ in 0..(2.2 * fps) as usize {
+            app.update();
+        }
+        let queued = app
+            .world()
+            .get::<PlayerInputQueue>(player)
+            .unwrap()
+            .queue
+            .len();
+        let player_state = app
+            .world_mut()
+            .entity_mut(player)
+            .take::<PlayerState>()
+            .unwrap();
+        let ticks = app.world_mut().remove_resource::<Ticks>().unwrap().0;
+        (ticks, player_state, queued)
+    }
+
+    #[test]
+    fn test_frame_rate_independent() {
+        let (slow, slow_state, slow_queued) = simulate(30.0);
+        let (fast, fast_state, fast_queued) = simulate(240.0);
+
+        // every queued input was applied
+        assert_eq!(slow_queued, 0);
+        assert_eq!(fast_queued, 0);
+        assert_eq!(slow_state.last_applied_serial, Some(59));
+        assert_eq!(fast_state.last_applied_serial, Some(479));
+        assert_eq!((slow_state.lon, slow_state.lat), (180.0, -60.0));
+        assert_eq!(
+            (fast_state.lon, fast_state.lat),
+            (slow_state.lon, slow_state.lat)
+        );
+
+        // the direction differs by rounding with the look deltas, the speed and the jumps do not
+        let n = slow.len().min(fast.len());
+        assert!(n >= 120, "{n} ticks");
+        let height = |ticks: &[Vec2]| ticks[..n].iter().map(|tick| tick.y).collect::<Vec<_>>();
+        assert_eq!(height(&slow), height(&fast));
+        assert!(slow[..n]
+            .iter()
+            .chain(&fast[..n])
+            .all(|tick| (tick.x - 6.0).abs() < 1e-3));
+
+        // it did jump
+        let height = slow[..n].iter().map(|tick| tick.y).fold(0.0, f32::max);
+        assert!(height > 0.3, "jump height {height}");
     }
 }